@@ -15,10 +15,11 @@
 // You should have received a copy of the GNU General Public License
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
-const weak = require('weak');
+const EventEmitter = require('events');
 const fastcall = require('fastcall');
 const ref = fastcall.ref;
 const StructType = fastcall.StructType;
+const ArrayType = fastcall.ArrayType;
 
 const os = require('os');
 const path = require('path');
@@ -33,47 +34,598 @@ const LIB_FILENAME = {
 
 const safe_thing_lib = new fastcall.Library(path.join(dir, LIB_FILENAME));
 
-// Definition of the ThingAttr
-safe_thing_lib.struct({
-  ThingAttr: {
-    attr: 'string',
-    value: 'string'
-  }
+// Values of the constants defined in the C interface (see ffi/include/safe_thing.h)
+const AccessType = {
+  Thing: 0,
+  Owner: 1,
+  Group: 2,
+  All: 3
+};
+
+const Status = ['Unknown', 'NonConnected', 'Connected', 'Published', 'Disabled'];
+
+const FilterOperator = {
+  Any: 0,
+  Equal: 1,
+  NotEqual: 2,
+  LessThan: 3,
+  GreaterThan: 4
+};
+
+const ValueKind = {
+  Number: 0,
+  Bool: 1,
+  String: 2,
+  Enum: 3
+};
+
+// Interval to poll the events queued by the library, in milliseconds
+const EVENTS_POLL_INTERVAL = 100;
+
+const ACTION_REQUEST_DONE_STATE = 'Done';
+const ACTION_REQUEST_REJECTED_STATE = 'Rejected';
+const ACTION_REQUEST_FAILED_STATE = 'Failed';
+
+// Definition of the structs and arrays passed through the C interface
+const StringArray = new ArrayType('string');
+
+const FfiThingAttr = new StructType({
+  attr: 'string',
+  value: 'string',
+  is_dynamic: 'bool',
+  access: 'int32'
+});
+const FfiThingAttrArray = new ArrayType(FfiThingAttr);
+
+const FfiTopic = new StructType({
+  name: 'string',
+  access: 'int32',
+  groups: 'pointer',
+  groups_len: 'size_t'
+});
+const FfiTopicArray = new ArrayType(FfiTopic);
+
+const FfiValueType = new StructType({
+  kind: 'int32',
+  has_min: 'bool',
+  min: 'double',
+  has_max: 'bool',
+  max: 'double',
+  options: 'pointer',
+  options_len: 'size_t'
+});
+const FfiValueTypeArray = new ArrayType(FfiValueType);
+
+const FfiActionParam = new StructType({
+  name: 'string',
+  value_type: FfiValueType,
+  default_value: 'pointer'
+});
+const FfiActionParamArray = new ArrayType(FfiActionParam);
+
+const FfiActionDef = new StructType({
+  name: 'string',
+  access: 'int32',
+  params: 'pointer',
+  params_len: 'size_t',
+  output: 'pointer',
+  groups: 'pointer',
+  groups_len: 'size_t'
+});
+const FfiActionDefArray = new ArrayType(FfiActionDef);
+
+const FfiGroup = new StructType({
+  name: 'string',
+  members: 'pointer',
+  members_len: 'size_t'
 });
-safe_thing_lib.array({ThingAttrArray: 'ThingAttr'});
+const FfiGroupArray = new ArrayType(FfiGroup);
 
-safe_thing_lib.function({safe_thing_new: ['pointer', ['string'] ]})
-          .function({safe_thing_register_thing: [ref.types.int, ['pointer', 'ThingAttrArray', ref.types.size_t] ]})
-          .function({safe_thing_publish_thing: [ref.types.int, ['pointer', 'string'] ]})
-          .function({safe_thing_delete: ['void', ['pointer'] ]});
+// Callbacks invoked by the library, always from the main thread as the SAFEthings are
+// created with safe_thing_new_polled, and the events are polled from the event loop
+safe_thing_lib.callback({SubsNotifCb: ['void', ['pointer', 'pointer', 'string', 'string', 'string', 'uint64']]})
+          .callback({ActionReqCb: ['void', ['pointer', 'pointer', 'uint64', 'string', 'string', 'pointer', 'size_t']]})
+          .callback({PolledActionReqStateCb: ['void', ['pointer', 'uint64', 'string', 'string', 'string']]})
+          .callback({ThingAttrsCb: ['void', ['pointer', 'pointer', 'size_t']]})
+          .callback({TopicsCb: ['void', ['pointer', 'pointer', 'size_t']]})
+          .callback({ActionsCb: ['void', ['pointer', 'pointer', 'size_t']]})
+          .callback({GroupsCb: ['void', ['pointer', 'pointer', 'size_t']]})
+          .callback({StateCb: ['void', ['pointer', 'string']]});
 
-module.exports.newSAFEoT = function(thing_id)  {
-	let handle = safe_thing_lib.interface.safe_thing_new(thing_id);
+safe_thing_lib.function({safe_thing_last_error_message: ['string', []]})
+          .function({safe_thing_new_polled: ['int32', ['string', 'string', 'pointer']]})
+          .function({safe_thing_poll_events: ['int32', ['pointer', 'pointer', 'SubsNotifCb', 'ActionReqCb', 'PolledActionReqStateCb']]})
+          .function({safe_thing_register: ['int32', ['pointer', 'pointer', 'size_t', 'pointer', 'size_t', 'pointer', 'size_t']]})
+          .function({safe_thing_register_private: ['int32', ['pointer', 'pointer', 'size_t', 'pointer', 'size_t', 'pointer', 'size_t', 'string']]})
+          .function({safe_thing_gen_enc_info: ['int32', ['pointer', 'pointer', 'StateCb']]})
+          .function({safe_thing_set_thing_enc_info: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_get_thing_enc_info: ['int32', ['pointer', 'string', 'pointer', 'StateCb']]})
+          .function({safe_thing_publish: ['int32', ['pointer']]})
+          .function({safe_thing_disable: ['int32', ['pointer']]})
+          .function({safe_thing_enable: ['int32', ['pointer']]})
+          .function({safe_thing_unregister: ['int32', ['pointer']]})
+          .function({safe_thing_sign_key: ['int32', ['pointer', 'pointer', 'StateCb']]})
+          .function({safe_thing_authorise: ['int32', ['pointer', 'string', 'int32']]})
+          .function({safe_thing_revoke: ['int32', ['pointer', 'string']]})
+          .function({safe_thing_status: ['int32', ['pointer', 'pointer']]})
+          .function({safe_thing_get_thing_status: ['int32', ['pointer', 'string', 'pointer']]})
+          .function({safe_thing_get_thing_attrs: ['int32', ['pointer', 'string', 'pointer', 'ThingAttrsCb']]})
+          .function({safe_thing_set_attr_value: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_set_claim_code: ['int32', ['pointer', 'string']]})
+          .function({safe_thing_claim: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_get_thing_owner: ['int32', ['pointer', 'string', 'pointer', 'StateCb']]})
+          .function({safe_thing_create_group: ['int32', ['pointer', 'string']]})
+          .function({safe_thing_delete_group: ['int32', ['pointer', 'string']]})
+          .function({safe_thing_add_group_member: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_remove_group_member: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_get_thing_groups: ['int32', ['pointer', 'string', 'pointer', 'GroupsCb']]})
+          .function({safe_thing_get_thing_topics: ['int32', ['pointer', 'string', 'pointer', 'TopicsCb']]})
+          .function({safe_thing_get_thing_actions: ['int32', ['pointer', 'string', 'pointer', 'ActionsCb']]})
+          .function({safe_thing_subscribe_to_topic: ['int32', ['pointer', 'string', 'string', 'int32', 'string']]})
+          .function({safe_thing_subscribe_to_attr: ['int32', ['pointer', 'string', 'string', 'int32', 'string']]})
+          .function({safe_thing_notify: ['int32', ['pointer', 'string', 'string']]})
+          .function({safe_thing_action_request_polled: ['int32', ['pointer', 'string', 'string', 'pointer', 'size_t', 'pointer']]})
+          .function({safe_thing_get_action_request_state: ['int32', ['pointer', 'string', 'uint64', 'pointer', 'StateCb']]})
+          .function({safe_thing_action_request_reply: ['int32', ['pointer', 'uint64', 'string', 'string']]})
+          .function({safe_thing_update_action_request_state: ['int32', ['pointer', 'uint64', 'string']]})
+          .function({safe_thing_shutdown: ['int32', ['pointer', 'bool']]})
+          .function({safe_thing_delete: ['void', ['pointer']]});
+
+const lib = safe_thing_lib.interface;
+
+// The events of the instances are polled until they are explicitly destroyed,
+// thus they are kept alive until then
+const instances = new Set();
+
+// Throw an Error with the last error message if the call to the library failed
+const checkResult = (res) => {
+  if (res !== 0) {
+    const err = new Error(lib.safe_thing_last_error_message());
+    err.code = res;
+    throw err;
+  }
+};
+
+// Read an array of structs of the given type returned by the library
+const readArray = (arrayType, ptr, len) => {
+  if (len === 0) return [];
+  const buf = ref.reinterpret(ptr, len * arrayType.type.size);
+  return Array.from(new arrayType(buf, len));
+};
 
-  let safe_thing = new SAFEthing(handle);
-  let ref = weak(safe_thing, function () {
-    console.log('"obj" has been garbage collected!')
-    safe_thing.destroy();
+const readStrings = (ptr, len) => readArray(StringArray, ptr, len);
+
+// The params of an action can also be just names, for parameters of type string
+const actionParams = (action) => (action.params || []).map((p) => (
+  typeof p === 'string' ? { name: p, type: { kind: ValueKind.String } } : p
+));
+
+// String representation of a value as expected by the library
+const toValueString = (value) => String(value);
+
+// Convert a value coerced by the library into its JS type
+const fromValueString = (type, value) => {
+  switch (type.kind) {
+    case ValueKind.Number: return Number(value);
+    case ValueKind.Bool: return value === 'true';
+    default: return value;
+  }
+};
+
+// Build the struct of a value type, the buffers it points to are added to keepAlive
+const toFfiValueType = (type, keepAlive) => {
+  const options = new StringArray(type.options || []);
+  keepAlive.push(options);
+  return new FfiValueType({
+    kind: type.kind,
+    has_min: type.min !== undefined && type.min !== null,
+    min: type.min || 0,
+    has_max: type.max !== undefined && type.max !== null,
+    max: type.max || 0,
+    options: options.buffer,
+    options_len: options.length
   });
+};
 
-	return ref;
+const fromFfiValueType = (ffiType) => {
+  const type = { kind: ffiType.kind };
+  if (ffiType.has_min) type.min = ffiType.min;
+  if (ffiType.has_max) type.max = ffiType.max;
+  if (ffiType.kind === ValueKind.Enum) type.options = readStrings(ffiType.options, ffiType.options_len);
+  return type;
 };
 
-class SAFEthing {
-  constructor(handle) {
-    this.handle = handle;
+// Request ids and timestamps are 64 bits integers, they are kept as strings
+// to not lose precision
+const toId = (value) => value.toString();
+
+// Key of the callbacks registered for a subscription
+const subsKey = (thingId, topic) => `${thingId}/${topic}`;
+
+module.exports.AccessType = AccessType;
+module.exports.FilterOperator = FilterOperator;
+module.exports.ValueKind = ValueKind;
+
+module.exports.newSAFEthing = function(thingId, authUri) {
+  const safeThing = new SAFEthing(thingId, authUri);
+  instances.add(safeThing);
+  return safeThing;
+};
+
+// Kept for backward compatibility
+module.exports.newSAFEoT = module.exports.newSAFEthing;
+
+/**
+ * A SAFEthing, it emits the following events:
+ * - 'notification' (thingId, topic, data, timestamp): for any of the subscriptions made
+ * - 'actionRequest' (requestId, thingId, action, args, reply): when an action request is received,
+ *   the listener calls reply(err, result) once to set the outcome of the action, either right away
+ *   or later, e.g. when a Promise is settled. No other action requests are received until then.
+ *   The request fails if there is no listener. The thingId of the requester was verified
+ *   against the signature of the request.
+ * The events are polled from the event loop, thus the listeners are always invoked from it.
+ */
+class SAFEthing extends EventEmitter {
+  constructor(thingId, authUri) {
+    super();
+    this.thingId = thingId;
+    this.subscriptions = new Map();
+    this.requestsCallbacks = new Map();
+    this.actions = new Map();
+
+    this.notifsCb = lib.SubsNotifCb((userData, handle, thingId, topic, data, timestamp) => {
+      const cb = this.subscriptions.get(subsKey(thingId, topic));
+      if (cb) cb(thingId, topic, data, toId(timestamp));
+      this.emit('notification', thingId, topic, data, toId(timestamp));
+    });
+
+    this.actionReqCb = lib.ActionReqCb((userData, handle, requestId, thingId, action, args, argsLen) => {
+      // the arguments are converted according to the types of the action's parameters
+      const params = this.actions.get(action) || [];
+      const values = readStrings(args, argsLen).map((arg, i) => (
+        params[i] ? fromValueString(params[i].type, arg) : arg
+      ));
+      let replied = false;
+      const reply = (err, result) => {
+        if (replied || !this.handle) return;
+        replied = true;
+        const error = err ? (err.message || String(err)) : null;
+        const output = (result === undefined || result === null) ? null : toValueString(result);
+        checkResult(lib.safe_thing_action_request_reply(this.handle, requestId, output, error));
+      };
+      if (this.listenerCount('actionRequest') === 0) {
+        return reply(new Error('No listener for the action requests'));
+      }
+      try {
+        this.emit('actionRequest', toId(requestId), thingId, action, values, reply);
+      } catch (err) {
+        // the request fails, and the error is thrown once we are out of the library's call
+        reply(err);
+        process.nextTick(() => { throw err; });
+      }
+    });
+
+    this.stateCb = lib.PolledActionReqStateCb((userData, requestId, state, resultJson, error) => {
+      const request = this.requestsCallbacks.get(toId(requestId));
+      if (request) request(state, resultJson ? JSON.parse(resultJson) : undefined, error || undefined);
+    });
+
+    const handlePtr = ref.alloc('pointer');
+    checkResult(lib.safe_thing_new_polled(thingId, authUri || '', handlePtr));
+    this.handle = handlePtr.deref();
+    this.poller = setInterval(() => this.pollEvents(), EVENTS_POLL_INTERVAL);
+  };
+
+  // Invoke the listeners with the events received by the library so far, it's done
+  // periodically but it can also be called to get them right away
+  pollEvents() {
+    if (!this.handle) return;
+    checkResult(lib.safe_thing_poll_events(this.handle, ref.NULL, this.notifsCb, this.actionReqCb, this.stateCb));
+  };
+
+  // attrs: [{attr, value, isDynamic, access}], topics: [{name, access}],
+  // actions: [{name, access, params, output}], where each param is either just a name,
+  // or {name, type, default}, and the types are {kind, min, max, options}.
+  // It's registered as private and encrypted if the encryption info is provided
+  register(attrs, topics, actions, encInfo) {
+    attrs = attrs || [];
+    topics = topics || [];
+    actions = actions || [];
+
+    const ffiAttrs = new FfiThingAttrArray(attrs.map((a) => new FfiThingAttr({
+      attr: a.attr,
+      value: a.value,
+      is_dynamic: !!a.isDynamic,
+      access: a.access === undefined ? AccessType.All : a.access
+    })));
+    // all the buffers the topics and actions point to need to be alive until the function returns
+    const keepAlive = [];
+    const ffiTopics = new FfiTopicArray(topics.map((t) => {
+      const groups = new StringArray(t.groups || []);
+      keepAlive.push(groups);
+      return new FfiTopic({
+        name: t.name,
+        access: t.access,
+        groups: groups.buffer,
+        groups_len: groups.length
+      });
+    }));
+    const ffiActions = new FfiActionDefArray(actions.map((a) => {
+      const params = new FfiActionParamArray(actionParams(a).map((p) => {
+        const defaultValue = p.default === undefined || p.default === null
+          ? ref.NULL : ref.allocCString(toValueString(p.default));
+        keepAlive.push(defaultValue);
+        return new FfiActionParam({
+          name: p.name,
+          value_type: toFfiValueType(p.type, keepAlive),
+          default_value: defaultValue
+        });
+      }));
+      keepAlive.push(params);
+      let output = ref.NULL;
+      if (a.output) {
+        output = new FfiValueTypeArray([toFfiValueType(a.output, keepAlive)]);
+        keepAlive.push(output);
+      }
+      const groups = new StringArray(a.groups || []);
+      keepAlive.push(groups);
+      return new FfiActionDef({
+        name: a.name,
+        access: a.access,
+        params: params.buffer,
+        params_len: params.length,
+        output: a.output ? output.buffer : ref.NULL,
+        groups: groups.buffer,
+        groups_len: groups.length
+      });
+    }));
+
+    if (encInfo) {
+      checkResult(lib.safe_thing_register_private(this.handle,
+                                                  ffiAttrs.buffer, attrs.length,
+                                                  ffiTopics.buffer, topics.length,
+                                                  ffiActions.buffer, actions.length,
+                                                  encInfo));
+    } else {
+      checkResult(lib.safe_thing_register(this.handle,
+                                          ffiAttrs.buffer, attrs.length,
+                                          ffiTopics.buffer, topics.length,
+                                          ffiActions.buffer, actions.length));
+    }
+    // keep the definitions to convert the arguments of the action requests received
+    this.actions = new Map(actions.map((a) => [a.name, actionParams(a)]));
+  };
+
+  genEncInfo() {
+    let encInfo;
+    checkResult(lib.safe_thing_gen_enc_info(this.handle, ref.NULL, (userData, e) => { encInfo = e; }));
+    return encInfo;
+  };
+
+  setThingEncInfo(thingId, encInfo) {
+    checkResult(lib.safe_thing_set_thing_enc_info(this.handle, thingId, encInfo));
+  };
+
+  // it returns null if the encryption info of the SAFEthing is not known
+  getThingEncInfo(thingId) {
+    let encInfo;
+    checkResult(lib.safe_thing_get_thing_enc_info(this.handle, thingId, ref.NULL, (userData, e) => { encInfo = e; }));
+    return encInfo;
+  };
+
+  publish() {
+    checkResult(lib.safe_thing_publish(this.handle));
+  };
+
+  disable() {
+    checkResult(lib.safe_thing_disable(this.handle));
+  };
+
+  enable() {
+    checkResult(lib.safe_thing_enable(this.handle));
+  };
+
+  unregister() {
+    checkResult(lib.safe_thing_unregister(this.handle));
+  };
+
+  signKey() {
+    let signKey;
+    checkResult(lib.safe_thing_sign_key(this.handle, ref.NULL, (userData, k) => { signKey = k; }));
+    return signKey;
+  };
+
+  authorise(signKey, access) {
+    checkResult(lib.safe_thing_authorise(this.handle, signKey, access));
+  };
+
+  revoke(signKey) {
+    checkResult(lib.safe_thing_revoke(this.handle, signKey));
+  };
+
+  setClaimCode(code) {
+    checkResult(lib.safe_thing_set_claim_code(this.handle, code));
+  };
+
+  // it blocks until the claim is accepted or rejected
+  claim(thingId, code) {
+    checkResult(lib.safe_thing_claim(this.handle, thingId, code));
+  };
+
+  // it returns null if the SAFEthing wasn't claimed
+  getThingOwner(thingId) {
+    let owner;
+    checkResult(lib.safe_thing_get_thing_owner(this.handle, thingId, ref.NULL, (userData, o) => { owner = o; }));
+    return owner;
+  };
+
+  createGroup(group) {
+    checkResult(lib.safe_thing_create_group(this.handle, group));
+  };
+
+  deleteGroup(group) {
+    checkResult(lib.safe_thing_delete_group(this.handle, group));
+  };
+
+  // the member is either the ID of a SAFEthing or a public signing key
+  addGroupMember(group, member) {
+    checkResult(lib.safe_thing_add_group_member(this.handle, group, member));
+  };
+
+  removeGroupMember(group, member) {
+    checkResult(lib.safe_thing_remove_group_member(this.handle, group, member));
+  };
+
+  // it returns an object with the list of members of each group
+  getThingGroups(thingId) {
+    const groups = {};
+    checkResult(lib.safe_thing_get_thing_groups(this.handle, thingId, ref.NULL, (userData, ptr, len) => {
+      readArray(FfiGroupArray, ptr, len).forEach((g) => {
+        groups[g.name] = readStrings(g.members, g.members_len);
+      });
+    }));
+    return groups;
+  };
+
+  status() {
+    const statusPtr = ref.alloc('int32');
+    checkResult(lib.safe_thing_status(this.handle, statusPtr));
+    return Status[statusPtr.deref()];
+  };
+
+  getThingStatus(thingId) {
+    const statusPtr = ref.alloc('int32');
+    checkResult(lib.safe_thing_get_thing_status(this.handle, thingId, statusPtr));
+    return Status[statusPtr.deref()];
+  };
+
+  getThingAttrs(thingId) {
+    let attrs = [];
+    checkResult(lib.safe_thing_get_thing_attrs(this.handle, thingId, ref.NULL, (userData, ptr, len) => {
+      attrs = readArray(FfiThingAttrArray, ptr, len).map((a) => ({
+        attr: a.attr,
+        value: a.value,
+        isDynamic: a.is_dynamic,
+        access: a.access
+      }));
+    }));
+    return attrs;
+  };
+
+  setAttrValue(attr, value) {
+    checkResult(lib.safe_thing_set_attr_value(this.handle, attr, value));
+  };
+
+  getThingTopics(thingId) {
+    let topics = [];
+    checkResult(lib.safe_thing_get_thing_topics(this.handle, thingId, ref.NULL, (userData, ptr, len) => {
+      topics = readArray(FfiTopicArray, ptr, len).map((t) => ({
+        name: t.name,
+        access: t.access,
+        groups: readStrings(t.groups, t.groups_len)
+      }));
+    }));
+    return topics;
+  };
+
+  getThingActions(thingId) {
+    let actions = [];
+    checkResult(lib.safe_thing_get_thing_actions(this.handle, thingId, ref.NULL, (userData, ptr, len) => {
+      actions = readArray(FfiActionDefArray, ptr, len).map((a) => {
+        const action = {
+          name: a.name,
+          access: a.access,
+          params: readArray(FfiActionParamArray, a.params, a.params_len).map((p) => {
+            const param = { name: p.name, type: fromFfiValueType(p.value_type) };
+            if (!ref.isNull(p.default_value)) param.default = ref.readCString(p.default_value, 0);
+            return param;
+          }),
+          groups: readStrings(a.groups, a.groups_len)
+        };
+        if (!ref.isNull(a.output)) {
+          action.output = fromFfiValueType(readArray(FfiValueTypeArray, a.output, 1)[0]);
+        }
+        return action;
+      });
+    }));
+    return actions;
+  };
+
+  // The optional callback is invoked with (thingId, topic, data, timestamp),
+  // a 'notification' event is emitted in any case
+  subscribeToTopic(thingId, topic, filterOp, filterValue, cb) {
+    checkResult(lib.safe_thing_subscribe_to_topic(this.handle, thingId, topic,
+                                                  filterOp || FilterOperator.Any, filterValue || ''));
+    if (cb) this.subscriptions.set(subsKey(thingId, topic), cb);
+  };
+
+  // The optional callback is invoked with (thingId, attr, value, timestamp),
+  // a 'notification' event is emitted in any case
+  subscribeToAttr(thingId, attr, filterOp, filterValue, cb) {
+    checkResult(lib.safe_thing_subscribe_to_attr(this.handle, thingId, attr,
+                                                 filterOp || FilterOperator.Any, filterValue || ''));
+    if (cb) this.subscriptions.set(subsKey(thingId, attr), cb);
+  };
+
+  notify(topic, data) {
+    checkResult(lib.safe_thing_notify(this.handle, topic, data || ''));
+  };
+
+  // Returns a Promise which is resolved with {requestId, result} once the action
+  // request reaches the "Done" state, or rejected with the error reported if it's
+  // either "Rejected" or "Failed".
+  // The optional callback is invoked with (state, result, error) upon every change in the
+  // state of the request, the result being the output of the action if it has any.
+  actionRequest(thingId, action, args, cb) {
+    return new Promise((resolve, reject) => {
+      const requestIdPtr = ref.alloc('uint64');
+      const ffiArgs = new StringArray((args || []).map(toValueString));
+      try {
+        checkResult(lib.safe_thing_action_request_polled(this.handle, thingId, action,
+                                                         ffiArgs.buffer, ffiArgs.length,
+                                                         requestIdPtr));
+      } catch (err) {
+        return reject(err);
+      }
+      // the changes in its state are only polled once the request was sent
+      const requestId = toId(requestIdPtr.deref());
+      this.requestsCallbacks.set(requestId, (state, result, error) => {
+        if (cb) cb(state, result, error);
+        if (state !== ACTION_REQUEST_DONE_STATE && state !== ACTION_REQUEST_REJECTED_STATE &&
+            state !== ACTION_REQUEST_FAILED_STATE) {
+          return;
+        }
+
+        this.requestsCallbacks.delete(requestId);
+        if (state === ACTION_REQUEST_DONE_STATE) {
+          resolve({ requestId, result });
+        } else {
+          reject(new Error(`Action request ${requestId} to ${thingId} is ${state}: ${error}`));
+        }
+      });
+    });
+  };
+
+  getActionRequestState(thingId, requestId) {
+    let state;
+    checkResult(lib.safe_thing_get_action_request_state(this.handle, thingId, requestId,
+                                                        ref.NULL, (userData, s) => { state = s; }));
+    return state;
   };
 
-  register_thing(attrs) {
-    return safe_thing_lib.interface.safe_thing_register_thing(this.handle, attrs, attrs.length);
+  updateActionRequestState(requestId, newState) {
+    checkResult(lib.safe_thing_update_action_request_state(this.handle, requestId, newState));
   };
 
-  publish_thing(thing_id) {
-    return safe_thing_lib.interface.safe_thing_publish_thing(this.handle, thing_id);
+  shutdown(disable) {
+    checkResult(lib.safe_thing_shutdown(this.handle, !!disable));
   };
 
   destroy() {
-    safe_thing_lib.interface.safe_thing_delete(this.handle);
+    if (!this.handle) return;
+    clearInterval(this.poller);
+    lib.safe_thing_delete(this.handle);
     this.handle = null;
+    instances.delete(this);
   };
 };
@@ -0,0 +1,899 @@
+# Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+#
+# This file is part of the SAFEthing Framework.
+#
+# The SAFEthing Framework is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# The SAFEthing Framework is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+"""Python binding for the SAFEthing framework library.
+
+It loads the `safe_thing` dynamic library built from the `ffi` crate, which is
+looked up in the path set in the `SAFE_THING_LIB` environment variable, then
+in this package's folder, and finally in the system's libraries paths.
+"""
+
+import ctypes
+import ctypes.util
+import json
+import os
+import sys
+import threading
+from collections import namedtuple
+from enum import IntEnum
+
+__all__ = [
+    "SAFEthing",
+    "SAFEthingError",
+    "ThingAttr",
+    "Topic",
+    "ActionDef",
+    "ActionParam",
+    "ValueType",
+    "ValueKind",
+    "AccessType",
+    "FilterOperator",
+    "Status",
+]
+
+ACTION_REQUEST_DONE_STATE = "Done"
+ACTION_REQUEST_REJECTED_STATE = "Rejected"
+ACTION_REQUEST_FAILED_STATE = "Failed"
+_ACTION_REQUEST_FINAL_STATES = (
+    ACTION_REQUEST_DONE_STATE,
+    ACTION_REQUEST_REJECTED_STATE,
+    ACTION_REQUEST_FAILED_STATE,
+)
+
+
+# Values of the constants defined in the C interface (see ffi/include/safe_thing.h)
+class AccessType(IntEnum):
+    THING = 0
+    OWNER = 1
+    GROUP = 2
+    ALL = 3
+
+
+class Status(IntEnum):
+    UNKNOWN = 0
+    NON_CONNECTED = 1
+    CONNECTED = 2
+    PUBLISHED = 3
+    DISABLED = 4
+
+
+class FilterOperator(IntEnum):
+    ANY = 0
+    EQUAL = 1
+    NOT_EQUAL = 2
+    LESS_THAN = 3
+    GREATER_THAN = 4
+
+
+class ValueKind(IntEnum):
+    NUMBER = 0
+    BOOL = 1
+    STRING = 2
+    ENUM = 3
+
+
+ThingAttr = namedtuple("ThingAttr", ["attr", "value", "is_dynamic", "access"])
+ThingAttr.__new__.__defaults__ = (False, AccessType.ALL)
+
+# With GROUP access only the members of the groups attached are granted access,
+# or the members of any group of the SAFEthing if none is attached
+Topic = namedtuple("Topic", ["name", "access", "groups"])
+Topic.__new__.__defaults__ = ((),)
+
+# The range is only used for numbers, and the options only for enums
+ValueType = namedtuple("ValueType", ["kind", "min", "max", "options"])
+ValueType.__new__.__defaults__ = (None, None, ())
+
+ActionParam = namedtuple("ActionParam", ["name", "value_type", "default"])
+ActionParam.__new__.__defaults__ = (ValueType(ValueKind.STRING), None)
+
+# The params can also be just names, for parameters of type string
+ActionDef = namedtuple("ActionDef", ["name", "access", "params", "output", "groups"])
+ActionDef.__new__.__defaults__ = ((), None, ())
+
+
+class SAFEthingError(Exception):
+    """Error returned by the SAFEthing library, `code` is one of its error codes"""
+
+    def __init__(self, code, message):
+        super(SAFEthingError, self).__init__(message)
+        self.code = code
+
+
+# Definition of the structs passed through the C interface
+class _FfiThingAttr(ctypes.Structure):
+    _fields_ = [
+        ("attr", ctypes.c_char_p),
+        ("value", ctypes.c_char_p),
+        ("is_dynamic", ctypes.c_bool),
+        ("access", ctypes.c_int32),
+    ]
+
+
+class _FfiTopic(ctypes.Structure):
+    _fields_ = [
+        ("name", ctypes.c_char_p),
+        ("access", ctypes.c_int32),
+        ("groups", ctypes.POINTER(ctypes.c_char_p)),
+        ("groups_len", ctypes.c_size_t),
+    ]
+
+
+class _FfiValueType(ctypes.Structure):
+    _fields_ = [
+        ("kind", ctypes.c_int32),
+        ("has_min", ctypes.c_bool),
+        ("min", ctypes.c_double),
+        ("has_max", ctypes.c_bool),
+        ("max", ctypes.c_double),
+        ("options", ctypes.POINTER(ctypes.c_char_p)),
+        ("options_len", ctypes.c_size_t),
+    ]
+
+
+class _FfiActionParam(ctypes.Structure):
+    _fields_ = [
+        ("name", ctypes.c_char_p),
+        ("value_type", _FfiValueType),
+        ("default_value", ctypes.c_char_p),
+    ]
+
+
+class _FfiActionDef(ctypes.Structure):
+    _fields_ = [
+        ("name", ctypes.c_char_p),
+        ("access", ctypes.c_int32),
+        ("params", ctypes.POINTER(_FfiActionParam)),
+        ("params_len", ctypes.c_size_t),
+        ("output", ctypes.POINTER(_FfiValueType)),
+        ("groups", ctypes.POINTER(ctypes.c_char_p)),
+        ("groups_len", ctypes.c_size_t),
+    ]
+
+
+class _FfiGroup(ctypes.Structure):
+    _fields_ = [
+        ("name", ctypes.c_char_p),
+        ("members", ctypes.POINTER(ctypes.c_char_p)),
+        ("members_len", ctypes.c_size_t),
+    ]
+
+
+# Callbacks invoked by the library
+_SubsNotifCb = ctypes.CFUNCTYPE(
+    None,
+    ctypes.c_void_p,
+    ctypes.c_void_p,
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.c_uint64,
+)
+_ActionReqCb = ctypes.CFUNCTYPE(
+    None,
+    ctypes.c_void_p,
+    ctypes.c_void_p,
+    ctypes.c_uint64,
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.POINTER(ctypes.c_char_p),
+    ctypes.c_size_t,
+)
+_ActionReqStateCb = ctypes.CFUNCTYPE(
+    ctypes.c_bool, ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p
+)
+_ThingAttrsCb = ctypes.CFUNCTYPE(
+    None, ctypes.c_void_p, ctypes.POINTER(_FfiThingAttr), ctypes.c_size_t
+)
+_TopicsCb = ctypes.CFUNCTYPE(
+    None, ctypes.c_void_p, ctypes.POINTER(_FfiTopic), ctypes.c_size_t
+)
+_ActionsCb = ctypes.CFUNCTYPE(
+    None, ctypes.c_void_p, ctypes.POINTER(_FfiActionDef), ctypes.c_size_t
+)
+_GroupsCb = ctypes.CFUNCTYPE(
+    None, ctypes.c_void_p, ctypes.POINTER(_FfiGroup), ctypes.c_size_t
+)
+_StateCb = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_char_p)
+_ReqStateCb = ctypes.CFUNCTYPE(
+    None, ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p
+)
+
+
+def _load_lib():
+    lib_filename = {
+        "win32": "safe_thing.dll",
+        "darwin": "libsafe_thing.dylib",
+    }.get(sys.platform, "libsafe_thing.so")
+
+    lib_path = os.environ.get("SAFE_THING_LIB")
+    if lib_path is None:
+        lib_path = os.path.join(os.path.dirname(__file__), lib_filename)
+        if not os.path.exists(lib_path):
+            lib_path = ctypes.util.find_library("safe_thing") or lib_filename
+
+    lib = ctypes.CDLL(lib_path)
+
+    handle = ctypes.c_void_p
+    string = ctypes.c_char_p
+    result = ctypes.c_int32
+    signatures = {
+        "safe_thing_last_error_message": (string, []),
+        "safe_thing_new": (
+            result,
+            [string, string, ctypes.c_void_p, _SubsNotifCb, _ActionReqCb, ctypes.POINTER(handle)],
+        ),
+        "safe_thing_register": (
+            result,
+            [
+                handle,
+                ctypes.POINTER(_FfiThingAttr),
+                ctypes.c_size_t,
+                ctypes.POINTER(_FfiTopic),
+                ctypes.c_size_t,
+                ctypes.POINTER(_FfiActionDef),
+                ctypes.c_size_t,
+            ],
+        ),
+        "safe_thing_register_private": (
+            result,
+            [
+                handle,
+                ctypes.POINTER(_FfiThingAttr),
+                ctypes.c_size_t,
+                ctypes.POINTER(_FfiTopic),
+                ctypes.c_size_t,
+                ctypes.POINTER(_FfiActionDef),
+                ctypes.c_size_t,
+                string,
+            ],
+        ),
+        "safe_thing_gen_enc_info": (result, [handle, ctypes.c_void_p, _StateCb]),
+        "safe_thing_set_thing_enc_info": (result, [handle, string, string]),
+        "safe_thing_get_thing_enc_info": (result, [handle, string, ctypes.c_void_p, _StateCb]),
+        "safe_thing_fetch_thing_enc_info": (result, [handle, string, ctypes.c_void_p, _StateCb]),
+        "safe_thing_publish": (result, [handle]),
+        "safe_thing_disable": (result, [handle]),
+        "safe_thing_enable": (result, [handle]),
+        "safe_thing_unregister": (result, [handle]),
+        "safe_thing_sign_key": (result, [handle, ctypes.c_void_p, _StateCb]),
+        "safe_thing_authorise": (result, [handle, string, ctypes.c_int32]),
+        "safe_thing_revoke": (result, [handle, string]),
+        "safe_thing_set_claim_code": (result, [handle, string]),
+        "safe_thing_claim": (result, [handle, string, string]),
+        "safe_thing_get_thing_owner": (result, [handle, string, ctypes.c_void_p, _StateCb]),
+        "safe_thing_create_group": (result, [handle, string]),
+        "safe_thing_delete_group": (result, [handle, string]),
+        "safe_thing_add_group_member": (result, [handle, string, string]),
+        "safe_thing_remove_group_member": (result, [handle, string, string]),
+        "safe_thing_get_thing_groups": (result, [handle, string, ctypes.c_void_p, _GroupsCb]),
+        "safe_thing_status": (result, [handle, ctypes.POINTER(ctypes.c_int32)]),
+        "safe_thing_get_thing_status": (result, [handle, string, ctypes.POINTER(ctypes.c_int32)]),
+        "safe_thing_get_thing_attrs": (result, [handle, string, ctypes.c_void_p, _ThingAttrsCb]),
+        "safe_thing_set_attr_value": (result, [handle, string, string]),
+        "safe_thing_get_thing_topics": (result, [handle, string, ctypes.c_void_p, _TopicsCb]),
+        "safe_thing_get_thing_actions": (result, [handle, string, ctypes.c_void_p, _ActionsCb]),
+        "safe_thing_subscribe_to_topic": (result, [handle, string, string, ctypes.c_int32, string]),
+        "safe_thing_subscribe_to_attr": (result, [handle, string, string, ctypes.c_int32, string]),
+        "safe_thing_notify": (result, [handle, string, string]),
+        "safe_thing_action_request": (
+            result,
+            [
+                handle,
+                string,
+                string,
+                ctypes.POINTER(string),
+                ctypes.c_size_t,
+                ctypes.c_void_p,
+                _ActionReqStateCb,
+                ctypes.POINTER(ctypes.c_uint64),
+            ],
+        ),
+        "safe_thing_get_action_request_state": (
+            result,
+            [handle, string, ctypes.c_uint64, ctypes.c_void_p, _StateCb],
+        ),
+        "safe_thing_wait_action_request": (
+            result,
+            [
+                handle,
+                string,
+                ctypes.c_uint64,
+                ctypes.c_uint64,
+                ctypes.c_uint64,
+                ctypes.c_void_p,
+                _ReqStateCb,
+            ],
+        ),
+        "safe_thing_action_request_reply": (result, [handle, ctypes.c_uint64, string, string]),
+        "safe_thing_update_action_request_state": (result, [handle, ctypes.c_uint64, string]),
+        "safe_thing_shutdown": (result, [handle, ctypes.c_bool]),
+        "safe_thing_delete": (None, [handle]),
+    }
+    for name, (restype, argtypes) in signatures.items():
+        func = getattr(lib, name)
+        func.restype = restype
+        func.argtypes = argtypes
+
+    return lib
+
+
+_lib = _load_lib()
+
+
+def _check(res):
+    """Raise a SAFEthingError with the last error message if the call failed"""
+    if res != 0:
+        message = _lib.safe_thing_last_error_message()
+        raise SAFEthingError(res, _decode(message) if message else "Unknown error")
+
+
+def _encode(s):
+    return s.encode("utf-8")
+
+
+def _decode(b):
+    return b.decode("utf-8")
+
+
+def _strings_array(strings):
+    encoded = [_encode(s) for s in strings]
+    return (ctypes.c_char_p * len(encoded))(*encoded)
+
+
+def _read_strings(ptr, length):
+    return [_decode(ptr[i]) for i in range(length)]
+
+
+def _to_value_string(value):
+    """String representation of a value as expected by the library"""
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    return str(value)
+
+
+def _from_value_string(value_type, value):
+    """Convert a value coerced by the library into its Python type"""
+    if value_type.kind == ValueKind.NUMBER:
+        number = float(value)
+        return int(number) if number.is_integer() else number
+    if value_type.kind == ValueKind.BOOL:
+        return value == "true"
+    return value
+
+
+def _action_params(action):
+    return [p if isinstance(p, ActionParam) else ActionParam(p) for p in action.params]
+
+
+class _FfiActionDefs(object):
+    """C structs of a list of actions, it keeps alive all the memory they point to"""
+
+    def __init__(self, actions):
+        self._keep_alive = []
+        ffi_actions = []
+        for action in actions:
+            params = _action_params(action)
+            ffi_params = (_FfiActionParam * len(params))(
+                *[
+                    _FfiActionParam(
+                        _encode(p.name),
+                        self._value_type(p.value_type),
+                        None if p.default is None else _encode(_to_value_string(p.default)),
+                    )
+                    for p in params
+                ]
+            )
+            self._keep_alive.append(ffi_params)
+            output = None
+            if action.output is not None:
+                output = ctypes.pointer(self._value_type(action.output))
+                self._keep_alive.append(output)
+            groups = _strings_array(action.groups)
+            self._keep_alive.append(groups)
+            ffi_actions.append(
+                _FfiActionDef(
+                    _encode(action.name),
+                    int(action.access),
+                    ffi_params,
+                    len(params),
+                    output,
+                    groups,
+                    len(action.groups),
+                )
+            )
+        self.array = (_FfiActionDef * len(ffi_actions))(*ffi_actions)
+
+    def _value_type(self, value_type):
+        options = _strings_array(value_type.options)
+        self._keep_alive.append(options)
+        return _FfiValueType(
+            int(value_type.kind),
+            value_type.min is not None,
+            value_type.min or 0.0,
+            value_type.max is not None,
+            value_type.max or 0.0,
+            options,
+            len(value_type.options),
+        )
+
+
+def _read_value_type(ffi_type):
+    return ValueType(
+        ValueKind(ffi_type.kind),
+        ffi_type.min if ffi_type.has_min else None,
+        ffi_type.max if ffi_type.has_max else None,
+        tuple(_read_strings(ffi_type.options, ffi_type.options_len)),
+    )
+
+
+class SAFEthing(object):
+    """A SAFEthing connected to the network.
+
+    `notifs_cb` is invoked with (safe_thing, thing_id, topic, data, timestamp) upon
+    notifications for any of the subscriptions made, and `action_req_cb` is invoked
+    with (safe_thing, request_id, thing_id, action, args) when an action request is
+    received, with the args already converted according to the action's parameters
+    and the thing_id of the requester verified against the signature of the request.
+    The value returned by `action_req_cb` is the output of the action, and an exception
+    raised by it makes the request fail with its message as the error.
+    Both are invoked from the library's threads.
+    """
+
+    def __init__(self, thing_id, auth_uri="", notifs_cb=None, action_req_cb=None):
+        self.thing_id = thing_id
+        self._notifs_cb = notifs_cb
+        self._action_req_cb = action_req_cb
+        # The state callbacks are kept alive while the action requests are monitored
+        self._requests_cbs = {}
+        self._actions = {}
+        self._lock = threading.Lock()
+
+        # References to the native callbacks need to be kept as the
+        # library can invoke them at any time
+        self._ffi_notifs_cb = _SubsNotifCb(self._on_notification)
+        self._ffi_action_req_cb = _ActionReqCb(self._on_action_request)
+
+        self._handle = ctypes.c_void_p()
+        _check(
+            _lib.safe_thing_new(
+                _encode(thing_id),
+                _encode(auth_uri),
+                None,
+                self._ffi_notifs_cb,
+                self._ffi_action_req_cb,
+                ctypes.byref(self._handle),
+            )
+        )
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *args):
+        self.close()
+
+    def register(self, attrs=(), topics=(), actions=(), enc_info=None):
+        """Register the SAFEthing on the network with its attributes, topics and actions,
+        it's registered as private and encrypted if the encryption info is provided"""
+        ffi_attrs = (_FfiThingAttr * len(attrs))(
+            *[
+                _FfiThingAttr(_encode(a.attr), _encode(a.value), a.is_dynamic, int(a.access))
+                for a in attrs
+            ]
+        )
+        # the groups arrays need to be alive until the function returns
+        topics_groups = [_strings_array(t.groups) for t in topics]
+        ffi_topics = (_FfiTopic * len(topics))(
+            *[
+                _FfiTopic(_encode(t.name), int(t.access), groups, len(t.groups))
+                for t, groups in zip(topics, topics_groups)
+            ]
+        )
+        # the actions structs need to be alive until the function returns
+        ffi_actions = _FfiActionDefs(actions)
+        args = [
+            self._handle,
+            ffi_attrs,
+            len(attrs),
+            ffi_topics,
+            len(topics),
+            ffi_actions.array,
+            len(actions),
+        ]
+        if enc_info is None:
+            _check(_lib.safe_thing_register(*args))
+        else:
+            _check(_lib.safe_thing_register_private(*args, _encode(enc_info)))
+        # keep the definitions to convert the arguments of the action requests received
+        self._actions = {a.name: _action_params(a) for a in actions}
+
+    def gen_enc_info(self):
+        """Generate new encryption info to register a private SAFEthing with"""
+        infos = []
+
+        def on_enc_info(_user_data, enc_info):
+            infos.append(_decode(enc_info))
+
+        _check(_lib.safe_thing_gen_enc_info(self._handle, None, _StateCb(on_enc_info)))
+        return infos[0]
+
+    def set_thing_enc_info(self, thing_id, enc_info):
+        """Set the encryption info needed to access a private SAFEthing"""
+        _check(_lib.safe_thing_set_thing_enc_info(self._handle, _encode(thing_id), _encode(enc_info)))
+
+    def get_thing_enc_info(self, thing_id):
+        """Get the encryption info known for a private SAFEthing, or None"""
+        infos = []
+
+        def on_enc_info(_user_data, enc_info):
+            infos.append(None if enc_info is None else _decode(enc_info))
+
+        _check(
+            _lib.safe_thing_get_thing_enc_info(
+                self._handle, _encode(thing_id), None, _StateCb(on_enc_info)
+            )
+        )
+        return infos[0]
+
+    def fetch_thing_enc_info(self, thing_id):
+        """Request the encryption info of a private SAFEthing which authorised this one,
+        it blocks until it's received and it's then set to access the SAFEthing"""
+        infos = []
+
+        def on_enc_info(_user_data, enc_info):
+            infos.append(_decode(enc_info))
+
+        _check(
+            _lib.safe_thing_fetch_thing_enc_info(
+                self._handle, _encode(thing_id), None, _StateCb(on_enc_info)
+            )
+        )
+        return infos[0]
+
+    def publish(self):
+        """Publish the SAFEthing making it available and operative in the network"""
+        _check(_lib.safe_thing_publish(self._handle))
+
+    def disable(self):
+        """Disable the SAFEthing, it rejects new action requests and doesn't notify events"""
+        _check(_lib.safe_thing_disable(self._handle))
+
+    def enable(self):
+        """Enable a disabled SAFEthing, restoring the status it had before it was disabled"""
+        _check(_lib.safe_thing_enable(self._handle))
+
+    def unregister(self):
+        """Unregister the SAFEthing, clearing its attributes, topics and actions"""
+        _check(_lib.safe_thing_unregister(self._handle))
+
+    def sign_key(self):
+        """Get the public signing key the SAFEthing is identified with"""
+        keys = []
+
+        def on_key(_user_data, sign_key):
+            keys.append(_decode(sign_key))
+
+        _check(_lib.safe_thing_sign_key(self._handle, None, _StateCb(on_key)))
+        return keys[0]
+
+    def authorise(self, sign_key, access):
+        """Authorise a public signing key with OWNER or GROUP access to the topics and actions"""
+        _check(_lib.safe_thing_authorise(self._handle, _encode(sign_key), int(access)))
+
+    def revoke(self, sign_key):
+        """Revoke the access previously authorised to a public signing key"""
+        _check(_lib.safe_thing_revoke(self._handle, _encode(sign_key)))
+
+    def set_claim_code(self, code):
+        """Set the one-time code printed on the device, its owner provides it to claim the
+        SAFEthing"""
+        _check(_lib.safe_thing_set_claim_code(self._handle, _encode(code)))
+
+    def claim(self, thing_id, code):
+        """Claim the ownership of a SAFEthing with the one-time code printed on it, the key
+        of this SAFEthing is then authorised with OWNER access"""
+        _check(_lib.safe_thing_claim(self._handle, _encode(thing_id), _encode(code)))
+
+    def get_thing_owner(self, thing_id):
+        """Get the public signing key of the owner of a SAFEthing, or None if it wasn't claimed"""
+        owners = []
+
+        def on_owner(_user_data, owner_key):
+            owners.append(None if owner_key is None else _decode(owner_key))
+
+        _check(
+            _lib.safe_thing_get_thing_owner(
+                self._handle, _encode(thing_id), None, _StateCb(on_owner)
+            )
+        )
+        return owners[0]
+
+    def create_group(self, group):
+        """Create a named group, its members are granted GROUP access to the topics and
+        actions it's attached to"""
+        _check(_lib.safe_thing_create_group(self._handle, _encode(group)))
+
+    def delete_group(self, group):
+        """Delete a group of the SAFEthing"""
+        _check(_lib.safe_thing_delete_group(self._handle, _encode(group)))
+
+    def add_group_member(self, group, member):
+        """Add a member to a group, either the ID of a SAFEthing or a public signing key"""
+        _check(_lib.safe_thing_add_group_member(self._handle, _encode(group), _encode(member)))
+
+    def remove_group_member(self, group, member):
+        """Remove a member from a group"""
+        _check(
+            _lib.safe_thing_remove_group_member(self._handle, _encode(group), _encode(member))
+        )
+
+    def get_thing_groups(self, thing_id):
+        """Get the groups of a SAFEthing as a dict with the list of members of each group"""
+        groups = {}
+
+        def on_groups(_user_data, ptr, length):
+            for i in range(length):
+                groups[_decode(ptr[i].name)] = _read_strings(ptr[i].members, ptr[i].members_len)
+
+        _check(
+            _lib.safe_thing_get_thing_groups(
+                self._handle, _encode(thing_id), None, _GroupsCb(on_groups)
+            )
+        )
+        return groups
+
+    def status(self):
+        """Get the status of the SAFEthing"""
+        status = ctypes.c_int32()
+        _check(_lib.safe_thing_status(self._handle, ctypes.byref(status)))
+        return Status(status.value)
+
+    def get_thing_status(self, thing_id):
+        """Get the status of a SAFEthing, it's NON_CONNECTED if it was never registered"""
+        status = ctypes.c_int32()
+        _check(_lib.safe_thing_get_thing_status(self._handle, _encode(thing_id), ctypes.byref(status)))
+        return Status(status.value)
+
+    def get_thing_attrs(self, thing_id):
+        """Get the list of attributes of a SAFEthing"""
+        attrs = []
+
+        def on_attrs(_user_data, ptr, length):
+            for i in range(length):
+                attrs.append(
+                    ThingAttr(
+                        _decode(ptr[i].attr),
+                        _decode(ptr[i].value),
+                        ptr[i].is_dynamic,
+                        AccessType(ptr[i].access),
+                    )
+                )
+
+        _check(
+            _lib.safe_thing_get_thing_attrs(
+                self._handle, _encode(thing_id), None, _ThingAttrsCb(on_attrs)
+            )
+        )
+        return attrs
+
+    def set_attr_value(self, attr, value):
+        """Set a new value for an attribute of the SAFEthing, or add it if it didn't exist"""
+        _check(_lib.safe_thing_set_attr_value(self._handle, _encode(attr), _encode(value)))
+
+    def get_thing_topics(self, thing_id):
+        """Get the list of topics supported by a SAFEthing"""
+        topics = []
+
+        def on_topics(_user_data, ptr, length):
+            for i in range(length):
+                topics.append(
+                    Topic(
+                        _decode(ptr[i].name),
+                        AccessType(ptr[i].access),
+                        _read_strings(ptr[i].groups, ptr[i].groups_len),
+                    )
+                )
+
+        _check(
+            _lib.safe_thing_get_thing_topics(
+                self._handle, _encode(thing_id), None, _TopicsCb(on_topics)
+            )
+        )
+        return topics
+
+    def get_thing_actions(self, thing_id):
+        """Get the list of actions supported by a SAFEthing"""
+        actions = []
+
+        def on_actions(_user_data, ptr, length):
+            for i in range(length):
+                params = [
+                    ActionParam(
+                        _decode(p.name),
+                        _read_value_type(p.value_type),
+                        _decode(p.default_value) if p.default_value else None,
+                    )
+                    for p in ptr[i].params[: ptr[i].params_len]
+                ]
+                output = _read_value_type(ptr[i].output[0]) if ptr[i].output else None
+                groups = _read_strings(ptr[i].groups, ptr[i].groups_len)
+                actions.append(
+                    ActionDef(
+                        _decode(ptr[i].name), AccessType(ptr[i].access), params, output, groups
+                    )
+                )
+
+        _check(
+            _lib.safe_thing_get_thing_actions(
+                self._handle, _encode(thing_id), None, _ActionsCb(on_actions)
+            )
+        )
+        return actions
+
+    def subscribe_to_topic(self, thing_id, topic, filter_op=FilterOperator.ANY, filter_value=""):
+        """Subscribe to a topic published by a SAFEthing"""
+        _check(
+            _lib.safe_thing_subscribe_to_topic(
+                self._handle, _encode(thing_id), _encode(topic), int(filter_op), _encode(filter_value)
+            )
+        )
+
+    def subscribe_to_attr(self, thing_id, attr, filter_op=FilterOperator.ANY, filter_value=""):
+        """Subscribe to a dynamic attribute published by a SAFEthing"""
+        _check(
+            _lib.safe_thing_subscribe_to_attr(
+                self._handle, _encode(thing_id), _encode(attr), int(filter_op), _encode(filter_value)
+            )
+        )
+
+    def notify(self, topic, data=""):
+        """Notify of an event associated to a topic"""
+        _check(_lib.safe_thing_notify(self._handle, _encode(topic), _encode(data)))
+
+    def action_request(self, thing_id, action, args=(), cb=None):
+        """Send an action request to a SAFEthing and return its id.
+
+        The optional `cb` is invoked with the new state, the output of the action if it has
+        any, and the error reported if it was rejected or failed, upon changes in the state
+        of the request. It shall return True to keep receiving notifications until it's
+        either "Done", "Rejected" or "Failed".
+        """
+        request_id = ctypes.c_uint64()
+        key = object()
+
+        def on_state(_user_data, state, result, error):
+            state = _decode(state)
+            result = json.loads(_decode(result)) if result else None
+            error = _decode(error) if error else None
+            keep_monitoring = cb(state, result, error) if cb else True
+            keep_monitoring = keep_monitoring and state not in _ACTION_REQUEST_FINAL_STATES
+            if not keep_monitoring:
+                with self._lock:
+                    self._requests_cbs.pop(key, None)
+            return keep_monitoring
+
+        ffi_state_cb = _ActionReqStateCb(on_state)
+        with self._lock:
+            self._requests_cbs[key] = ffi_state_cb
+
+        args = [_to_value_string(arg) for arg in args]
+        ffi_args = _strings_array(args)
+        try:
+            _check(
+                _lib.safe_thing_action_request(
+                    self._handle,
+                    _encode(thing_id),
+                    _encode(action),
+                    ffi_args,
+                    len(args),
+                    None,
+                    ffi_state_cb,
+                    ctypes.byref(request_id),
+                )
+            )
+        except SAFEthingError:
+            with self._lock:
+                self._requests_cbs.pop(key, None)
+            raise
+
+        return request_id.value
+
+    def get_action_request_state(self, thing_id, request_id):
+        """Get the current state of an action request sent to a SAFEthing"""
+        states = []
+
+        def on_state(_user_data, state):
+            states.append(_decode(state))
+
+        _check(
+            _lib.safe_thing_get_action_request_state(
+                self._handle, _encode(thing_id), request_id, None, _StateCb(on_state)
+            )
+        )
+        return states[0]
+
+    def wait_action_request(self, thing_id, request_id, timeout, poll_interval=2.0):
+        """Block until an action request sent to a SAFEthing reaches a final state.
+
+        It returns a tuple with the final state, the output of the action and the error
+        reported, polling the state every `poll_interval` seconds. A SAFEthingError is
+        raised if the request is not final after `timeout` seconds.
+        """
+        outcome = []
+
+        def on_state(_user_data, state, result, error):
+            outcome.append(
+                (
+                    _decode(state),
+                    json.loads(_decode(result)) if result else None,
+                    _decode(error) if error else None,
+                )
+            )
+
+        _check(
+            _lib.safe_thing_wait_action_request(
+                self._handle,
+                _encode(thing_id),
+                request_id,
+                int(poll_interval * 1000),
+                int(timeout * 1000),
+                None,
+                _ReqStateCb(on_state),
+            )
+        )
+        return outcome[0]
+
+    def update_action_request_state(self, request_id, new_state):
+        """Update the state of an action request received by the SAFEthing"""
+        _check(
+            _lib.safe_thing_update_action_request_state(
+                self._handle, request_id, _encode(new_state)
+            )
+        )
+
+    def shutdown(self, disable=False):
+        """Stop the background workers, optionally setting the SAFEthing status to Disabled"""
+        _check(_lib.safe_thing_shutdown(self._handle, disable))
+
+    def close(self):
+        """Free the SAFEthing instance, stopping its background workers"""
+        if self._handle:
+            _lib.safe_thing_delete(self._handle)
+            self._handle = ctypes.c_void_p()
+
+    def _on_notification(self, _user_data, _handle, thing_id, topic, data, timestamp):
+        if self._notifs_cb:
+            self._notifs_cb(self, _decode(thing_id), _decode(topic), _decode(data), timestamp)
+
+    def _on_action_request(self, _user_data, handle, request_id, thing_id, action, args, args_len):
+        if self._action_req_cb:
+            action = _decode(action)
+            params = self._actions.get(action, [])
+            args = [
+                _from_value_string(p.value_type, arg)
+                for p, arg in zip(params, _read_strings(args, args_len))
+            ]
+            result, error = None, None
+            try:
+                result = self._action_req_cb(self, request_id, _decode(thing_id), action, args)
+            except Exception as err:
+                error = str(err)
+            if result is not None:
+                result = _encode(_to_value_string(result))
+            _lib.safe_thing_action_request_reply(
+                handle, request_id, result, None if error is None else _encode(error)
+            )
@@ -0,0 +1,31 @@
+# Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+#
+# This file is part of the SAFEthing Framework.
+#
+# The SAFEthing Framework is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# The SAFEthing Framework is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+from setuptools import setup
+
+setup(
+    name="safe_thing",
+    version="0.0.1",
+    description="Python binding for the SAFEthing framework library",
+    author="Gabriel Viganotti",
+    license="GPL-3.0",
+    keywords="IoT SAFE SAFENet SAFEthing descentralised communication",
+    packages=["safe_thing"],
+    # the safe_thing library built from the ffi crate can be copied into the package folder
+    package_data={"safe_thing": ["*.so", "*.dylib", "*.dll"]},
+    python_requires=">=3.4",
+)
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU General Public License
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
-use safe_thing::{FilterOperator, SAFEthing, ThingAttr};
+use safe_thing::{ActionValue, FilterOperator, SAFEthing, ThingAttr};
 use std::thread;
 use std::time::Duration;
 
@@ -40,8 +40,8 @@ pub fn main() {
     // Let's create an instance of SAFEthing for this device.
     // We already provide the two callback functions to be called
     // for subscriptions notifications and action requests respectively.
-    let mut safe_thing =
-        SAFEthing::new(&id, auth_uri, &subscriptions_notif, &|_, _, _, _, _| {}).unwrap();
+    let safe_thing =
+        SAFEthing::new(&id, auth_uri, subscriptions_notif, |_, _, _, _, _| Ok(None)).unwrap();
 
     // Register the SAFEthing on the network, this won't make it active yet
     safe_thing
@@ -104,7 +104,7 @@ fn subscriptions_notif(
                     thing_id,    // the id of the gardening device where we are sending the action request to
                     "OpenValve", // request to open the water valve
                     &["60"],     // the desired water pressure
-                    &handle_req_state_change, // callback to get notified about the state of this action request
+                    handle_req_state_change, // callback to get notified about the state of this action request
                 )
                 .expect("Failed to send 'OpenValve' action request");
 
@@ -121,7 +121,7 @@ fn subscriptions_notif(
                     thing_id,
                     "CloseValve", // request to close the water valve
                     &[],
-                    &handle_req_state_change,
+                    handle_req_state_change,
                 )
                 .expect("Failed to send 'CloseValve' action request");
 
@@ -134,11 +134,16 @@ fn subscriptions_notif(
     };
 }
 
-fn handle_req_state_change(state: &str) -> bool {
+fn handle_req_state_change(state: &str, outcome: Result<Option<&ActionValue>, &str>) -> bool {
     println!(
         "The action request sent to open/close the water valve was reported to be in state: '{}'",
         state
     );
+    match outcome {
+        Ok(Some(psi)) => println!("The water valve was left with a pressure of {} psi", psi),
+        Ok(None) => {}
+        Err(err) => eprintln!("The action request couldn't be completed: {}", err),
+    }
 
     // We return 'true' to keep receiving state changes notifications for this action request
     // until the action is finally in "Done" state. Although in this particular case we won't be
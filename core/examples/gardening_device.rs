@@ -15,14 +15,16 @@
 // You should have received a copy of the GNU General Public License
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
-use safe_thing::{AccessType, ActionDef, SAFEthing, ThingAttr, Topic};
-use std::thread;
-use std::time::Duration;
+use safe_thing::{
+    AccessType, ActionDef, ActionParam, ActionResult, ActionValue, SAFEthing, ThingAttr,
+    ThingEvent, Topic, ValueType,
+};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
 
 const SOME_RANDOM_PSI_FACTOR: f32 = 8.0;
 const MAX_PSI_ALLOWED: u32 = 100;
-
-static mut CURRENT_MOISTURE_LEVEL_FACTOR: f32 = -1.0;
+const SENSOR_READING_FREQ: u64 = 3_000;
 
 pub fn main() {
     // Let's create a SAFEthing id for the gardening device, this could be the device serial number
@@ -45,16 +47,26 @@ pub fn main() {
         Topic::new("VeryWetAlarm", AccessType::All),
     ];
 
+    // The water pressure is optional when requesting to open the valve,
+    // and both actions return the pressure the valve was left with.
+    let psi_type = ValueType::Number {
+        min: Some(0.0),
+        max: Some(f64::from(MAX_PSI_ALLOWED)),
+    };
     let actions = [
-        ActionDef::new("OpenValve", AccessType::All, &["psi"]),
-        ActionDef::new("CloseValve", AccessType::All, &[]),
+        ActionDef::with_types(
+            "OpenValve",
+            AccessType::All,
+            &[ActionParam::new("psi", psi_type.clone(), Some("10"))],
+            Some(psi_type.clone()),
+        ),
+        ActionDef::with_types("CloseValve", AccessType::All, &[], Some(psi_type)),
     ];
 
     // Let's create an instance of SAFEthing for this device.
-    // We already provide here the two callback functions to be called
-    // for subscriptions notifications and action requests respectively.
-    let mut safe_thing =
-        SAFEthing::new(&id, auth_uri, &subscriptions_notif, &action_request_notif).unwrap();
+    // Rather than providing callback functions, we get the action requests
+    // from a channel so we can handle them from the device's main loop.
+    let (safe_thing, events) = SAFEthing::with_events(&id, auth_uri).unwrap();
 
     // Register the SAFEthing on the network, this won't make it active yet
     // but it will just store the device's data onto the network as a SAFEthing entity
@@ -74,10 +86,42 @@ pub fn main() {
     // the other device's SAFEthing framework instance rather than this one.
     // Let's set some initial value simulating to be the current moisture level read from a sensor.
     let mut current_moisture_level: f32 = 6.5;
+    let mut moisture_level_factor: f32 = -1.0;
     let mut notif_sent = false;
+    let mut next_reading = Instant::now();
 
     // We now go into an infinite loop which contains the main logic of this device...
     loop {
+        // Let's handle any action request received while we wait for the next sensor reading
+        let now = Instant::now();
+        if now < next_reading {
+            match events.recv_timeout(next_reading - now) {
+                Ok(ThingEvent::ActionRequest {
+                    request_id,
+                    thing_id,
+                    action,
+                    args,
+                    reply,
+                }) => {
+                    let result = handle_action_request(
+                        &safe_thing,
+                        &mut moisture_level_factor,
+                        request_id,
+                        &thing_id,
+                        &action,
+                        &args,
+                    );
+                    reply.send(result);
+                }
+                // We haven't subsribed to any SAFEthing's topic, thus we won't get notifications
+                Ok(ThingEvent::Notification { .. }) => (),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => panic!("SAFEthing events channel closed"),
+            }
+            continue;
+        }
+        next_reading = now + Duration::from_millis(SENSOR_READING_FREQ);
+
         // In a real situation, the following statement would be replaced by the logic
         // to actually read the value from a soil moisture sensor.
         // In order to simulate such an environment, we will consider the moisture level
@@ -85,9 +129,7 @@ pub fn main() {
         // where the soil dries out as the time passes by. And we consider the moisture level would
         // be increased while the water valve is open (note we are using the requested water PSI
         // in when setting the factor)
-        unsafe {
-            current_moisture_level += 0.1 * CURRENT_MOISTURE_LEVEL_FACTOR;
-        }
+        current_moisture_level += 0.1 * moisture_level_factor;
 
         // Let's now keep the published dynamic attribute up to date so any
         // other SAFEthing can be notified if it's interested in knowing about the new value
@@ -117,20 +159,17 @@ pub fn main() {
         } else {
             notif_sent = false;
         }
-
-        // Let's wait for some time before going into a new cycle of reading the
-        // current soil moisture level and publishing the new value
-        thread::sleep(Duration::from_millis(3000));
     }
 }
 
-fn action_request_notif(
+fn handle_action_request(
     safe_thing: &SAFEthing,
+    moisture_level_factor: &mut f32,
     request_id: u128,
     thing_id: &str,
     action: &str,
-    args: &[&str],
-) {
+    args: &[ActionValue],
+) -> ActionResult {
     println!(
         "New action request received, id: '{}', from thing_id: '{}', action: '{}', args: {:?}",
         request_id, thing_id, action, args
@@ -139,13 +178,9 @@ fn action_request_notif(
     // Let's act according to the action request we received...
     match action {
         "OpenValve" => {
-            let psi = args[0].parse::<u32>().unwrap_or(10); // if we fail to parse it assume 10 psi by default
-            let requested_water_psi: u32 = if psi > MAX_PSI_ALLOWED {
-                // 100 psi is the max we allow
-                MAX_PSI_ALLOWED
-            } else {
-                psi
-            };
+            // The framework already checked the psi is a number within the range we
+            // defined for the action, and it defaults to 10 psi if it wasn't provided
+            let requested_water_psi = args[0].as_f64().unwrap() as u32;
 
             // We just print a message here , but in a real situation this is where we should
             // open the watering device valve.
@@ -153,39 +188,35 @@ fn action_request_notif(
                 "Opening the water valve as requested by SAFEthing {}, with a presure of {} psi",
                 thing_id, requested_water_psi
             );
-            safe_thing.set_attr_value("valve-state", "open").unwrap();
+            safe_thing
+                .set_attr_value("valve-state", "open")
+                .map_err(|err| err.to_string())?;
 
             // To simulate that the soil moisture level increases when the water valve is open
             // we set the factor to a positive number and apply some other factor to also consider the
             // requested PSI. Remember, in a real situation, the following statement wouldn't be needed.
-            unsafe {
-                CURRENT_MOISTURE_LEVEL_FACTOR = requested_water_psi as f32 / SOME_RANDOM_PSI_FACTOR;
-            }
+            *moisture_level_factor = requested_water_psi as f32 / SOME_RANDOM_PSI_FACTOR;
+
+            // Let the requester know the pressure the valve was left with
+            Ok(Some(requested_water_psi.to_string()))
         }
         "CloseValve" => {
             println!(
                 "Closing the water valve as requested by SAFEthing {}",
                 thing_id
             );
-            safe_thing.set_attr_value("valve-state", "closed").unwrap();
+            safe_thing
+                .set_attr_value("valve-state", "closed")
+                .map_err(|err| err.to_string())?;
 
             // To simulate that the soil moisture level decreases when the water valve is closed
             // we set the factor to a negative number. Remember, in a real situation,
             // the following statement wouldn't be needed.
-            unsafe {
-                CURRENT_MOISTURE_LEVEL_FACTOR = -1.0; // negative to decrease the level
-            }
+            *moisture_level_factor = -1.0; // negative to decrease the level
+
+            Ok(Some("0".to_string()))
         }
-        &_ => eprintln!("Unknown action request received: {}", action),
+        // The framework only passes us requests for the actions we published
+        &_ => Err(format!("Unknown action request received: {}", action)),
     }
 }
-
-// We haven't subsribed to any SAFEthing's topic, thus this function shoulnd't be invoked
-fn subscriptions_notif(
-    _safe_thing: &SAFEthing,
-    _thing_id: &str,
-    _topic: &str,
-    _data: &str,
-    _timestamp: u128,
-) {
-}
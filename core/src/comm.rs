@@ -15,21 +15,27 @@
 // You should have received a copy of the GNU General Public License
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
-use log::debug;
+use log::{debug, warn};
 
 use crate::errors::{Error, ErrorCode, ResultReturn};
 
-// Functions to access the SAFE Network
-use crate::safe_net::{MutableData, SAFENet};
+// Functions to access the storage backend, e.g. the SAFE Network
+use crate::thing_store::ThingStore;
 use safe_core::ffi::arrays::XorNameArray;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const SAFE_THING_TYPE_TAG: u64 = 27417;
+// A private SAFEthing also has a public MutableData at the same XoR name, where the peers
+// which don't know its encryption info yet can request it
+const SAFE_THING_PUB_TYPE_TAG: u64 = 27418;
 
 static SAFE_THING_ENTRY_K_STATUS: &'static str = "_safe_thing_status";
 static SAFE_THING_ENTRY_V_STATUS_CONNECTED: &'static str = "Connected";
 static SAFE_THING_ENTRY_V_STATUS_PUBLISHED: &'static str = "Published";
 static SAFE_THING_ENTRY_V_STATUS_DISABLED: &'static str = "Disabled";
+static SAFE_THING_ENTRY_K_ENABLED_STATUS: &'static str = "_safe_thing_enabled_status";
 
 static SAFE_THING_ENTRY_K_ATTRS: &'static str = "_safe_thing_attributes";
 static SAFE_THING_ENTRY_K_TOPICS: &'static str = "_safe_thing_topics";
@@ -37,8 +43,16 @@ static SAFE_THING_ENTRY_K_ACTIONS: &'static str = "_safe_thing_actions";
 static SAFE_THING_ENTRY_K_SUBSCRIPTIONS: &'static str = "_safe_thing_subscriptions";
 static SAFE_THING_ENTRY_K_EVENTS: &'static str = "_safe_thing_events_";
 static SAFE_THING_ENTRY_K_ACTION_REQ: &'static str = "_safe_thing_action_req_";
-
-#[derive(Debug)]
+static SAFE_THING_ENTRY_K_SIGN_KEY: &'static str = "_safe_thing_sign_key";
+static SAFE_THING_ENTRY_K_ENC_KEY: &'static str = "_safe_thing_enc_key";
+static SAFE_THING_ENTRY_K_AUTHORISED: &'static str = "_safe_thing_authorised";
+static SAFE_THING_ENTRY_K_GROUPS: &'static str = "_safe_thing_groups";
+static SAFE_THING_ENTRY_K_OWNER: &'static str = "_safe_thing_owner";
+static SAFE_THING_ENTRY_K_CLAIM_REQ: &'static str = "_safe_thing_claim_req_";
+static SAFE_THING_ENTRY_K_ENC_INFO_REQ: &'static str = "_safe_thing_enc_info_req_";
+static SAFE_THING_ENTRY_K_SUBSCRIBER: &'static str = "_safe_thing_subscriber_";
+
+#[derive(Debug, PartialEq)]
 pub enum ThingStatus {
     Unknown,
     Connected,
@@ -46,62 +60,66 @@ pub enum ThingStatus {
     Disabled,
 }
 
-pub struct SAFEthingComm {
+/// The connection to the backend is shared by all the threads of a SAFEthing,
+/// thus it's synchronised internally and only one request is sent at a time
+pub struct SAFEthingComm<S: ThingStore> {
     thing_id: String,
-    safe_net: SAFENet,
-    auth_str: String,
-    thing_mdata: MutableData,
-    xor_name: XorNameArray,
+    sign_key: String,
+    safe_net: Mutex<S>,
+    thing_mdata: Mutex<S::MData>,
+    xor_name: Mutex<XorNameArray>,
+    // Encryption info of the private SAFEthings known, including this one if it's private
+    enc_infos: Mutex<BTreeMap<String, String>>,
 }
 
-impl Clone for SAFEthingComm {
-    fn clone(&self) -> SAFEthingComm {
-        let safething_comm = SAFEthingComm {
-            thing_id: self.thing_id.clone(),
-            /// TODO: pass a callback function for disconnection notif to reconnect
-            safe_net: SAFENet::connect(&self.thing_id, &self.auth_str).unwrap(),
-            auth_str: self.auth_str.clone(),
-            thing_mdata: self.thing_mdata.clone(),
-            xor_name: self.xor_name.clone(),
-        };
-
-        safething_comm
-    }
-}
-impl SAFEthingComm {
-    pub fn new(thing_id: &str, auth_uri: &str) -> ResultReturn<SAFEthingComm> {
+impl<S: ThingStore> SAFEthingComm<S> {
+    pub fn new(thing_id: &str, auth_uri: &str) -> ResultReturn<SAFEthingComm<S>> {
         let auth_str: String = if auth_uri.is_empty() {
             debug!("Authorising SAFEthing app with safe_auth webservice...");
-            SAFENet::gen_auth_request(thing_id)?
+            S::gen_auth_request(thing_id)?
         } else {
             debug!("Using the provided authorisation credentials to connect...");
             auth_uri.to_string()
         };
 
+        /// TODO: pass a callback function for disconnection notif to reconnect
+        let safe_net = S::connect(thing_id, &auth_str)?; // Connect to the SAFE Network using the auth URI
         let safe_thing_comm = SAFEthingComm {
             thing_id: thing_id.to_string(),
-            /// TODO: pass a callback function for disconnection notif to reconnect
-            safe_net: SAFENet::connect(thing_id, &auth_str)?, // Connect to the SAFE Network using the auth URI
-            auth_str,
+            sign_key: safe_net.pub_sign_key()?,
+            safe_net: Mutex::new(safe_net),
             thing_mdata: Default::default(),
             xor_name: Default::default(),
+            enc_infos: Default::default(),
         };
 
         Ok(safe_thing_comm)
     }
 
-    pub fn store_thing_entity(&mut self) -> ResultReturn<(String, u64)> {
-        let xor_name = self.safe_net.gen_xor_name(self.thing_id.as_str());
-        self.thing_mdata = self
-            .safe_net
-            .new_pub_mutable_data(xor_name, SAFE_THING_TYPE_TAG)?;
-        self.xor_name = xor_name;
+    // The entity is stored as a private MutableData if the encryption info is provided
+    pub fn store_thing_entity(&self, enc_info: Option<&str>) -> ResultReturn<(String, u64)> {
+        let xor_name = self.net().gen_xor_name(self.thing_id.as_str());
+        let thing_mdata = match enc_info {
+            Some(enc_info) => {
+                let _ = self
+                    .net()
+                    .new_pub_mutable_data(xor_name, SAFE_THING_PUB_TYPE_TAG)?;
+                self.net()
+                    .new_priv_mutable_data(xor_name, SAFE_THING_TYPE_TAG, enc_info)?
+            }
+            None => self
+                .net()
+                .new_pub_mutable_data(xor_name, SAFE_THING_TYPE_TAG)?,
+        };
+        self.set_thing_enc_info(&self.thing_id, enc_info);
+        *self.thing_mdata.lock().unwrap() = thing_mdata;
+        *self.xor_name.lock().unwrap() = xor_name;
         Ok((self.addr_name()?, SAFE_THING_TYPE_TAG))
     }
 
     pub fn addr_name(&self) -> ResultReturn<String> {
         let mut xor_name = String::new();
-        for i in self.xor_name.iter() {
+        for i in self.xor_name.lock().unwrap().iter() {
             let x = format!("{:02x}", i);
             xor_name.push_str(x.as_str());
         }
@@ -122,8 +140,8 @@ impl SAFEthingComm {
                 ));
             }
         }
-        self.safe_net.mutable_data_set_value(
-            &self.thing_mdata,
+        self.net().mutable_data_set_value(
+            &self.own_mdata(),
             SAFE_THING_ENTRY_K_STATUS,
             status_str,
         )?;
@@ -131,57 +149,117 @@ impl SAFEthingComm {
     }
 
     pub fn get_status(&self) -> ResultReturn<ThingStatus> {
-        let mut status = ThingStatus::Unknown;
         let status_str = self
-            .safe_net
-            .mutable_data_get_value(&self.thing_mdata, SAFE_THING_ENTRY_K_STATUS)?;
-        if status_str == SAFE_THING_ENTRY_V_STATUS_CONNECTED {
-            status = ThingStatus::Connected;
-        } else if status_str == SAFE_THING_ENTRY_V_STATUS_PUBLISHED {
-            status = ThingStatus::Published;
-        } else if status_str == SAFE_THING_ENTRY_V_STATUS_DISABLED {
-            status = ThingStatus::Disabled;
+            .net()
+            .mutable_data_get_value(&self.own_mdata(), SAFE_THING_ENTRY_K_STATUS)?;
+        Ok(parse_status(&status_str))
+    }
+
+    // The status it had before being disabled is kept so it can be restored when enabled
+    pub fn set_disabled(&self) -> ResultReturn<()> {
+        let status_str = self
+            .net()
+            .mutable_data_get_value(&self.own_mdata(), SAFE_THING_ENTRY_K_STATUS)?;
+        if parse_status(&status_str) != ThingStatus::Disabled {
+            self.net().mutable_data_set_value(
+                &self.own_mdata(),
+                SAFE_THING_ENTRY_K_ENABLED_STATUS,
+                &status_str,
+            )?;
         }
-        Ok(status)
+        self.set_status(ThingStatus::Disabled)
+    }
+
+    pub fn get_enabled_status(&self) -> ResultReturn<ThingStatus> {
+        match self
+            .net()
+            .mutable_data_get_value(&self.own_mdata(), SAFE_THING_ENTRY_K_ENABLED_STATUS)
+        {
+            Ok(status_str) => Ok(parse_status(&status_str)),
+            // It was never disabled since it was registered
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => Ok(ThingStatus::Published),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub fn get_thing_status(&self, thing_id: &str) -> ResultReturn<ThingStatus> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        let status_str = self
+            .net()
+            .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_STATUS)?;
+        Ok(parse_status(&status_str))
     }
 
     pub fn set_attributes(&self, attrs: &str) -> ResultReturn<()> {
-        self.safe_net
-            .mutable_data_set_value(&self.thing_mdata, SAFE_THING_ENTRY_K_ATTRS, attrs)?;
+        self.net()
+            .mutable_data_set_value(&self.own_mdata(), SAFE_THING_ENTRY_K_ATTRS, attrs)?;
         Ok(())
     }
 
+    pub fn gen_enc_info(&self) -> ResultReturn<String> {
+        self.net().gen_enc_info()
+    }
+
+    pub fn set_thing_enc_info(&self, thing_id: &str, enc_info: Option<&str>) {
+        let mut enc_infos = self.enc_infos.lock().unwrap();
+        match enc_info {
+            Some(enc_info) => {
+                let _ = enc_infos.insert(thing_id.to_string(), enc_info.to_string());
+            }
+            None => {
+                let _ = enc_infos.remove(thing_id);
+            }
+        }
+    }
+
+    pub fn get_thing_enc_info(&self, thing_id: &str) -> Option<String> {
+        self.enc_infos.lock().unwrap().get(thing_id).cloned()
+    }
+
     // Private helper
-    fn get_mdata(&self, thing_id: &str) -> ResultReturn<MutableData> {
-        let xor_name = self.safe_net.gen_xor_name(thing_id);
-        self.safe_net
-            .get_pub_mutable_data(xor_name, SAFE_THING_TYPE_TAG)
+    fn get_mdata(&self, thing_id: &str) -> ResultReturn<S::MData> {
+        let xor_name = self.net().gen_xor_name(thing_id);
+        match self.get_thing_enc_info(thing_id) {
+            Some(enc_info) => {
+                self.net()
+                    .get_priv_mutable_data(xor_name, SAFE_THING_TYPE_TAG, &enc_info)
+            }
+            None => self
+                .net()
+                .get_pub_mutable_data(xor_name, SAFE_THING_TYPE_TAG),
+        }
+    }
+
+    // Private helper to get the public MutableData of a private SAFEthing
+    fn get_pub_mdata(&self, thing_id: &str) -> ResultReturn<S::MData> {
+        let xor_name = self.net().gen_xor_name(thing_id);
+        self.net()
+            .get_pub_mutable_data(xor_name, SAFE_THING_PUB_TYPE_TAG)
     }
 
     pub fn get_thing_attrs(&self, thing_id: &str) -> ResultReturn<String> {
         let thing_mdata = self.get_mdata(thing_id)?;
-        self.safe_net
+        self.net()
             .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_ATTRS)
     }
 
     pub fn set_topics(&self, topics: &str) -> ResultReturn<()> {
-        self.safe_net.mutable_data_set_value(
-            &self.thing_mdata,
-            SAFE_THING_ENTRY_K_TOPICS,
-            topics,
-        )?;
+        self.net()
+            .mutable_data_set_value(&self.own_mdata(), SAFE_THING_ENTRY_K_TOPICS, topics)?;
         Ok(())
     }
 
     pub fn get_thing_topics(&self, thing_id: &str) -> ResultReturn<String> {
         let thing_mdata = self.get_mdata(thing_id)?;
-        self.safe_net
+        self.net()
             .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_TOPICS)
     }
 
     pub fn set_actions(&self, actions: &str) -> ResultReturn<()> {
-        self.safe_net.mutable_data_set_value(
-            &self.thing_mdata,
+        self.net().mutable_data_set_value(
+            &self.own_mdata(),
             SAFE_THING_ENTRY_K_ACTIONS,
             actions,
         )?;
@@ -190,14 +268,13 @@ impl SAFEthingComm {
 
     pub fn get_thing_actions(&self, thing_id: &str) -> ResultReturn<String> {
         let thing_mdata = self.get_mdata(thing_id)?;
-        self.safe_net
+        self.net()
             .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_ACTIONS)
     }
 
-    #[allow(dead_code)]
     pub fn set_subscriptions(&self, subscriptions: &str) -> ResultReturn<()> {
-        self.safe_net.mutable_data_set_value(
-            &self.thing_mdata,
+        self.net().mutable_data_set_value(
+            &self.own_mdata(),
             SAFE_THING_ENTRY_K_SUBSCRIPTIONS,
             subscriptions,
         )?;
@@ -209,26 +286,110 @@ impl SAFEthingComm {
         let thing_mdata = self.get_mdata(&self.thing_id)?;
 
         match self
-            .safe_net
+            .net()
             .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_SUBSCRIPTIONS)
         {
             Ok(str) => Ok(str),
-            Err(_) => Ok(String::from("{}")),
+            // Only if it never subscribed to anything, any other error is reported so
+            // the subscriptions are not overwritten with an empty list
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => Ok(String::from("{}")),
+                _ => Err(err),
+            },
+        }
+    }
+
+    pub fn sign_key(&self) -> &str {
+        &self.sign_key
+    }
+
+    pub fn set_sign_key(&self) -> ResultReturn<()> {
+        self.net().mutable_data_set_value(
+            &self.own_mdata(),
+            SAFE_THING_ENTRY_K_SIGN_KEY,
+            &self.sign_key,
+        )?;
+        Ok(())
+    }
+
+    pub fn get_thing_sign_key(&self, thing_id: &str) -> ResultReturn<String> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        self.net()
+            .mutable_data_get_value(&thing_mdata, SAFE_THING_ENTRY_K_SIGN_KEY)
+    }
+
+    // The encryption key changes on every session, it's signed so the data sealed
+    // with it can only be read by the SAFEthing it was published by
+    pub fn set_pub_enc_key(&self) -> ResultReturn<()> {
+        let pub_enc_key = self.net().pub_enc_key()?;
+        self.set_signed_entry(SAFE_THING_ENTRY_K_ENC_KEY, &pub_enc_key)
+    }
+
+    pub fn get_thing_pub_enc_key(&self, thing_id: &str) -> ResultReturn<String> {
+        self.get_thing_signed_entry(thing_id, SAFE_THING_ENTRY_K_ENC_KEY)
+    }
+
+    // Anyone can insert entries, thus the list of keys authorised, the groups, and the
+    // owner (empty until the SAFEthing is claimed) are signed by the SAFEthing, and they
+    // are reset if they are missing or they weren't signed by it, e.g. if someone else
+    // inserted them before the SAFEthing was registered
+    pub fn init_access_entries(&self) -> ResultReturn<()> {
+        let entries = self.net().mutable_data_get_entries(&self.own_mdata())?;
+        let is_valid = |entry_key: &str| {
+            entries.iter().any(|(key, value)| {
+                key == entry_key
+                    && self
+                        .verify_entry(&self.thing_id, &self.sign_key, entry_key, value)
+                        .is_some()
+            })
+        };
+        let defaults = [
+            (SAFE_THING_ENTRY_K_AUTHORISED, "{}"),
+            (SAFE_THING_ENTRY_K_GROUPS, "{}"),
+            (SAFE_THING_ENTRY_K_OWNER, ""),
+        ];
+        for (entry_key, default) in defaults.iter() {
+            if !is_valid(entry_key) {
+                if entries.iter().any(|(key, _)| key == entry_key) {
+                    warn!(
+                        "Resetting entry '{}' as it wasn't signed by the SAFEthing",
+                        entry_key
+                    );
+                }
+                self.set_signed_entry(entry_key, default)?;
+            }
         }
+        Ok(())
+    }
+
+    pub fn set_authorised(&self, authorised: &str) -> ResultReturn<()> {
+        self.set_signed_entry(SAFE_THING_ENTRY_K_AUTHORISED, authorised)
+    }
+
+    pub fn get_thing_authorised(&self, thing_id: &str) -> ResultReturn<String> {
+        self.get_thing_signed_entry(thing_id, SAFE_THING_ENTRY_K_AUTHORISED)
+    }
+
+    pub fn set_groups(&self, groups: &str) -> ResultReturn<()> {
+        self.set_signed_entry(SAFE_THING_ENTRY_K_GROUPS, groups)
+    }
+
+    pub fn get_thing_groups(&self, thing_id: &str) -> ResultReturn<String> {
+        self.get_thing_signed_entry(thing_id, SAFE_THING_ENTRY_K_GROUPS)
     }
 
     pub fn set_topic_events(&self, topic: &str, events: &str) -> ResultReturn<()> {
         let topic_entry_key = SAFE_THING_ENTRY_K_EVENTS.to_owned() + topic;
-        self.safe_net
-            .mutable_data_set_value(&self.thing_mdata, &topic_entry_key, events)?;
+        self.net()
+            .mutable_data_set_value(&self.own_mdata(), &topic_entry_key, events)?;
         Ok(())
     }
 
     pub fn get_topic_events(&self, topic: &str) -> ResultReturn<(String)> {
         let topic_entry_key = SAFE_THING_ENTRY_K_EVENTS.to_owned() + topic;
         match self
-            .safe_net
-            .mutable_data_get_value(&self.thing_mdata, &topic_entry_key)
+            .net()
+            .mutable_data_get_value(&self.own_mdata(), &topic_entry_key)
         {
             Ok(str) => Ok(str),
             Err(_) => Ok(String::from("[]")),
@@ -239,26 +400,89 @@ impl SAFEthingComm {
         let topic_entry_key = SAFE_THING_ENTRY_K_EVENTS.to_owned() + topic;
         let thing_mdata = self.get_mdata(thing_id)?;
         match self
-            .safe_net
+            .net()
             .mutable_data_get_value(&thing_mdata, &topic_entry_key)
         {
             Ok(str) => Ok(str),
-            Err(_) => Ok(String::from("[]")),
+            // No events were notified yet for the topic
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => Ok(String::from("[]")),
+                _ => Err(err),
+            },
         }
     }
 
-    pub fn send_action_request(&self, thing_id: &str, action_req: &str) -> ResultReturn<u128> {
-        let start = SystemTime::now();
-        let since_the_epoch = start
-            .duration_since(UNIX_EPOCH)
-            .expect("Failed to get time since epoch");
-        let request_id: u128 = since_the_epoch.as_nanos();
-        let actions_req_key = format!("{}{:?}", SAFE_THING_ENTRY_K_ACTION_REQ, request_id);
+    // The events of the topics which are not for All are sealed for each subscriber, they are
+    // kept in an entry per subscriber and signed so no one else can insert fake events in it
+    pub fn set_sealed_topic_events(
+        &self,
+        topic: &str,
+        subscriber_key: &str,
+        events: &str,
+    ) -> ResultReturn<()> {
+        let entry_key = format!("{}{}:{}", SAFE_THING_ENTRY_K_EVENTS, topic, subscriber_key);
+        self.set_signed_entry(&entry_key, events)
+    }
+
+    pub fn get_sealed_topic_events(&self, topic: &str, subscriber_key: &str) -> String {
+        let entry_key = format!("{}{}:{}", SAFE_THING_ENTRY_K_EVENTS, topic, subscriber_key);
+        match self.get_thing_signed_entry(&self.thing_id, &entry_key) {
+            Ok(str) => str,
+            Err(_) => String::from("[]"),
+        }
+    }
+
+    pub fn get_thing_sealed_topic_events(
+        &self,
+        thing_id: &str,
+        topic: &str,
+    ) -> ResultReturn<String> {
+        let entry_key = format!("{}{}:{}", SAFE_THING_ENTRY_K_EVENTS, topic, self.sign_key);
+        match self.get_thing_signed_entry(thing_id, &entry_key) {
+            Ok(str) => Ok(str),
+            // No events were sealed yet for this subscriber
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => Ok(String::from("[]")),
+                _ => Err(err),
+            },
+        }
+    }
+
+    // The subscribers of the topics which are not for All let the SAFEthing know the key
+    // to seal the events for them with, the latest entry of each subscriber is the valid one
+    pub fn send_topic_subscriber(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+        subscriber: &str,
+    ) -> ResultReturn<()> {
         let thing_mdata = self.get_mdata(thing_id)?;
-        self.safe_net
-            .mutable_data_set_value(&thing_mdata, &actions_req_key, action_req)?;
+        self.send_request(
+            SAFE_THING_ENTRY_K_SUBSCRIBER,
+            &thing_mdata,
+            request_id,
+            subscriber,
+        )
+    }
 
-        Ok(request_id)
+    pub fn get_topic_subscribers(&self) -> ResultReturn<Vec<(u128, String)>> {
+        let thing_mdata = self.get_mdata(&self.thing_id)?;
+        self.get_requests(SAFE_THING_ENTRY_K_SUBSCRIBER, &thing_mdata)
+    }
+
+    pub fn send_action_request(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+        action_req: &str,
+    ) -> ResultReturn<()> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        self.send_request(
+            SAFE_THING_ENTRY_K_ACTION_REQ,
+            &thing_mdata,
+            request_id,
+            action_req,
+        )
     }
 
     pub fn get_thing_action_request_state(
@@ -266,66 +490,428 @@ impl SAFEthingComm {
         thing_id: &str,
         request_id: u128,
     ) -> ResultReturn<(String)> {
-        let actions_req_key = format!("{}{:?}", SAFE_THING_ENTRY_K_ACTION_REQ, request_id);
         let thing_mdata = self.get_mdata(thing_id)?;
-        let action_req: String = match self
-            .safe_net
-            .mutable_data_get_value(&thing_mdata, &actions_req_key)
-        {
-            Ok(str) => str,
-            Err(_) => String::from("{}"),
-        };
-
-        Ok(action_req)
+        self.get_thing_request_state(SAFE_THING_ENTRY_K_ACTION_REQ, &thing_mdata, request_id)
     }
 
     pub fn get_actions_requests(&self) -> ResultReturn<(Vec<(u128, String)>)> {
         // FIXME: we are not being able to retrieve the entry with self.thing_mdata
         let thing_mdata = self.get_mdata(&self.thing_id)?;
+        self.get_requests(SAFE_THING_ENTRY_K_ACTION_REQ, &thing_mdata)
+    }
 
-        let actions_reqs = match self.safe_net.mutable_data_get_entries(&thing_mdata) {
-            Ok(entries) => {
-                entries
-                    .iter()
-                    .filter_map(|(key, value)| {
-                        // let's filter the soft-deleted values and those whic are not action requests
-                        if key.starts_with(SAFE_THING_ENTRY_K_ACTION_REQ) && !value.is_empty() {
-                            let request_id = key
-                                .replace(SAFE_THING_ENTRY_K_ACTION_REQ, "")
-                                .parse::<u128>()
-                                .unwrap();
-                            Some((request_id, value.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            }
-            Err(_) => vec![],
-        };
+    pub fn set_action_request_state(&self, request_id: u128, new_state: &str) -> ResultReturn<()> {
+        // FIXME: we are not being able to retrieve the entry with self.thing_mdata
+        let thing_mdata = self.get_mdata(&self.thing_id)?;
+        self.set_request_state(
+            SAFE_THING_ENTRY_K_ACTION_REQ,
+            &thing_mdata,
+            request_id,
+            new_state,
+        )
+    }
 
-        Ok(actions_reqs)
+    pub fn send_claim_request(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+        claim_req: &str,
+    ) -> ResultReturn<()> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        self.send_request(
+            SAFE_THING_ENTRY_K_CLAIM_REQ,
+            &thing_mdata,
+            request_id,
+            claim_req,
+        )
     }
 
-    pub fn set_action_request_state(&self, request_id: u128, new_state: &str) -> ResultReturn<()> {
-        let actions_req_key = format!("{}{:?}", SAFE_THING_ENTRY_K_ACTION_REQ, request_id);
+    pub fn get_thing_claim_request_state(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+    ) -> ResultReturn<String> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        self.get_thing_request_state(SAFE_THING_ENTRY_K_CLAIM_REQ, &thing_mdata, request_id)
+    }
 
-        // FIXME: we are not being able to retrieve the entry with self.thing_mdata
+    pub fn get_claim_requests(&self) -> ResultReturn<Vec<(u128, String)>> {
+        let thing_mdata = self.get_mdata(&self.thing_id)?;
+        self.get_requests(SAFE_THING_ENTRY_K_CLAIM_REQ, &thing_mdata)
+    }
+
+    pub fn set_claim_request_state(&self, request_id: u128, new_state: &str) -> ResultReturn<()> {
         let thing_mdata = self.get_mdata(&self.thing_id)?;
+        self.set_request_state(
+            SAFE_THING_ENTRY_K_CLAIM_REQ,
+            &thing_mdata,
+            request_id,
+            new_state,
+        )
+    }
+
+    // The requests for the encryption info of a private SAFEthing are sent to its
+    // public MutableData, the encryption info is sealed in them for the requester
+    pub fn send_enc_info_request(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+        enc_info_req: &str,
+    ) -> ResultReturn<()> {
+        let pub_mdata = self.get_pub_mdata(thing_id)?;
+        self.send_request(
+            SAFE_THING_ENTRY_K_ENC_INFO_REQ,
+            &pub_mdata,
+            request_id,
+            enc_info_req,
+        )
+    }
+
+    pub fn get_thing_enc_info_request_state(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+    ) -> ResultReturn<String> {
+        let pub_mdata = self.get_pub_mdata(thing_id)?;
+        self.get_thing_request_state(SAFE_THING_ENTRY_K_ENC_INFO_REQ, &pub_mdata, request_id)
+    }
+
+    pub fn get_enc_info_requests(&self) -> ResultReturn<Vec<(u128, String)>> {
+        let pub_mdata = self.get_pub_mdata(&self.thing_id)?;
+        self.get_requests(SAFE_THING_ENTRY_K_ENC_INFO_REQ, &pub_mdata)
+    }
+
+    pub fn set_enc_info_request_state(
+        &self,
+        request_id: u128,
+        new_state: &str,
+    ) -> ResultReturn<()> {
+        let pub_mdata = self.get_pub_mdata(&self.thing_id)?;
+        self.set_request_state(
+            SAFE_THING_ENTRY_K_ENC_INFO_REQ,
+            &pub_mdata,
+            request_id,
+            new_state,
+        )
+    }
+
+    pub fn set_owner(&self, owner_key: &str) -> ResultReturn<()> {
+        self.set_signed_entry(SAFE_THING_ENTRY_K_OWNER, owner_key)
+    }
+
+    pub fn get_thing_owner(&self, thing_id: &str) -> ResultReturn<String> {
+        self.get_thing_signed_entry(thing_id, SAFE_THING_ENTRY_K_OWNER)
+    }
+
+    // Private helpers to store the entries signed by this SAFEthing, the value is the
+    // signature followed by the data, and the ID of the SAFEthing and the entry key
+    // are signed along with the data so the value cannot be copied to another entry
+    fn set_signed_entry(&self, entry_key: &str, data: &str) -> ResultReturn<()> {
+        let signature = self.sign(&format!("{}:{}:{}", self.thing_id, entry_key, data))?;
+        self.net().mutable_data_set_value(
+            &self.own_mdata(),
+            entry_key,
+            &format!("{}:{}", signature, data),
+        )
+    }
+
+    fn get_thing_signed_entry(&self, thing_id: &str, entry_key: &str) -> ResultReturn<String> {
+        let thing_mdata = self.get_mdata(thing_id)?;
+        let value = self.net().mutable_data_get_value(&thing_mdata, entry_key)?;
+        let thing_key = if thing_id == self.thing_id {
+            self.sign_key.clone()
+        } else {
+            self.get_thing_sign_key(thing_id)?
+        };
+        self.verify_entry(thing_id, &thing_key, entry_key, &value)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorCode::AccessDenied,
+                    format!(
+                        "Entry '{}' of thing {} wasn't signed by it",
+                        entry_key, thing_id
+                    )
+                    .as_str(),
+                )
+            })
+    }
+
+    fn verify_entry(
+        &self,
+        thing_id: &str,
+        thing_key: &str,
+        entry_key: &str,
+        value: &str,
+    ) -> Option<String> {
+        let mut parts = value.splitn(2, ':');
+        let (signature, data) = match (parts.next(), parts.next()) {
+            (Some(signature), Some(data)) => (signature, data),
+            _ => return None,
+        };
+        let signed_data = format!("{}:{}:{}", thing_id, entry_key, data);
+        match self.verify(thing_key, &signed_data, signature) {
+            Ok(true) => Some(data.to_string()),
+            _ => None,
+        }
+    }
+
+    // Sign some data with the app's secret key, returning the signature hex encoded
+    pub fn sign(&self, data: &str) -> ResultReturn<String> {
+        self.net().sign(data)
+    }
+
+    pub fn verify(&self, pub_sign_key: &str, data: &str, signature: &str) -> ResultReturn<bool> {
+        self.net().verify(pub_sign_key, data, signature)
+    }
+
+    pub fn pub_enc_key(&self) -> ResultReturn<String> {
+        self.net().pub_enc_key()
+    }
 
-        self.safe_net
-            .mutable_data_set_value(&thing_mdata, &actions_req_key, new_state)?;
+    pub fn encrypt_sealed(&self, pub_enc_key: &str, data: &str) -> ResultReturn<String> {
+        self.net().encrypt_sealed(pub_enc_key, data)
+    }
+
+    pub fn decrypt_sealed(&self, ciphertext: &str) -> ResultReturn<String> {
+        self.net().decrypt_sealed(ciphertext)
+    }
+
+    // The ID of a request is known before sending it, thus it can be signed along with it
+    pub fn gen_request_id(&self) -> u128 {
+        let start = SystemTime::now();
+        let since_the_epoch = start
+            .duration_since(UNIX_EPOCH)
+            .expect("Failed to get time since epoch");
+        since_the_epoch.as_nanos()
+    }
+
+    // Private helpers to send requests to other SAFEthings and process the ones
+    // received, the entries of each type of request have a different prefix
+    fn send_request(
+        &self,
+        prefix: &str,
+        thing_mdata: &S::MData,
+        request_id: u128,
+        request: &str,
+    ) -> ResultReturn<()> {
+        let req_key = format!("{}{:?}", prefix, request_id);
+        self.net()
+            .mutable_data_set_value(thing_mdata, &req_key, request)
+    }
+
+    fn get_thing_request_state(
+        &self,
+        prefix: &str,
+        thing_mdata: &S::MData,
+        request_id: u128,
+    ) -> ResultReturn<String> {
+        let req_key = format!("{}{:?}", prefix, request_id);
+        let request: String = match self.net().mutable_data_get_value(thing_mdata, &req_key) {
+            Ok(str) => str,
+            Err(_) => String::from("{}"),
+        };
+
+        Ok(request)
+    }
+
+    fn get_requests(
+        &self,
+        prefix: &str,
+        thing_mdata: &S::MData,
+    ) -> ResultReturn<Vec<(u128, String)>> {
+        let entries = self.net().mutable_data_get_entries(thing_mdata)?;
+        let requests = entries
+            .iter()
+            .filter_map(|(key, value)| {
+                // let's filter the soft-deleted values and those which are other type of entries
+                if key.starts_with(prefix) && !value.is_empty() {
+                    // anyone can insert entries, thus keys without a valid ID are skipped
+                    match key[prefix.len()..].parse::<u128>() {
+                        Ok(request_id) => Some((request_id, value.clone())),
+                        Err(_) => {
+                            debug!("Skipping request with an invalid ID: {}", key);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(requests)
+    }
+
+    fn set_request_state(
+        &self,
+        prefix: &str,
+        thing_mdata: &S::MData,
+        request_id: u128,
+        new_state: &str,
+    ) -> ResultReturn<()> {
+        let req_key = format!("{}{:?}", prefix, request_id);
+        self.net()
+            .mutable_data_set_value(thing_mdata, &req_key, new_state)?;
 
         Ok(())
     }
 
-    pub fn sim_net_disconnect(&mut self) {
-        self.safe_net.sim_net_disconnect();
+    pub fn sim_net_disconnect(&self) {
+        self.net().sim_net_disconnect();
+    }
+
+    // The lock is held until the guard is dropped, thus it shall not be
+    // kept while calling other functions which also need the connection
+    fn net(&self) -> MutexGuard<S> {
+        self.safe_net.lock().unwrap()
+    }
+
+    fn own_mdata(&self) -> S::MData {
+        self.thing_mdata.lock().unwrap().clone()
+    }
+}
+
+// Helper to map the status stored on the network
+fn parse_status(status_str: &str) -> ThingStatus {
+    if status_str == SAFE_THING_ENTRY_V_STATUS_CONNECTED {
+        ThingStatus::Connected
+    } else if status_str == SAFE_THING_ENTRY_V_STATUS_PUBLISHED {
+        ThingStatus::Published
+    } else if status_str == SAFE_THING_ENTRY_V_STATUS_DISABLED {
+        ThingStatus::Disabled
+    } else {
+        ThingStatus::Unknown
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::mem_store::MemStore;
+
+    #[test]
+    fn status_and_entity_data() {
+        let comm = SAFEthingComm::<MemStore>::new("comm-thing", "comm_test_status").unwrap();
+        comm.store_thing_entity(None).unwrap();
+        comm.set_status(ThingStatus::Published).unwrap();
+        match comm.get_status().unwrap() {
+            ThingStatus::Published => (),
+            status => panic!("Unexpected status: {:?}", status),
+        };
+        assert!(comm.set_status(ThingStatus::Unknown).is_err());
+
+        comm.set_topics("[]").unwrap();
+        assert_eq!(comm.get_thing_topics("comm-thing").unwrap(), "[]");
+        assert_eq!(comm.get_subscriptions().unwrap(), "{}");
+        assert_eq!(comm.get_topic_events("SomeTopic").unwrap(), "[]");
+    }
+
     #[test]
-    fn it_works() {}
+    fn action_requests() {
+        let network = "comm_test_action_requests";
+        let comm = SAFEthingComm::<MemStore>::new("comm-thing", network).unwrap();
+        comm.store_thing_entity(None).unwrap();
+        let requester = SAFEthingComm::<MemStore>::new("comm-requester", network).unwrap();
+
+        let request_id = requester.gen_request_id();
+        requester
+            .send_action_request("comm-thing", request_id, "request")
+            .unwrap();
+        let thing_mdata = requester.get_mdata("comm-thing").unwrap();
+        requester
+            .net()
+            .mutable_data_set_value(
+                &thing_mdata,
+                &format!("{}x", SAFE_THING_ENTRY_K_ACTION_REQ),
+                "request",
+            )
+            .unwrap();
+        assert_eq!(
+            comm.get_actions_requests().unwrap(),
+            vec![(request_id, "request".to_string())]
+        );
+
+        comm.set_action_request_state(request_id, "done").unwrap();
+        let state = requester
+            .get_thing_action_request_state("comm-thing", request_id)
+            .unwrap();
+        assert_eq!(state, "done");
+    }
+
+    #[test]
+    fn signatures() {
+        let network = "comm_test_signatures";
+        let comm = SAFEthingComm::<MemStore>::new("comm-signer", network).unwrap();
+        let other = SAFEthingComm::<MemStore>::new("comm-other", network).unwrap();
+
+        let signature = comm.sign("data").unwrap();
+        assert!(other.verify(comm.sign_key(), "data", &signature).unwrap());
+        assert!(!other
+            .verify(comm.sign_key(), "other data", &signature)
+            .unwrap());
+        assert!(!other.verify(other.sign_key(), "data", &signature).unwrap());
+    }
+
+    #[test]
+    fn access_entries() {
+        let network = "comm_test_access_entries";
+        let comm = SAFEthingComm::<MemStore>::new("comm-access", network).unwrap();
+        comm.store_thing_entity(None).unwrap();
+        comm.set_sign_key().unwrap();
+
+        // someone else inserts the owner and authorised entries before the thing does
+        let attacker = SAFEthingComm::<MemStore>::new("comm-attacker", network).unwrap();
+        let thing_mdata = attacker.get_mdata("comm-access").unwrap();
+        let forged_owner = format!(
+            "{}:{}",
+            attacker
+                .sign(&format!(
+                    "comm-access:{}:{}",
+                    SAFE_THING_ENTRY_K_OWNER, "attacker"
+                ))
+                .unwrap(),
+            "attacker"
+        );
+        attacker
+            .net()
+            .mutable_data_set_value(&thing_mdata, SAFE_THING_ENTRY_K_OWNER, &forged_owner)
+            .unwrap();
+        attacker
+            .net()
+            .mutable_data_set_value(
+                &thing_mdata,
+                SAFE_THING_ENTRY_K_AUTHORISED,
+                "{\"attacker\":[]}",
+            )
+            .unwrap();
+        assert!(attacker.get_thing_owner("comm-access").is_err());
+
+        comm.init_access_entries().unwrap();
+        assert_eq!(attacker.get_thing_owner("comm-access").unwrap(), "");
+        assert_eq!(attacker.get_thing_authorised("comm-access").unwrap(), "{}");
+        assert_eq!(attacker.get_thing_groups("comm-access").unwrap(), "{}");
+
+        comm.set_owner("owner").unwrap();
+        comm.init_access_entries().unwrap();
+        assert_eq!(attacker.get_thing_owner("comm-access").unwrap(), "owner");
+    }
+
+    #[test]
+    fn private_entity() {
+        let network = "comm_test_private_entity";
+        let comm = SAFEthingComm::<MemStore>::new("comm-private", network).unwrap();
+        let enc_info = comm.gen_enc_info().unwrap();
+        comm.store_thing_entity(Some(&enc_info)).unwrap();
+        comm.set_topics("[]").unwrap();
+        assert_eq!(
+            comm.get_thing_enc_info("comm-private"),
+            Some(enc_info.clone())
+        );
+
+        let peer = SAFEthingComm::<MemStore>::new("comm-peer", network).unwrap();
+        assert!(peer.get_thing_topics("comm-private").is_err());
+
+        peer.set_thing_enc_info("comm-private", Some(&enc_info));
+        assert_eq!(peer.get_thing_topics("comm-private").unwrap(), "[]");
+    }
 }
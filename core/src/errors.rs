@@ -24,6 +24,13 @@ pub enum ErrorCode {
     InvalidArgument,
     ConnectionErr,
     NetworkErr,
+    ThingNotPublished,
+    TopicNotFound,
+    AttrNotFound,
+    AttrNotDynamic,
+    Timeout,
+    NoSuchData,
+    AccessDenied,
 }
 
 #[derive(Debug)]
@@ -39,6 +46,10 @@ impl Error {
             info: String::from(info),
         }
     }
+
+    pub fn code(&self) -> &ErrorCode {
+        &self.code
+    }
 }
 
 impl fmt::Display for Error {
@@ -50,6 +61,13 @@ impl fmt::Display for Error {
                 ErrorCode::InvalidArgument => "Invalid argument",
                 ErrorCode::ConnectionErr => "Connection error",
                 ErrorCode::NetworkErr => "Network error",
+                ErrorCode::ThingNotPublished => "Thing not published",
+                ErrorCode::TopicNotFound => "Topic not found",
+                ErrorCode::AttrNotFound => "Attribute not found",
+                ErrorCode::AttrNotDynamic => "Attribute not dynamic",
+                ErrorCode::Timeout => "Timeout",
+                ErrorCode::NoSuchData => "No such data",
+                ErrorCode::AccessDenied => "Access denied",
             },
             (*self).info
         )
@@ -17,32 +17,49 @@
 
 mod comm;
 mod errors;
+mod mem_store;
 mod safe_net;
 mod safe_net_helpers;
+mod thing_store;
 
 use comm::{SAFEthingComm, ThingStatus};
-use errors::{Error, ErrorCode, ResultReturn};
+pub use errors::{Error, ErrorCode, ResultReturn};
 use log::{debug, error, info, trace, warn};
+pub use mem_store::MemStore;
+pub use safe_net::SAFENet;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{fmt, thread};
+pub use thing_store::ThingStore;
 
 const THING_ID_MIN_LENGTH: usize = 5;
 const SUBSCRIPTIONS_CHECK_FREQ: u64 = 5_000;
 const ACTION_REQUEST_CHECK_FREQ: u64 = 4_000;
 const ACTION_REQUEST_INIT_STATE: &str = "Requested";
 const ACTION_REQUEST_DONE_STATE: &str = "Done";
+const ACTION_REQUEST_REJECTED_STATE: &str = "Rejected";
+const ACTION_REQUEST_FAILED_STATE: &str = "Failed";
 const ACTION_REQUEST_MONITORING_FREQ: u64 = 2_000;
 const ACTION_REQUEST_MONITORING_TIMEOUT: u64 = 60_000;
+const ACTION_REQUEST_REPLY_CHECK_FREQ: u64 = 200;
+// The claims and the requests for the encryption info block until they are processed
+const PEER_REQUEST_CHECK_FREQ: u64 = 1_000;
+const PEER_REQUEST_TIMEOUT: u64 = 60_000;
+// The claim code is discarded after this many claim requests are rejected
+const MAX_CLAIM_REJECTIONS: u32 = 5;
 
 /// Group of SAFEthings that are allow to register to a topic
 /// Thing: access only to the thing's application. This is the default and lowest level of access type.
 /// Owner: access also is allowed to an individual, application or system that is the actual owner of the SAFEthing, plus the SAFEthing itself.
 /// Group: access to a group of individuals or SAFEthings, plus the Owner and the SAFEthing itself.
+///        The groups are created by the SAFEthing, and they can be attached to its topics and actions.
 /// All: access is allowed to anyone or anything, including the SAFEthing itself.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum AccessType {
     Thing,
     Owner,
@@ -79,11 +96,14 @@ impl fmt::Display for Status {
     }
 }
 
-/// Topic name and access type
+/// Topic name and access type, with Group access only the members of the groups
+/// attached are granted access, or the members of any group if none is attached
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Topic {
     pub name: String,
     pub access: AccessType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
 }
 
 impl Topic {
@@ -91,17 +111,28 @@ impl Topic {
         Topic {
             name: name.to_string(),
             access: access,
+            groups: vec![],
         }
     }
+
+    /// Attach groups to the topic, it's then given Group access for their members
+    pub fn for_groups(mut self, groups: &[&str]) -> Topic {
+        self.access = AccessType::Group;
+        self.groups = groups.iter().map(|g| g.to_string()).collect();
+        self
+    }
 }
 
 /// This is the structure which defines the attributes of a SAFEthing
-/// SAFEthings can subscribe for notifications upon changes detected on dynamic attributes
+/// SAFEthings can subscribe for notifications upon changes detected on dynamic attributes,
+/// if they are granted the access type of the attribute
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ThingAttr {
     pub attr: String,
     pub value: String,
     pub is_dynamic: bool,
+    #[serde(default = "access_all")]
+    pub access: AccessType,
 }
 
 impl ThingAttr {
@@ -110,41 +141,351 @@ impl ThingAttr {
             attr: attr.to_string(),
             value: value.to_string(),
             is_dynamic,
+            access: AccessType::All,
+        }
+    }
+
+    /// Set the access type required to subscribe to the attribute, it's All by default
+    pub fn with_access(mut self, access: AccessType) -> ThingAttr {
+        self.access = access;
+        self
+    }
+}
+
+// The attributes stored before they had an access type can be accessed by anyone
+fn access_all() -> AccessType {
+    AccessType::All
+}
+
+/// Type of the values an action parameter accepts, or an action returns as its output
+/// Number: a number, optionally within the range [min, max]
+/// Bool: either 'true' or 'false' ('1'/'0', 'yes'/'no' and 'on'/'off' are also accepted)
+/// String: any string
+/// Enum: one of the strings listed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ValueType {
+    Number { min: Option<f64>, max: Option<f64> },
+    Bool,
+    String,
+    Enum(Vec<String>),
+}
+
+impl ValueType {
+    /// Validate a value against the type, coercing it into a typed value
+    pub fn coerce(&self, value: &str) -> Result<ActionValue, String> {
+        match self {
+            ValueType::Number { min, max } => {
+                let number = match value.trim().parse::<f64>() {
+                    Ok(number) if number.is_finite() => number,
+                    _ => return Err(format!("'{}' is not a number", value)),
+                };
+                match (min, max) {
+                    (Some(min), _) if number < *min => {
+                        Err(format!("{} is lower than the minimum of {}", number, min))
+                    }
+                    (_, Some(max)) if number > *max => {
+                        Err(format!("{} is greater than the maximum of {}", number, max))
+                    }
+                    _ => Ok(ActionValue::Number(number)),
+                }
+            }
+            ValueType::Bool => match value.trim().to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(ActionValue::Bool(true)),
+                "false" | "0" | "no" | "off" => Ok(ActionValue::Bool(false)),
+                _ => Err(format!("'{}' is not a boolean", value)),
+            },
+            ValueType::String => Ok(ActionValue::Text(value.to_string())),
+            ValueType::Enum(options) => {
+                if options.iter().any(|option| option == value) {
+                    Ok(ActionValue::Text(value.to_string()))
+                } else {
+                    Err(format!("'{}' is not one of {:?}", value, options))
+                }
+            }
+        }
+    }
+
+    // Sanity check of the type definition itself
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ValueType::Number {
+                min: Some(min),
+                max: Some(max),
+            } if min > max => Err(format!("invalid range [{}, {}]", min, max)),
+            ValueType::Enum(options) if options.is_empty() => {
+                Err("an enum needs at least one option".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Value of an action argument, or an action output, coerced according to its `ValueType`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ActionValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl ActionValue {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ActionValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ActionValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ActionValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ActionValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActionValue::Bool(b) => write!(f, "{}", b),
+            ActionValue::Number(number) => write!(f, "{}", number),
+            ActionValue::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Input parameter of an action, the default value is used when the argument is not provided
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredActionParam")]
+pub struct ActionParam {
+    pub name: String,
+    pub value_type: ValueType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+// The parameters of the actions registered before they had types are just their
+// names, those are taken as string parameters without a default value
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredActionParam {
+    Typed {
+        name: String,
+        value_type: ValueType,
+        #[serde(default)]
+        default: Option<String>,
+    },
+    Legacy(String),
+}
+
+impl From<StoredActionParam> for ActionParam {
+    fn from(param: StoredActionParam) -> ActionParam {
+        match param {
+            StoredActionParam::Typed {
+                name,
+                value_type,
+                default,
+            } => ActionParam {
+                name,
+                value_type,
+                default,
+            },
+            StoredActionParam::Legacy(name) => ActionParam::new(&name, ValueType::String, None),
+        }
+    }
+}
+
+impl ActionParam {
+    pub fn new(name: &str, value_type: ValueType, default: Option<&str>) -> ActionParam {
+        ActionParam {
+            name: name.to_string(),
+            value_type,
+            default: default.map(|d| d.to_string()),
         }
     }
 }
 
-/// Actions that can be requested to a SAFEthing
+/// Actions that can be requested to a SAFEthing, with the definition of the parameters
+/// it expects and the type of its output, if it returns any.
+/// The groups attached restrict the Group access the same way as for the topics.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActionDef {
     pub name: String,
     pub access: AccessType,
-    pub params: Vec<String>,
+    pub params: Vec<ActionParam>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<ValueType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
 }
 
 impl ActionDef {
+    /// Action which expects string parameters and has no output
     pub fn new(name: &str, access: AccessType, params: &[&str]) -> ActionDef {
         let mut parameters = vec![];
-        parameters.extend(params.iter().map(|&p| p.to_string()));
+        parameters.extend(
+            params
+                .iter()
+                .map(|&p| ActionParam::new(p, ValueType::String, None)),
+        );
         ActionDef {
             name: name.to_string(),
             access: access,
             params: parameters,
+            output: None,
+            groups: vec![],
+        }
+    }
+
+    /// Action with typed parameters and, optionally, the type of its output
+    pub fn with_types(
+        name: &str,
+        access: AccessType,
+        params: &[ActionParam],
+        output: Option<ValueType>,
+    ) -> ActionDef {
+        ActionDef {
+            name: name.to_string(),
+            access,
+            params: params.to_vec(),
+            output,
+            groups: vec![],
+        }
+    }
+
+    /// Attach groups to the action, it's then given Group access for their members
+    pub fn for_groups(mut self, groups: &[&str]) -> ActionDef {
+        self.access = AccessType::Group;
+        self.groups = groups.iter().map(|g| g.to_string()).collect();
+        self
+    }
+
+    // Check the types are valid, the defaults match them, and only the last parameters have defaults
+    fn validate(&self) -> Result<(), String> {
+        if !self.groups.is_empty() && self.access != AccessType::Group {
+            return Err(format!("groups attached to {:?} access", self.access));
+        }
+        let mut default_found = false;
+        for param in self.params.iter() {
+            param
+                .value_type
+                .validate()
+                .map_err(|err| format!("parameter '{}': {}", param.name, err))?;
+            match &param.default {
+                Some(default) => {
+                    default_found = true;
+                    param.value_type.coerce(default).map_err(|err| {
+                        format!("default value of parameter '{}': {}", param.name, err)
+                    })?;
+                }
+                None if default_found => {
+                    return Err(format!(
+                        "parameter '{}' needs a default value since a previous one has it",
+                        param.name
+                    ))
+                }
+                None => {}
+            }
+        }
+        if let Some(output) = &self.output {
+            output
+                .validate()
+                .map_err(|err| format!("output: {}", err))?;
         }
+        Ok(())
     }
 }
 
-pub type ActionArgs = Vec<String>; // the values are opaque for the framework
+pub type ActionArgs = Vec<String>; // the values are validated and coerced by the framework
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct ActionReq {
     pub thing_id: String,
+    #[serde(default)]
+    pub requester_key: String,
     pub action: String,
     pub args: ActionArgs,
+    #[serde(default)]
+    pub signature: String,
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ActionValue>,
+}
+
+// The claim code set on the SAFEthing, along with the number of claims rejected since
+#[derive(Debug)]
+struct ClaimCode {
+    code: String,
+    rejections: u32,
+}
+
+// The claim requests go through the same states as the action requests
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ClaimReq {
+    pub thing_id: String,
+    pub requester_key: String,
+    pub proof: String,
+    #[serde(default)]
+    pub signature: String,
     pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// The requests for the encryption info of a private SAFEthing too, once accepted
+// the encryption info is sealed in them with the requester's encryption key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct EncInfoReq {
+    pub thing_id: String,
+    pub requester_key: String,
+    pub enc_key: String,
+    #[serde(default)]
+    pub signature: String,
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enc_info: Option<String>,
+}
+
+/// State of an action request sent, along with its outcome once it's known
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionReqState {
+    pub state: String,
+    pub result: Option<ActionValue>,
+    pub error: Option<String>,
+}
+
+impl ActionReqState {
+    /// Whether the action request reached a state which won't change anymore,
+    /// i.e. it's either 'Done', 'Rejected' or 'Failed'
+    pub fn is_final(&self) -> bool {
+        self.state == ACTION_REQUEST_DONE_STATE
+            || self.state == ACTION_REQUEST_REJECTED_STATE
+            || self.state == ACTION_REQUEST_FAILED_STATE
+    }
+
+    /// The output of the action if it has any, or the reason why the request
+    /// was rejected or failed
+    pub fn outcome(&self) -> Result<Option<&ActionValue>, &str> {
+        match self.error {
+            Some(ref err) => Err(err.as_str()),
+            None => Ok(self.result.as_ref()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum FilterOperator {
     Any,
     Equal,
@@ -179,33 +520,88 @@ impl FilterOperator {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Subscription made by a SAFEthing to a topic, or to a dynamic attribute, of another SAFEthing
+#[derive(Clone, Debug)]
+pub struct SubscriptionInfo {
+    pub thing_id: String,
+    pub name: String,
+    pub is_attr: bool,
+    pub filter_op: FilterOperator,
+    pub filter_value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct AttrSubscription {
     attr_name: String,
     filter_op: FilterOperator,
     filter_value: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct TopicSubscription {
     topic: String,
     filter_op: FilterOperator,
     filter_value: String,
+    // The events of the topics which are not for All are sealed for the subscriber
+    #[serde(default)]
+    sealed: bool,
+}
+
+// A subscriber of a topic which is not for All, which lets the SAFEthing know the key
+// to seal the events for it with. The key is empty once it unsubscribed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TopicSubscriber {
+    pub thing_id: String,
+    pub requester_key: String,
+    pub topic: String,
+    pub enc_key: String,
+    pub signature: String,
 }
 
 /// Each subscription can either be for a specific topic or for a dynamic attribute.
 /// We also keep track of the last time we checked the remote thing for any new topic event, or
 /// in the case of a attribute subscription we keep a copy of last value we reported in a
 /// notification to prevent from sending duplicate notifications.
+/// These are stored on the network too so they are not lost upon a device restart.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 enum Subscription {
     Topic((TopicSubscription, Timestamp)),
     Attr((AttrSubscription, String)),
 }
 
+impl Subscription {
+    // Take the last report from another copy of the same subscription if it's more recent
+    fn update_last_report(&mut self, other: &Subscription) -> bool {
+        match (self, other) {
+            (
+                Subscription::Topic((subs, timestamp)),
+                Subscription::Topic((other_subs, other_ts)),
+            ) if subs == other_subs && other_ts > timestamp => {
+                *timestamp = *other_ts;
+                true
+            }
+            (Subscription::Attr((subs, value)), Subscription::Attr((other_subs, other_value)))
+                if subs == other_subs && other_value != value =>
+            {
+                *value = other_value.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 /// Timestamps for events are all kept in nanos elapsed since epoch
 type Timestamp = u128;
 
+/// Public signing keys authorised to access the topics and actions of a SAFEthing,
+/// mapped to the access type granted to each of them (either Owner or Group)
+pub type AuthorisedKeys = BTreeMap<String, AccessType>;
+
+/// Named groups of a SAFEthing, each with its members which are either
+/// IDs of SAFEthings or public signing keys
+pub type ThingGroups = BTreeMap<String, BTreeSet<String>>;
+
 /// Several subscriptions can be registered for a remote SAFEthing.
 type ThingSubscriptions = Vec<Subscription>;
 
@@ -214,6 +610,10 @@ type ThingSubscriptions = Vec<Subscription>;
 /// This is just an in memory cache since it's all stored on the network.
 type RegisteredSubscriptions = BTreeMap<String, ThingSubscriptions>;
 
+/// The subscriptions are shared with the thread monitoring them,
+/// which keeps track of the last report made for each of them.
+type SharedSubscriptions = Arc<Mutex<RegisteredSubscriptions>>;
+
 /// Everytime a new event is emitted for any topic the SAFEthing has subscribed to,
 /// the framework will invoke the registered callback function.
 /// The following arguments are passed to the callback function:
@@ -221,8 +621,8 @@ type RegisteredSubscriptions = BTreeMap<String, ThingSubscriptions>;
 /// topic: the corresponding topic the event belongs to
 /// data: any data provided by the event emitter with the event
 /// timestamp: event timestamp as registered by the event emitter
-type SubsNotifCallback =
-    Fn(&SAFEthing, &str, &str, &str, Timestamp) + std::marker::Send + std::marker::Sync;
+type SubsNotifCallback<S = SAFENet> =
+    Fn(&SAFEthing<S>, &str, &str, &str, Timestamp) + std::marker::Send + std::marker::Sync;
 
 /// Every action reqeust is assigned its unique identifier
 type ActionReqId = u128;
@@ -231,32 +631,192 @@ type ActionReqId = u128;
 /// will invoke a callback function for the SAFEthing can act upon it.
 /// The following arguments are passed to the callback function:
 /// request_id: an unique identifier for the action request
-/// thing_id: identifier of the SAFEthing sending the action request
+/// thing_id: identifier of the SAFEthing sending the action request, the framework verified
+/// the request was signed with the key published by that SAFEthing
 /// action: the name of the action
-/// args: the list of arguments provided for the action
-type ActionReqCallback =
-    Fn(&SAFEthing, ActionReqId, &str, &str, &[&str]) + std::marker::Send + std::marker::Sync;
+/// args: the list of arguments for the action, already validated and coerced according
+/// to its parameters definition, including the default values of those not provided
+/// The callback returns the outcome of the action, which is stored in the action request.
+type ActionReqCallback<S = SAFENet> = Fn(&SAFEthing<S>, ActionReqId, &str, &str, &[ActionValue]) -> ActionResult
+    + std::marker::Send
+    + std::marker::Sync;
+
+/// Outcome of an action as returned by the callback handling the request: either the output
+/// of the action, if it defines one, or the reason why it failed.
+/// The output is validated and coerced according to the output type of the action, and the
+/// action request is then set to the 'Done' state, or to the 'Failed' state upon an error.
+pub type ActionResult = Result<Option<String>, String>;
+
+/// Every time the state of an action request sent to a SAFEthing changes, the framework
+/// will invoke the callback function provided when sending the request.
+/// The following arguments are passed to the callback function:
+/// state: the new state of the action request
+/// outcome: the output of the action if it has any, or the reason why the request
+/// was rejected or failed
+/// The callback shall return true to keep being notified about new states.
+type ActionReqStateCallback =
+    Fn(&str, Result<Option<&ActionValue>, &str>) -> bool + std::marker::Send + std::marker::Sync;
+
+/// Events received by a SAFEthing created with `with_events`, for the application
+/// to process them from its own loop rather than from the framework's threads
+#[derive(Debug)]
+pub enum ThingEvent {
+    /// A notification for any of the subscriptions made
+    Notification {
+        thing_id: String,
+        topic: String,
+        data: String,
+        timestamp: Timestamp,
+    },
+    /// An action request received, its outcome shall be sent back with `reply`.
+    /// The framework doesn't process other action requests until then.
+    ActionRequest {
+        request_id: ActionReqId,
+        thing_id: String,
+        action: String,
+        args: Vec<ActionValue>,
+        reply: ActionReqReply,
+    },
+}
+
+/// Sends the outcome of an action request received as a `ThingEvent` back to the framework,
+/// the request fails if it's dropped without sending it
+#[derive(Debug)]
+pub struct ActionReqReply(mpsc::Sender<ActionResult>);
+
+impl ActionReqReply {
+    pub fn send(self, result: ActionResult) {
+        // the framework is only gone if the SAFEthing was dropped
+        let _ = self.0.send(result);
+    }
+}
+
+/// Signal the background threads of a SAFEthing wait on between their checks,
+/// they finish as soon as it's stopped
+#[derive(Default)]
+struct StopSignal {
+    stopped: Mutex<bool>,
+    cvar: Condvar,
+}
+
+impl StopSignal {
+    fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.cvar.notify_all();
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    // Wait for the time given, returning true if it was stopped meanwhile
+    fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut stopped = self.stopped.lock().unwrap();
+        while !*stopped {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            stopped = self.cvar.wait_timeout(stopped, deadline - now).unwrap().0;
+        }
+        *stopped
+    }
+}
 
-#[derive(Clone)]
-pub struct SAFEthing {
+/// A SAFEthing stores all its information on a `ThingStore` backend,
+/// which is the SAFE Network unless a different one is specified.
+pub struct SAFEthing<S: ThingStore = SAFENet> {
     pub thing_id: String,
-    safe_thing_comm: SAFEthingComm,
-    subscriptions: RegisteredSubscriptions,
-    subsc_thread_channel_tx: Option<Sender<(String, ThingSubscriptions)>>,
-    notifs_cb: &'static SubsNotifCallback,
-    action_req_cb: &'static ActionReqCallback,
+    safe_thing_comm: Arc<SAFEthingComm<S>>,
+    subscriptions: SharedSubscriptions,
+    notifs_cb: Arc<SubsNotifCallback<S>>,
+    action_req_cb: Arc<ActionReqCallback<S>>,
+    // Replaced when the background threads are restarted, the old ones keep the signal
+    // they were stopped with
+    stop_signal: Arc<Mutex<Arc<StopSignal>>>,
+    // One-time code to claim the SAFEthing, it's only kept in memory
+    claim_code: Arc<Mutex<Option<ClaimCode>>>,
+    // Background threads, shared by all the instances so they are spawned only once
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    // Number of instances held by the application, the background threads are stopped
+    // when the last one is dropped. The ones given to the background threads aren't counted.
+    instances: Arc<AtomicUsize>,
+    counted: bool,
+}
+
+impl<S: ThingStore> Clone for SAFEthing<S> {
+    fn clone(&self) -> SAFEthing<S> {
+        let _ = self.instances.fetch_add(1, Ordering::SeqCst);
+        SAFEthing {
+            thing_id: self.thing_id.clone(),
+            safe_thing_comm: Arc::clone(&self.safe_thing_comm),
+            subscriptions: Arc::clone(&self.subscriptions),
+            notifs_cb: Arc::clone(&self.notifs_cb),
+            action_req_cb: Arc::clone(&self.action_req_cb),
+            stop_signal: Arc::clone(&self.stop_signal),
+            claim_code: Arc::clone(&self.claim_code),
+            workers: Arc::clone(&self.workers),
+            instances: Arc::clone(&self.instances),
+            counted: true,
+        }
+    }
+}
+
+impl<S: ThingStore> Drop for SAFEthing<S> {
+    fn drop(&mut self) {
+        if self.counted && self.instances.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.stop_workers();
+        }
+    }
 }
 
 impl SAFEthing {
     /// The thing id shall be an opaque string, and the auth URI shall not contain any
     /// scheme/protocol (i.e. without 'safe-...:' prefix) but just the encoded authorisation
-    pub fn new(
+    pub fn new<N, A>(
+        thing_id: &str,
+        auth_uri: &str,
+        notifs_cb: N,
+        action_req_cb: A,
+    ) -> ResultReturn<SAFEthing>
+    where
+        N: Fn(&SAFEthing, &str, &str, &str, Timestamp) + Send + Sync + 'static,
+        A: Fn(&SAFEthing, ActionReqId, &str, &str, &[ActionValue]) -> ActionResult
+            + Send
+            + Sync
+            + 'static,
+    {
+        SAFEthing::with_store(thing_id, auth_uri, notifs_cb, action_req_cb)
+    }
+
+    /// Same as `new` but the notifications and action requests are received
+    /// as `ThingEvent`s from the channel returned
+    pub fn with_events(
+        thing_id: &str,
+        auth_uri: &str,
+    ) -> ResultReturn<(SAFEthing, Receiver<ThingEvent>)> {
+        SAFEthing::with_store_events(thing_id, auth_uri)
+    }
+}
+
+impl<S: ThingStore> SAFEthing<S> {
+    /// Same as `new` but storing the SAFEthing on the `ThingStore` backend specified
+    /// as type parameter, the auth URI is passed as is to the backend to connect to it
+    pub fn with_store<N, A>(
         thing_id: &str,
         auth_uri: &str,
-        notifs_cb: &'static SubsNotifCallback,
-        action_req_cb: &'static ActionReqCallback,
-    ) -> ResultReturn<SAFEthing> {
-        env_logger::init();
+        notifs_cb: N,
+        action_req_cb: A,
+    ) -> ResultReturn<SAFEthing<S>>
+    where
+        N: Fn(&SAFEthing<S>, &str, &str, &str, Timestamp) + Send + Sync + 'static,
+        A: Fn(&SAFEthing<S>, ActionReqId, &str, &str, &[ActionValue]) -> ActionResult
+            + Send
+            + Sync
+            + 'static,
+    {
+        let _ = env_logger::try_init();
         if thing_id.len() < THING_ID_MIN_LENGTH {
             return Err(Error::new(
                 ErrorCode::InvalidArgument,
@@ -270,27 +830,203 @@ impl SAFEthing {
 
         let safe_thing = SAFEthing {
             thing_id: thing_id.to_string(),
-            safe_thing_comm: SAFEthingComm::new(thing_id, auth_uri)?,
-            subscriptions: RegisteredSubscriptions::default(),
-            subsc_thread_channel_tx: None,
-            notifs_cb: notifs_cb,
-            action_req_cb: action_req_cb,
+            safe_thing_comm: Arc::new(SAFEthingComm::new(thing_id, auth_uri)?),
+            subscriptions: SharedSubscriptions::default(),
+            notifs_cb: Arc::new(notifs_cb),
+            action_req_cb: Arc::new(action_req_cb),
+            stop_signal: Arc::default(),
+            claim_code: Arc::default(),
+            workers: Arc::default(),
+            instances: Arc::new(AtomicUsize::new(1)),
+            counted: true,
         };
 
         info!("SAFEthing instance created with ID: {}", thing_id);
         Ok(safe_thing)
     }
 
+    /// Same as `with_events` but storing the SAFEthing on the `ThingStore` backend
+    /// specified as type parameter
+    pub fn with_store_events(
+        thing_id: &str,
+        auth_uri: &str,
+    ) -> ResultReturn<(SAFEthing<S>, Receiver<ThingEvent>)> {
+        let (tx, rx) = mpsc::channel();
+        let notifs_tx = Mutex::new(tx.clone());
+        let action_req_tx = Mutex::new(tx);
+
+        let notifs_cb = move |_: &SAFEthing<S>,
+                              thing_id: &str,
+                              topic: &str,
+                              data: &str,
+                              timestamp: Timestamp| {
+            let event = ThingEvent::Notification {
+                thing_id: thing_id.to_string(),
+                topic: topic.to_string(),
+                data: data.to_string(),
+                timestamp,
+            };
+            if notifs_tx.lock().unwrap().send(event).is_err() {
+                warn!("Notification dropped as the events receiver is gone");
+            }
+        };
+
+        // The outcome of the request is awaited so it's handled as any other action request
+        let action_req_cb = move |safe_thing: &SAFEthing<S>,
+                                  request_id: ActionReqId,
+                                  thing_id: &str,
+                                  action: &str,
+                                  args: &[ActionValue]| {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            let event = ThingEvent::ActionRequest {
+                request_id,
+                thing_id: thing_id.to_string(),
+                action: action.to_string(),
+                args: args.to_vec(),
+                reply: ActionReqReply(reply_tx),
+            };
+            if action_req_tx.lock().unwrap().send(event).is_err() {
+                return Err("The SAFEthing is not receiving action requests".to_string());
+            }
+            loop {
+                match reply_rx.recv_timeout(Duration::from_millis(ACTION_REQUEST_REPLY_CHECK_FREQ))
+                {
+                    Ok(result) => return result,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err("The action request was not replied".to_string())
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        if safe_thing.stop_signal().is_stopped() {
+                            return Err("The SAFEthing was shut down".to_string());
+                        }
+                    }
+                }
+            }
+        };
+
+        let safe_thing = SAFEthing::with_store(thing_id, auth_uri, notifs_cb, action_req_cb)?;
+        Ok((safe_thing, rx))
+    }
+
     /// Register and re-register a SAFEthing specifying its attributes,
     /// events/topics and available actions
     pub fn register(
-        &mut self,
+        &self,
+        attrs: &[ThingAttr],
+        topics: &[Topic],
+        actions: &[ActionDef],
+    ) -> ResultReturn<()> {
+        self.register_entity(attrs, topics, actions, None)
+    }
+
+    /// Register and re-register a SAFEthing as a private and encrypted entity,
+    /// only the peers which were given the encryption info can access it
+    pub fn register_private(
+        &self,
+        attrs: &[ThingAttr],
+        topics: &[Topic],
+        actions: &[ActionDef],
+        enc_info: &str,
+    ) -> ResultReturn<()> {
+        self.register_entity(attrs, topics, actions, Some(enc_info))
+    }
+
+    /// Generate new encryption info to register a private SAFEthing with,
+    /// it needs to be kept to register it again after restarting
+    pub fn gen_enc_info(&self) -> ResultReturn<String> {
+        self.safe_thing_comm.gen_enc_info()
+    }
+
+    /// Set the encryption info needed to access a private SAFEthing
+    pub fn set_thing_enc_info(&self, thing_id: &str, enc_info: &str) {
+        self.safe_thing_comm
+            .set_thing_enc_info(thing_id, Some(enc_info));
+    }
+
+    /// Get the encryption info known for a private SAFEthing, including this one,
+    /// so it can be shared with other authorised peers
+    pub fn get_thing_enc_info(&self, thing_id: &str) -> Option<String> {
+        self.safe_thing_comm.get_thing_enc_info(thing_id)
+    }
+
+    /// Request the encryption info of a private SAFEthing, which seals it for this SAFEthing
+    /// only if its key is authorised or it's a member of any of its groups. It's then set to
+    /// access the private SAFEthing, and returned so it can be kept to set it after restarting.
+    /// It blocks until the request is processed, or it fails with `AccessDenied` if it's rejected.
+    pub fn fetch_thing_enc_info(&self, thing_id: &str) -> ResultReturn<String> {
+        let request_id = self.safe_thing_comm.gen_request_id();
+        let mut enc_info_req = EncInfoReq {
+            thing_id: self.thing_id.clone(),
+            requester_key: self.sign_key().to_string(),
+            enc_key: self.safe_thing_comm.pub_enc_key()?,
+            signature: String::new(),
+            state: ACTION_REQUEST_INIT_STATE.to_string(),
+            error: None,
+            enc_info: None,
+        };
+        enc_info_req.signature = self.safe_thing_comm.sign(&enc_info_req_signed_data(
+            thing_id,
+            request_id,
+            &enc_info_req,
+        ))?;
+        let enc_info_req_str = serde_json::to_string(&enc_info_req).unwrap();
+        self.safe_thing_comm
+            .send_enc_info_request(thing_id, request_id, &enc_info_req_str)?;
+        debug!(
+            "Encryption info request sent to thing {}: {}",
+            thing_id, request_id
+        );
+
+        let sealed = self.wait_peer_request(thing_id, "Encryption info request", || {
+            let enc_info_req_str = self
+                .safe_thing_comm
+                .get_thing_enc_info_request_state(thing_id, request_id)?;
+            match serde_json::from_str::<EncInfoReq>(&enc_info_req_str) {
+                Ok(enc_info_req) => match enc_info_req.enc_info {
+                    Some(sealed) if enc_info_req.state == ACTION_REQUEST_DONE_STATE => {
+                        Ok(Some(sealed))
+                    }
+                    _ => rejected_outcome(enc_info_req.state, enc_info_req.error),
+                },
+                Err(_) => Ok(None),
+            }
+        })?;
+        let enc_info = self.safe_thing_comm.decrypt_sealed(&sealed)?;
+        self.set_thing_enc_info(thing_id, &enc_info);
+        info!("Encryption info of thing {} fetched", thing_id);
+        Ok(enc_info)
+    }
+
+    // Private helper to register the entity either as public or private
+    fn register_entity(
+        &self,
         attrs: &[ThingAttr],
         topics: &[Topic],
         actions: &[ActionDef],
+        enc_info: Option<&str>,
     ) -> ResultReturn<()> {
+        for action in actions.iter() {
+            action.validate().map_err(|err| {
+                Error::new(
+                    ErrorCode::InvalidArgument,
+                    &format!("Invalid definition of action '{}': {}", action.name, err),
+                )
+            })?;
+        }
+        for topic in topics.iter() {
+            if !topic.groups.is_empty() && topic.access != AccessType::Group {
+                return Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    &format!(
+                        "Invalid definition of topic '{}': groups attached to {:?} access",
+                        topic.name, topic.access
+                    ),
+                ));
+            }
+        }
+
         // Register it on the network
-        let (thing_xorname, thing_typetag) = self.safe_thing_comm.store_thing_entity()?;
+        let (thing_xorname, thing_typetag) = self.safe_thing_comm.store_thing_entity(enc_info)?;
         debug!(
             "SAFEthing entity XoRname: {}:{}",
             thing_xorname, thing_typetag
@@ -308,33 +1044,59 @@ impl SAFEthing {
         let actions: String = serde_json::to_string(&actions).unwrap();
         self.safe_thing_comm.set_actions(actions.as_str())?;
 
+        // Publish the key it's identified with when accessing other SAFEthings and the key
+        // to seal data only it can read, and make sure only the owner can set the keys
+        // authorised and the groups to access it
+        self.safe_thing_comm.set_sign_key()?;
+        self.safe_thing_comm.set_pub_enc_key()?;
+        self.safe_thing_comm.init_access_entries()?;
+
         // Set SAFEthing status as Connected
         self.safe_thing_comm.set_status(ThingStatus::Connected)?;
 
         // We read the subscriptions from the network as this could have been a device
         // which was restarted and we need to catch up with any pending notifs.
         let subscriptions_str = self.safe_thing_comm.get_subscriptions()?;
-        self.subscriptions = serde_json::from_str(&subscriptions_str).unwrap();
-
-        // Create a channel to notify the subscriptions monitoring thread
-        // upon any new subscriptions created by the SAFEthing
-        let (tx, rx): (
-            Sender<(String, ThingSubscriptions)>,
-            Receiver<(String, ThingSubscriptions)>,
-        ) = mpsc::channel();
-        self.subsc_thread_channel_tx = Some(tx);
-
-        // Spawn thread in charge of checking subscriptions
-        // and notifying the SAFEthing by invoking the callback
-        // TODO: share self (SAFEthing) among threads instead of cloning
-        let notifs_cb: &'static SubsNotifCallback = self.notifs_cb;
-        spawn_check_subsc_thread(self.clone(), notifs_cb, self.subscriptions.clone(), rx);
-
-        // Spawn thread in charge of checking for action requests
-        // and invoking the corresponding callback function
-        // TODO: share self (SAFEthing) among threads instead of cloning
-        let action_req_cb: &'static ActionReqCallback = self.action_req_cb;
-        spawn_check_new_action_reqs(self.clone(), action_req_cb);
+        let subscriptions: RegisteredSubscriptions = serde_json::from_str(&subscriptions_str)
+            .map_err(|err| {
+                Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid subscriptions stored: {}", err).as_str(),
+                )
+            })?;
+
+        // The encryption key changes on every session, thus the SAFEthings which seal
+        // the events of their topics for this one need to know the new one
+        let enc_key = self.safe_thing_comm.pub_enc_key()?;
+        for (thing_id, topic) in sealed_topics(&subscriptions).iter() {
+            if let Err(err) = self.send_topic_subscriber(thing_id, topic, &enc_key) {
+                warn!(
+                    "Failed to send the encryption key to thing {} for topic '{}': {}",
+                    thing_id, topic, err
+                );
+            }
+        }
+        *self.subscriptions.lock().unwrap() = subscriptions;
+
+        // The background threads are only spawned if they are not
+        // running already from a previous registration
+        let mut workers = self.workers.lock().unwrap();
+        if workers.is_empty() {
+            let mut stop_signal = self.stop_signal.lock().unwrap();
+            if stop_signal.is_stopped() {
+                *stop_signal = Arc::new(StopSignal::default());
+            }
+            drop(stop_signal);
+
+            // Spawn thread in charge of checking subscriptions
+            // and notifying the SAFEthing by invoking the callback.
+            // The clones share the connection to the network with this instance.
+            workers.push(spawn_check_subsc_thread(self.worker_clone()));
+
+            // Spawn thread in charge of checking for action requests
+            // and invoking the corresponding callback function
+            workers.push(spawn_check_new_action_reqs(self.worker_clone()));
+        }
 
         info!("SAFEthing Connected with ID: {}", self.thing_id);
         Ok(())
@@ -342,12 +1104,19 @@ impl SAFEthing {
 
     /// Get status of this SAFEthing
     pub fn status(&self) -> ResultReturn<Status> {
-        match self.safe_thing_comm.get_status() {
-            Ok(ThingStatus::Unknown) => Ok(Status::Unknown),
-            Ok(ThingStatus::Connected) => Ok(Status::Connected),
-            Ok(ThingStatus::Published) => Ok(Status::Published),
-            Ok(ThingStatus::Disabled) => Ok(Status::Disabled),
-            Err(err) => return Err(err),
+        let status = self.safe_thing_comm.get_status()?;
+        Ok(to_status(status))
+    }
+
+    /// Get status of a SAFEthing, it's NonConnected if it was never registered
+    /// Search on the network by thing_id
+    pub fn get_thing_status(&self, thing_id: &str) -> ResultReturn<Status> {
+        match self.safe_thing_comm.get_thing_status(thing_id) {
+            Ok(status) => Ok(to_status(status)),
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => Ok(Status::NonConnected),
+                _ => Err(err),
+            },
         }
     }
 
@@ -355,24 +1124,23 @@ impl SAFEthing {
     /// Search on the network by thing_id
     pub fn get_thing_attrs(&self, thing_id: &str) -> ResultReturn<Vec<ThingAttr>> {
         let attrs_str = self.safe_thing_comm.get_thing_attrs(thing_id)?;
-        let attrs: Vec<ThingAttr> = serde_json::from_str(&attrs_str).unwrap();
-        Ok(attrs)
+        serde_json::from_str(&attrs_str).map_err(|err| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid attributes of thing {}: {}", thing_id, err).as_str(),
+            )
+        })
     }
 
     /// Set a new value for an attribute, or add it if it didn't exist
     pub fn set_attr_value(&self, attr: &str, value: &str) -> ResultReturn<()> {
-        let attrs_str = self.safe_thing_comm.get_thing_attrs(&self.thing_id)?;
-        let mut attrs: Vec<ThingAttr> = serde_json::from_str(&attrs_str).unwrap();
+        let mut attrs = self.get_thing_attrs(&self.thing_id)?;
         match attrs.iter().position(|ref x| x.attr == attr) {
             Some(i) => {
                 let item = &mut attrs[i];
                 item.value = value.to_string();
             }
-            None => attrs.push(ThingAttr {
-                attr: attr.to_string(),
-                value: value.to_string(),
-                is_dynamic: true,
-            }),
+            None => attrs.push(ThingAttr::new(attr, value, true)),
         };
 
         // Update attributes on the network
@@ -385,16 +1153,24 @@ impl SAFEthing {
     /// Search on the network by thing_id
     pub fn get_thing_topics(&self, thing_id: &str) -> ResultReturn<Vec<Topic>> {
         let topics_str = self.safe_thing_comm.get_thing_topics(thing_id)?;
-        let topics: Vec<Topic> = serde_json::from_str(&topics_str).unwrap();
-        Ok(topics)
+        serde_json::from_str(&topics_str).map_err(|err| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid topics of thing {}: {}", thing_id, err).as_str(),
+            )
+        })
     }
 
     /// Get list of actions supported by a SAFEthing
     /// Search on the network by thing_id
     pub fn get_thing_actions(&self, thing_id: &str) -> ResultReturn<Vec<ActionDef>> {
         let actions_str = self.safe_thing_comm.get_thing_actions(thing_id)?;
-        let actions: Vec<ActionDef> = serde_json::from_str(&actions_str).unwrap();
-        Ok(actions)
+        serde_json::from_str(&actions_str).map_err(|err| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid actions of thing {}: {}", thing_id, err).as_str(),
+            )
+        })
     }
 
     /// Publish the thing making it available and operative in the network, allowing other SAFEthings
@@ -405,443 +1181,3293 @@ impl SAFEthing {
         Ok(())
     }
 
-    /// Subscribe to topics published by a SAFEthing (all data is stored in the network to support device resets/reboots)
-    pub fn subscribe_to_topic(
-        &mut self,
-        thing_id: &str,
-        topic: &str,
-        filter_op: FilterOperator,
-        filter_value: &str,
-    ) -> ResultReturn<()> {
-        // TODO: check if thing is 'Published' before subscribing,
-        // and also check if it supports the topic
+    /// Disable the SAFEthing taking it offline, it rejects any new action request and it
+    /// doesn't notify events, but its information is still visible on the network
+    pub fn disable(&self) -> ResultReturn<()> {
+        self.safe_thing_comm.set_disabled()?;
+        info!("SAFEthing disabled with ID: {}", self.thing_id);
+        Ok(())
+    }
 
-        let topic_subs = TopicSubscription {
-            topic: topic.to_string(),
-            filter_op: filter_op.clone(),
-            filter_value: filter_value.to_string(),
+    /// Enable a disabled SAFEthing, restoring the status it had before it was disabled,
+    /// i.e. it's operative in the network again only if it was published
+    pub fn enable(&self) -> ResultReturn<()> {
+        if !self.is_disabled() {
+            return Ok(());
+        }
+        let status = match self.safe_thing_comm.get_enabled_status()? {
+            ThingStatus::Connected => ThingStatus::Connected,
+            _ => ThingStatus::Published,
         };
+        self.safe_thing_comm.set_status(status)?;
+        info!("SAFEthing enabled with ID: {}", self.thing_id);
+        Ok(())
+    }
 
-        self.register_new_subscription(thing_id, Subscription::Topic((topic_subs, gen_timestamp())))
+    /// Unregister the SAFEthing stopping its background threads, and clearing its
+    /// attributes, topics and actions from the network. It's left Disabled until
+    /// it's registered again.
+    pub fn unregister(&self) -> ResultReturn<()> {
+        self.stop_workers();
+        self.safe_thing_comm.set_disabled()?;
+        self.safe_thing_comm.set_attributes("[]")?;
+        self.safe_thing_comm.set_topics("[]")?;
+        self.safe_thing_comm.set_actions("[]")?;
+        info!("SAFEthing unregistered with ID: {}", self.thing_id);
+        Ok(())
     }
 
-    /// Subscribe to a dynamic attribute published by a SAFEthing in order to receive notifications
-    /// upon changes detected on them and based on the filters provided
-    pub fn subscribe_to_attr(
-        &mut self,
-        thing_id: &str,
-        attr_name: &str,
-        filter_op: FilterOperator,
-        filter_value: &str,
-    ) -> ResultReturn<()> {
-        // TODO: check if thing is 'Published' before subscribing,
-        // and also check if the attribute is_dynamic
+    /// Get the public signing key this SAFEthing is identified with,
+    /// other SAFEthings need it to authorise it to access their topics and actions
+    pub fn sign_key(&self) -> &str {
+        self.safe_thing_comm.sign_key()
+    }
 
-        let attr_subs = AttrSubscription {
-            attr_name: attr_name.to_string(),
-            filter_op: filter_op.clone(),
-            filter_value: filter_value.to_string(),
-        };
-
-        self.register_new_subscription(thing_id, Subscription::Attr((attr_subs, String::from(""))))
+    /// Authorise the owner of a public signing key to access the topics and actions
+    /// of this SAFEthing with Owner or Group access type
+    pub fn authorise(&self, sign_key: &str, access: AccessType) -> ResultReturn<()> {
+        match access {
+            AccessType::Owner | AccessType::Group => (),
+            _ => {
+                return Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "Only Owner or Group access can be authorised, not {:?}",
+                        access
+                    )
+                    .as_str(),
+                ))
+            }
+        }
+        let mut authorised = self.authorised_keys()?;
+        let _ = authorised.insert(sign_key.to_string(), access);
+        self.store_authorised(&authorised)
     }
 
-    // private helper
-    fn register_new_subscription(
-        &mut self,
-        thing_id: &str,
-        subscription: Subscription,
-    ) -> ResultReturn<()> {
-        // TODO: check if thing is 'Published' before subscribing,
-        // and also check if it supports the topic
-
-        // We keep track of the suscriptions list in memory first
-        let mut thing_subs = match self.subscriptions.get(thing_id) {
-            Some(subs) => subs.clone(),
-            None => vec![],
-        };
-        thing_subs.push(subscription);
-        let thing_subs_clone = thing_subs.clone();
-        self.subscriptions.insert(thing_id.to_string(), thing_subs);
-
-        // Also notify the thread which is monitoring topics so it starts checking this new one
-        if let Some(tx) = &self.subsc_thread_channel_tx {
-            match tx.send((thing_id.to_string(), thing_subs_clone)) {
-                Ok(()) => trace!("New subscription notified to monitoring thread"),
-                Err(err) => error!(
-                    "Failed to notify new subscription to monitoring thread: {}",
-                    err
-                ),
-            };
+    /// Revoke the access previously authorised to a public signing key
+    pub fn revoke(&self, sign_key: &str) -> ResultReturn<()> {
+        let mut authorised = self.authorised_keys()?;
+        if authorised.remove(sign_key).is_none() {
+            return Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Key {} was not authorised", sign_key).as_str(),
+            ));
         }
+        self.store_authorised(&authorised)
+    }
 
-        // But also update subscriptions list on the network
-        // TODO: we are not making it persistent just yet
-        /*
-        let subscriptions_str: String = serde_json::to_string(&self.subscriptions).unwrap();
-        self.safe_thing_comm
-            .set_subscriptions(subscriptions_str.as_str())?;
-        */
+    /// Get the public signing keys authorised to access this SAFEthing
+    pub fn authorised_keys(&self) -> ResultReturn<AuthorisedKeys> {
+        self.read_authorised(&self.thing_id)
+    }
 
-        Ok(())
+    fn store_authorised(&self, authorised: &AuthorisedKeys) -> ResultReturn<()> {
+        let authorised_str: String = serde_json::to_string(authorised).unwrap();
+        self.safe_thing_comm.set_authorised(&authorised_str)
     }
 
-    /// Notify of an event associated to an speficic topic.
-    /// Eventually this can support multiple topics.
-    pub fn notify(&self, topic: &str, data: &str) -> ResultReturn<()> {
-        info!("Notifying event for topic: {}, data: {}", topic, data);
-        let events: String = self.safe_thing_comm.get_topic_events(topic)?;
-        let mut events_vec: Vec<(Timestamp, String)> = match serde_json::from_str(&events) {
-            Ok(vec) => vec,
-            Err(_) => vec![],
-        };
-        let timestamp = gen_timestamp();
-        events_vec.push((timestamp, data.to_string()));
-        let events_str: String = serde_json::to_string(&events_vec).unwrap();
-        self.safe_thing_comm
-            .set_topic_events(topic, events_str.as_str())?;
-        Ok(())
+    /// Set the one-time code printed on the device, its owner provides it to claim the SAFEthing.
+    /// It can be claimed only once, and the code is not stored on the network.
+    /// The code is discarded after 5 claim requests are rejected, a new one needs to be set then.
+    pub fn set_claim_code(&self, code: &str) {
+        *self.claim_code.lock().unwrap() = Some(ClaimCode {
+            code: code.to_string(),
+            rejections: 0,
+        });
     }
 
-    /// Send an action request to a SAFEthing and monitor its state
-    /// Search on the network by thing_id
-    pub fn action_request(
-        &self,
-        thing_id: &str,
-        action: &str,
-        args: &[&str],
-        cb: &'static (Fn(&str) -> bool + std::marker::Send + std::marker::Sync),
-    ) -> ResultReturn<ActionReqId> {
-        let mut args_vec = Vec::new();
-        args_vec.extend(args.iter().map(|&arg| arg.to_string()));
-        let action_req = ActionReq {
+    /// Claim the ownership of a SAFEthing with the one-time code printed on it, the key
+    /// this SAFEthing is identified with is then authorised with Owner access.
+    /// It blocks until the claim is accepted, or it fails with `AccessDenied` if it's rejected.
+    pub fn claim(&self, thing_id: &str, code: &str) -> ResultReturn<()> {
+        // The code is sealed along with the requester's key, thus only the SAFEthing
+        // claimed can read it, and it cannot be used with another key
+        let pub_enc_key = self.safe_thing_comm.get_thing_pub_enc_key(thing_id)?;
+        let proof = self
+            .safe_thing_comm
+            .encrypt_sealed(&pub_enc_key, &claim_proof_data(code, self.sign_key()))?;
+        let request_id = self.safe_thing_comm.gen_request_id();
+        let mut claim_req = ClaimReq {
             thing_id: self.thing_id.clone(),
-            action: action.to_string(),
-            args: args_vec,
+            requester_key: self.sign_key().to_string(),
+            proof,
+            signature: String::new(),
             state: ACTION_REQUEST_INIT_STATE.to_string(),
+            error: None,
         };
-        let action_req_str: String = serde_json::to_string(&action_req).unwrap();
-
-        let req_id = self
+        claim_req.signature = self
             .safe_thing_comm
-            .send_action_request(thing_id, action_req_str.as_str())?;
-
-        // TODO: share self (SAFEthing) among threads instead of cloning
-        let safething_comm = self.safe_thing_comm.clone();
-        spawn_action_req_monitoring_thread(thing_id.to_string(), req_id, safething_comm, cb);
+            .sign(&claim_req_signed_data(thing_id, request_id, &claim_req))?;
+        let claim_req_str = serde_json::to_string(&claim_req).unwrap();
+        self.safe_thing_comm
+            .send_claim_request(thing_id, request_id, &claim_req_str)?;
+        debug!("Claim request sent to thing {}: {}", thing_id, request_id);
 
-        Ok(req_id)
+        self.wait_peer_request(thing_id, "Claim request", || {
+            let claim_req_str = self
+                .safe_thing_comm
+                .get_thing_claim_request_state(thing_id, request_id)?;
+            match serde_json::from_str::<ClaimReq>(&claim_req_str) {
+                Ok(ref claim_req) if claim_req.state == ACTION_REQUEST_DONE_STATE => {
+                    info!("SAFEthing {} claimed by {}", thing_id, self.thing_id);
+                    Ok(Some(()))
+                }
+                Ok(claim_req) => rejected_outcome(claim_req.state, claim_req.error),
+                Err(_) => Ok(None),
+            }
+        })
     }
 
-    /// Update the state of an action reqeust
-    pub fn update_action_request_state(
+    // private helper to wait for a claim or a request for the encryption info sent to
+    // another SAFEthing to be processed, the outcome is read with the function given
+    // which returns it only once the request was processed
+    fn wait_peer_request<T, F>(
         &self,
-        request_id: ActionReqId,
-        new_state: &str,
-    ) -> ResultReturn<()> {
-        self.safe_thing_comm
-            .set_action_request_state(request_id, new_state)?;
-        Ok(())
+        thing_id: &str,
+        what: &str,
+        read_outcome: F,
+    ) -> ResultReturn<T>
+    where
+        F: Fn() -> ResultReturn<Option<T>>,
+    {
+        let timeout = Duration::from_millis(PEER_REQUEST_TIMEOUT);
+        let start = Instant::now();
+        loop {
+            if let Some(outcome) = read_outcome()? {
+                return Ok(outcome);
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(Error::new(
+                    ErrorCode::Timeout,
+                    &format!("{} to thing {} was not processed", what, thing_id),
+                ));
+            }
+            thread::sleep(std::cmp::min(
+                Duration::from_millis(PEER_REQUEST_CHECK_FREQ),
+                timeout - elapsed,
+            ));
+        }
     }
 
-    /// Only for testing, to simulate a network disconnection event
-    pub fn simulate_net_disconnect(&mut self) {
-        self.safe_thing_comm.sim_net_disconnect();
+    /// Get the public signing key of the owner of this SAFEthing, if it was claimed
+    pub fn owner(&self) -> ResultReturn<Option<String>> {
+        self.get_thing_owner(&self.thing_id)
     }
-}
 
-// Helper to generate timestamp (in nanos)
-fn gen_timestamp() -> Timestamp {
-    let now_timestamp = SystemTime::now();
-    let since_the_epoch = now_timestamp
-        .duration_since(UNIX_EPOCH)
-        .expect("Failed to get time since epoch");
+    /// Get the public signing key of the owner of a SAFEthing, if it was claimed
+    /// Search on the network by thing_id
+    pub fn get_thing_owner(&self, thing_id: &str) -> ResultReturn<Option<String>> {
+        let owner_key = self.safe_thing_comm.get_thing_owner(thing_id)?;
+        if owner_key.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(owner_key))
+        }
+    }
 
-    since_the_epoch.as_nanos()
-}
+    // private helper to check a claim request was signed by the SAFEthing it claims to be
+    // sent from, and that the code it sealed is the one set on this SAFEthing
+    fn verify_claim(
+        &self,
+        request_id: ActionReqId,
+        claim_req: &ClaimReq,
+        code: &str,
+    ) -> Result<(), String> {
+        let signed_data = claim_req_signed_data(&self.thing_id, request_id, claim_req);
+        match self.safe_thing_comm.verify(
+            &claim_req.requester_key,
+            &signed_data,
+            &claim_req.signature,
+        ) {
+            Ok(true) => (),
+            Ok(false) => return Err("Invalid signature of the claim request".to_string()),
+            Err(err) => return Err(format!("Failed to verify the claim request: {}", err)),
+        }
+        match self.safe_thing_comm.get_thing_sign_key(&claim_req.thing_id) {
+            Ok(ref thing_key) if *thing_key == claim_req.requester_key => (),
+            _ => {
+                return Err(format!(
+                    "Claim request not signed with the key published by {}",
+                    claim_req.thing_id
+                ))
+            }
+        }
+        match self.safe_thing_comm.decrypt_sealed(&claim_req.proof) {
+            Ok(ref proof) if *proof == claim_proof_data(code, &claim_req.requester_key) => Ok(()),
+            _ => Err("Invalid claim code".to_string()),
+        }
+    }
 
-// spawn a thread which takes care of monitoring topics which the SAFEthing subcribed to
-fn spawn_check_subsc_thread(
-    safe_thing: SAFEthing,
-    notifs_cb: &'static SubsNotifCallback,
-    subs: RegisteredSubscriptions,
-    subsc_thread_channel_rx: Receiver<(String, ThingSubscriptions)>,
-) {
-    thread::spawn(move || {
-        let mut subscriptions = subs;
-        loop {
-            trace!("Checking subscriptions...");
-            for (thing_id, thing_subscription) in subsc_thread_channel_rx.try_iter() {
-                debug!(
-                    "New subscription to monitor: {} - {:?}",
-                    thing_id, thing_subscription
-                );
-                subscriptions.insert(thing_id, thing_subscription);
+    // private helper to process the claim requests received, the first one with a valid
+    // proof of the claim code is accepted and the rest are rejected. The requests which
+    // cannot be processed are left pending to be retried.
+    fn check_claim_requests(&self) -> ResultReturn<()> {
+        let claim_reqs = self.safe_thing_comm.get_claim_requests()?;
+        for (request_id, claim_req_str) in claim_reqs.iter() {
+            let mut claim_req = match serde_json::from_str::<ClaimReq>(claim_req_str) {
+                Ok(claim_req) => claim_req,
+                Err(err) => {
+                    warn!("Invalid claim request ({}): {}", request_id, err);
+                    continue;
+                }
+            };
+            if claim_req.state != ACTION_REQUEST_INIT_STATE {
+                continue;
             }
 
-            for (thing_id, thing_subs) in subscriptions.iter_mut() {
-                for subscription in thing_subs.iter_mut() {
-                    match subscription {
-                        Subscription::Topic((topic_subs, last_report_timestamp)) => {
-                            check_topic_subs_and_notify(
-                                thing_id,
-                                safe_thing.clone(),
-                                notifs_cb,
-                                topic_subs,
-                                last_report_timestamp,
-                            );
-                        }
-                        Subscription::Attr((attr_subs, last_val_reported)) => {
-                            check_attrs_subs_and_notify(
-                                thing_id,
-                                safe_thing.clone(),
-                                notifs_cb,
-                                attr_subs,
-                                last_val_reported,
-                            );
+            let mut claim_code = self.claim_code.lock().unwrap();
+            let owner = match self.owner() {
+                Ok(owner) => owner,
+                Err(err) => {
+                    warn!("Failed to read the owner ({}): {}", request_id, err);
+                    continue;
+                }
+            };
+            let result = match (owner, claim_code.as_mut()) {
+                // its state wasn't stored after it was accepted
+                (Some(ref owner), _) if *owner == claim_req.requester_key => Ok(()),
+                (Some(_), _) => Err(format!("SAFEthing {} was already claimed", self.thing_id)),
+                (None, None) => Err(format!("SAFEthing {} cannot be claimed", self.thing_id)),
+                (None, Some(claim_code)) => {
+                    let result = self.verify_claim(*request_id, &claim_req, &claim_code.code);
+                    if result.is_ok() {
+                        if let Err(err) = self.grant_ownership(&claim_req.requester_key) {
+                            warn!("Failed to grant the ownership ({}): {}", request_id, err);
+                            continue;
                         }
+                    } else {
+                        claim_code.rejections += 1;
                     }
+                    result
+                }
+            };
+            match *claim_code {
+                Some(ref code) if code.rejections >= MAX_CLAIM_REJECTIONS => {
+                    warn!("Too many claim requests rejected, the claim code was discarded");
+                    *claim_code = None;
                 }
+                Some(_) if result.is_ok() => *claim_code = None,
+                _ => (),
+            }
+            match result {
+                Ok(()) => {
+                    info!("SAFEthing claimed by {}", claim_req.thing_id);
+                    claim_req.state = ACTION_REQUEST_DONE_STATE.to_string();
+                }
+                Err(err) => {
+                    debug!(
+                        "Claim request from {} rejected: {}",
+                        claim_req.thing_id, err
+                    );
+                    claim_req.state = ACTION_REQUEST_REJECTED_STATE.to_string();
+                    claim_req.error = Some(err);
+                }
+            }
+            let claim_req_str = serde_json::to_string(&claim_req).unwrap();
+            if let Err(err) = self
+                .safe_thing_comm
+                .set_claim_request_state(*request_id, &claim_req_str)
+            {
+                warn!(
+                    "Failed to store the state of the claim request ({}): {}",
+                    request_id, err
+                );
             }
-
-            trace!("CHECKED SUBSCRIPTIONS....WAIT FOR NEXT LOOP");
-            thread::sleep(Duration::from_millis(SUBSCRIPTIONS_CHECK_FREQ));
         }
-    });
-}
-
-fn check_topic_subs_and_notify(
-    thing_id: &String,
-    safe_thing: SAFEthing,
-    notifs_cb: &'static SubsNotifCallback,
-    topic_subs: &mut TopicSubscription,
-    last_report_timestamp: &mut Timestamp,
-) {
-    let TopicSubscription {
-        topic,
-        filter_op,
-        filter_value,
-    } = topic_subs;
-    trace!(
-        "CHECKING TOPIC EVENTS FROM (thingId -> topic): {} -> {}",
-        thing_id,
-        topic
-    );
-
-    let events = safe_thing
-        .safe_thing_comm
-        .get_thing_topic_events(thing_id, topic)
-        .unwrap();
-    let events_vec: Vec<(Timestamp, String)> = match serde_json::from_str(&events) {
-        Ok(vec) => vec,
-        Err(_) => vec![],
-    };
-    for (event_timestamp, event) in events_vec.iter() {
-        let do_eval = event_timestamp > last_report_timestamp;
-        if do_eval && filter_op.eval(&event, &filter_value) {
-            debug!(
-                "Event occurred for topic: {}, event: ({}, {})",
-                topic, event_timestamp, event
-            );
-            (notifs_cb)(
-                &safe_thing,
-                thing_id.as_str(),
-                topic.as_str(),
-                event.as_str(),
-                *event_timestamp,
-            );
+        Ok(())
+    }
 
-            // update last_report_timestamp in the subscriptions list to not
-            // keep sending the notification for same (and already notified) event
-            *last_report_timestamp = *event_timestamp;
-            // TODO: we may want to persist this updates on the network as well
+    // private helper to authorise the key with Owner access and record it as the owner,
+    // the access previously granted to the key is restored if the owner cannot be recorded
+    fn grant_ownership(&self, owner_key: &str) -> ResultReturn<()> {
+        let previous = self.authorised_keys()?.get(owner_key).cloned();
+        self.authorise(owner_key, AccessType::Owner)?;
+        if let Err(err) = self.safe_thing_comm.set_owner(owner_key) {
+            let undo = match previous {
+                Some(access) => self.authorise(owner_key, access),
+                None => self.revoke(owner_key),
+            };
+            if let Err(undo_err) = undo {
+                warn!("Failed to undo the Owner access granted: {}", undo_err);
+            }
+            return Err(err);
         }
+        Ok(())
     }
-}
 
-fn check_attrs_subs_and_notify(
-    thing_id: &String,
-    safe_thing: SAFEthing,
-    notifs_cb: &'static SubsNotifCallback,
-    attr_subs: &mut AttrSubscription,
-    last_val_reported: &mut String,
-) {
-    let AttrSubscription {
-        attr_name,
-        filter_op,
-        filter_value,
-    } = attr_subs;
+    // private helper to process the requests for the encryption info of this SAFEthing if
+    // it's private, it's sealed only for the requesters whose key is authorised or which
+    // are members of any of its groups
+    fn check_enc_info_requests(&self) -> ResultReturn<()> {
+        let enc_info = match self.get_thing_enc_info(&self.thing_id) {
+            Some(enc_info) => enc_info,
+            None => return Ok(()),
+        };
+        let enc_info_reqs = self.safe_thing_comm.get_enc_info_requests()?;
+        let mut pending = enc_info_reqs
+            .iter()
+            .filter_map(
+                |(request_id, req_str)| match serde_json::from_str::<EncInfoReq>(req_str) {
+                    Ok(ref req) if req.state != ACTION_REQUEST_INIT_STATE => None,
+                    Ok(req) => Some((*request_id, req)),
+                    Err(err) => {
+                        warn!("Invalid encryption info request ({}): {}", request_id, err);
+                        None
+                    }
+                },
+            )
+            .peekable();
+        if pending.peek().is_none() {
+            return Ok(());
+        }
 
-    trace!(
-        "CHECKING DYNAMIC ATTRIBUTES CHANGES FROM thingId: {} - {:?}",
-        thing_id,
-        attr_name
-    );
+        let authorised = self.authorised_keys()?;
+        let groups = self.groups()?;
+        for (request_id, mut enc_info_req) in pending {
+            let signed_data = enc_info_req_signed_data(&self.thing_id, request_id, &enc_info_req);
+            let result = match self.safe_thing_comm.verify(
+                &enc_info_req.requester_key,
+                &signed_data,
+                &enc_info_req.signature,
+            ) {
+                Ok(true) => {
+                    if authorised.contains_key(&enc_info_req.requester_key)
+                        || self.is_group_member(
+                            &groups,
+                            &[],
+                            &enc_info_req.thing_id,
+                            &enc_info_req.requester_key,
+                        )
+                    {
+                        self.safe_thing_comm
+                            .encrypt_sealed(&enc_info_req.enc_key, &enc_info)
+                            .map_err(|err| format!("Failed to seal the encryption info: {}", err))
+                    } else {
+                        Err(format!(
+                            "Access to the encryption info is not authorised to {}",
+                            enc_info_req.thing_id
+                        ))
+                    }
+                }
+                Ok(false) => Err("Invalid signature of the encryption info request".to_string()),
+                Err(err) => Err(format!(
+                    "Failed to verify the encryption info request: {}",
+                    err
+                )),
+            };
+            match result {
+                Ok(sealed) => {
+                    info!("Encryption info sent to {}", enc_info_req.thing_id);
+                    enc_info_req.state = ACTION_REQUEST_DONE_STATE.to_string();
+                    enc_info_req.enc_info = Some(sealed);
+                }
+                Err(err) => {
+                    debug!(
+                        "Encryption info request from {} rejected: {}",
+                        enc_info_req.thing_id, err
+                    );
+                    enc_info_req.state = ACTION_REQUEST_REJECTED_STATE.to_string();
+                    enc_info_req.error = Some(err);
+                }
+            }
+            let enc_info_req_str = serde_json::to_string(&enc_info_req).unwrap();
+            if let Err(err) = self
+                .safe_thing_comm
+                .set_enc_info_request_state(request_id, &enc_info_req_str)
+            {
+                warn!(
+                    "Failed to store the encryption info request ({}): {}",
+                    request_id, err
+                );
+            }
+        }
+        Ok(())
+    }
 
-    let attrs = safe_thing
-        .safe_thing_comm
-        .get_thing_attrs(thing_id)
-        .unwrap();
-    let attrs_vec: Vec<ThingAttr> = match serde_json::from_str(&attrs) {
-        Ok(vec) => vec,
-        Err(_) => vec![],
-    };
-    for ThingAttr {
-        attr,
-        value,
-        is_dynamic,
-    } in attrs_vec
-    {
-        let do_eval = is_dynamic
-            && *attr_name == attr
-            && (last_val_reported.len() == 0 || !filter_op.eval(&value, last_val_reported));
-        if do_eval && filter_op.eval(&value, &filter_value) {
-            debug!(
-                "Dynamic attribute change occurred: {}, value: {}",
-                attr, value
-            );
-            (notifs_cb)(
-                &safe_thing,
-                thing_id.as_str(),
-                attr.as_str(),
-                value.as_str(),
-                0,
-            );
+    /// Create a named group, its members are granted Group access to the topics
+    /// and actions it's attached to
+    pub fn create_group(&self, group: &str) -> ResultReturn<()> {
+        let mut groups = self.groups()?;
+        if groups.contains_key(group) {
+            return Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Group '{}' already exists", group).as_str(),
+            ));
+        }
+        let _ = groups.insert(group.to_string(), BTreeSet::new());
+        self.store_groups(&groups)
+    }
 
-            // update last_val_reported in the subscriptions list to not
-            // keep sending the notification for same (and already notified) event
-            *last_val_reported = value;
-            // TODO: we may want to persist this updates on the network as well
+    /// Delete a group, its members are no longer granted access through it
+    pub fn delete_group(&self, group: &str) -> ResultReturn<()> {
+        let mut groups = self.groups()?;
+        if groups.remove(group).is_none() {
+            return Err(no_such_group(group));
         }
+        self.store_groups(&groups)
     }
-}
 
-// spawn a thread which takes care of monitoring for new action requests received
-fn spawn_check_new_action_reqs(safe_thing: SAFEthing, action_req_cb: &'static ActionReqCallback) {
-    thread::spawn(move || {
-        loop {
-            trace!("Checking for new action requests...");
-            let actions_reqs_vec = safe_thing.safe_thing_comm.get_actions_requests().unwrap();
-            trace!("Actions requested to process: {:?}", actions_reqs_vec);
-            for (request_id, action_req_str) in actions_reqs_vec.iter() {
-                match serde_json::from_str(&action_req_str) {
-                    Ok(ActionReq {
-                        thing_id,
-                        action,
-                        args,
-                        state,
-                    }) => {
-                        if state == ACTION_REQUEST_INIT_STATE {
-                            debug!("Action requested: {:?}", action);
-                            let args2 = args.clone();
-                            let action_args: Vec<&str> = args.iter().map(|i| i.as_str()).collect();
-                            (action_req_cb)(
-                                &safe_thing,
-                                *request_id,
-                                thing_id.as_str(),
-                                action.as_str(),
-                                &action_args,
-                            );
-                            debug!(
-                                "Action request handled by SAFEthing. Updating new state to {}",
-                                ACTION_REQUEST_DONE_STATE
-                            );
-                            let action_req = ActionReq {
-                                thing_id,
-                                action,
-                                args: args2,
-                                state: ACTION_REQUEST_DONE_STATE.to_string(),
-                            };
-                            let action_req_str: String =
-                                serde_json::to_string(&action_req).unwrap();
-                            safe_thing
-                                .safe_thing_comm
-                                .set_action_request_state(*request_id, action_req_str.as_str())
-                                .expect("Failed to update action request state");
-                        }
-                    }
-                    Err(err) => error!("Action request is invalid, thus ignoring it: {}", err),
-                };
+    /// Add a member to a group, either the ID of a SAFEthing or a public signing key
+    pub fn add_group_member(&self, group: &str, member: &str) -> ResultReturn<()> {
+        self.update_group(group, |members| {
+            let _ = members.insert(member.to_string());
+            Ok(())
+        })
+    }
 
-                // TODO: keep track of the actions requests that were already notified
+    /// Remove a member from a group
+    pub fn remove_group_member(&self, group: &str, member: &str) -> ResultReturn<()> {
+        self.update_group(group, |members| {
+            if members.remove(member) {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!("'{}' is not a member of group '{}'", member, group).as_str(),
+                ))
             }
-            trace!("CHECKED ACTIONS....WAIT FOR NEXT LOOP");
-            thread::sleep(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ));
-        }
-    });
-}
+        })
+    }
 
-// spawn a thread to check for a change in the state of an action request sent
-fn spawn_action_req_monitoring_thread(
-    thing_id: String,
+    /// Get the groups of this SAFEthing along with their members
+    pub fn groups(&self) -> ResultReturn<ThingGroups> {
+        self.get_thing_groups(&self.thing_id)
+    }
+
+    /// Get the groups of a SAFEthing along with their members
+    /// Search on the network by thing_id
+    pub fn get_thing_groups(&self, thing_id: &str) -> ResultReturn<ThingGroups> {
+        let groups_str = self.safe_thing_comm.get_thing_groups(thing_id)?;
+        serde_json::from_str(&groups_str).map_err(|err| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid groups of thing {}: {}", thing_id, err).as_str(),
+            )
+        })
+    }
+
+    // private helper
+    fn update_group<F>(&self, group: &str, f: F) -> ResultReturn<()>
+    where
+        F: FnOnce(&mut BTreeSet<String>) -> ResultReturn<()>,
+    {
+        let mut groups = self.groups()?;
+        let members = groups.get_mut(group).ok_or_else(|| no_such_group(group))?;
+        f(members)?;
+        self.store_groups(&groups)
+    }
+
+    fn store_groups(&self, groups: &ThingGroups) -> ResultReturn<()> {
+        let groups_str: String = serde_json::to_string(groups).unwrap();
+        self.safe_thing_comm.set_groups(&groups_str)
+    }
+
+    /// Subscribe to topics published by a SAFEthing (all data is stored in the network to support device resets/reboots)
+    /// The subscription fails with an `AccessDenied` error if this SAFEthing isn't granted the
+    /// access type of the topic. The events of a topic which is not for All are sealed for each
+    /// subscriber as long as it's granted access, thus no one else can read them.
+    pub fn subscribe_to_topic(
+        &self,
+        thing_id: &str,
+        topic: &str,
+        filter_op: FilterOperator,
+        filter_value: &str,
+    ) -> ResultReturn<()> {
+        // Check the thing is 'Published' and it supports the topic
+        self.check_thing_published(thing_id)?;
+        let topics = self.get_thing_topics(thing_id)?;
+        let topic_def = match topics.iter().find(|t| t.name == topic) {
+            Some(topic_def) => topic_def,
+            None => {
+                return Err(Error::new(
+                    ErrorCode::TopicNotFound,
+                    format!("Topic '{}' not supported by thing: {}", topic, thing_id).as_str(),
+                ))
+            }
+        };
+        let what = format!("topic '{}'", topic);
+        self.check_access(thing_id, &topic_def.access, &topic_def.groups, &what)?;
+
+        let sealed = topic_def.access != AccessType::All;
+        if sealed {
+            let enc_key = self.safe_thing_comm.pub_enc_key()?;
+            self.send_topic_subscriber(thing_id, topic, &enc_key)?;
+        }
+
+        let topic_subs = TopicSubscription {
+            topic: topic.to_string(),
+            filter_op: filter_op.clone(),
+            filter_value: filter_value.to_string(),
+            sealed,
+        };
+
+        self.register_new_subscription(thing_id, Subscription::Topic((topic_subs, gen_timestamp())))
+    }
+
+    /// Subscribe to a dynamic attribute published by a SAFEthing in order to receive notifications
+    /// upon changes detected on them and based on the filters provided, the access type of the
+    /// attribute is checked as for the topics
+    pub fn subscribe_to_attr(
+        &self,
+        thing_id: &str,
+        attr_name: &str,
+        filter_op: FilterOperator,
+        filter_value: &str,
+    ) -> ResultReturn<()> {
+        // Check the thing is 'Published' and the attribute is_dynamic
+        self.check_thing_published(thing_id)?;
+        let attrs = self.get_thing_attrs(thing_id)?;
+        let attr = match attrs.iter().find(|a| a.attr == attr_name) {
+            Some(attr) if attr.is_dynamic => attr,
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorCode::AttrNotDynamic,
+                    format!(
+                        "Attribute '{}' of thing {} is not dynamic",
+                        attr_name, thing_id
+                    )
+                    .as_str(),
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorCode::AttrNotFound,
+                    format!("Attribute '{}' not found in thing: {}", attr_name, thing_id).as_str(),
+                ))
+            }
+        };
+        let what = format!("attribute '{}'", attr_name);
+        self.check_access(thing_id, &attr.access, &[], &what)?;
+
+        let attr_subs = AttrSubscription {
+            attr_name: attr_name.to_string(),
+            filter_op: filter_op.clone(),
+            filter_value: filter_value.to_string(),
+        };
+
+        self.register_new_subscription(thing_id, Subscription::Attr((attr_subs, String::from(""))))
+    }
+
+    // private helper, if the status cannot be read it's assumed not to be disabled
+    fn is_disabled(&self) -> bool {
+        self.safe_thing_comm.get_status().ok() == Some(ThingStatus::Disabled)
+    }
+
+    // private helper to check if this SAFEthing is granted the access type by another one,
+    // either by the key authorised or by being a member of the groups attached.
+    // The other SAFEthing checks it again before sealing each event of its topics for this one,
+    // the attributes are part of its entity though, thus they can be read by anyone unless
+    // it was registered as private.
+    fn check_access(
+        &self,
+        thing_id: &str,
+        access: &AccessType,
+        attached_groups: &[String],
+        what: &str,
+    ) -> ResultReturn<()> {
+        if let AccessType::All = access {
+            return Ok(());
+        }
+        let thing_key = self.safe_thing_comm.get_thing_sign_key(thing_id)?;
+        let authorised = self.read_authorised(thing_id)?;
+        let is_group_member = || match self.get_thing_groups(thing_id) {
+            Ok(groups) => {
+                self.is_group_member(&groups, attached_groups, &self.thing_id, self.sign_key())
+            }
+            Err(_) => false,
+        };
+        if is_access_granted(
+            access,
+            self.sign_key(),
+            &thing_key,
+            &authorised,
+            is_group_member,
+        ) {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorCode::AccessDenied,
+                format!("Access to {} of thing {} is not authorised", what, thing_id).as_str(),
+            ))
+        }
+    }
+
+    // private helper to check if a requester is a member of the groups attached to a topic or
+    // action, or of any of the groups if none is attached. A member which is the ID of a
+    // SAFEthing is only matched if the requester uses the key published by that SAFEthing.
+    fn is_group_member(
+        &self,
+        groups: &ThingGroups,
+        attached_groups: &[String],
+        requester_id: &str,
+        requester_key: &str,
+    ) -> bool {
+        groups
+            .iter()
+            .filter(|(group, _)| attached_groups.is_empty() || attached_groups.contains(group))
+            .any(|(_, members)| {
+                members.contains(requester_key)
+                    || (members.contains(requester_id)
+                        && self
+                            .safe_thing_comm
+                            .get_thing_sign_key(requester_id)
+                            .map(|key| key == requester_key)
+                            .unwrap_or(false))
+            })
+    }
+
+    // private helper to check an action request was signed by the SAFEthing it claims
+    // to be sent from, i.e. with the key that SAFEthing published
+    fn verify_requester(
+        &self,
+        request_id: ActionReqId,
+        action_req: &ActionReq,
+    ) -> Result<(), String> {
+        let signed_data = action_req_signed_data(&self.thing_id, request_id, action_req);
+        match self.safe_thing_comm.verify(
+            &action_req.requester_key,
+            &signed_data,
+            &action_req.signature,
+        ) {
+            Ok(true) => (),
+            Ok(false) => return Err("Invalid signature of the action request".to_string()),
+            Err(err) => return Err(format!("Failed to verify the action request: {}", err)),
+        }
+        match self
+            .safe_thing_comm
+            .get_thing_sign_key(&action_req.thing_id)
+        {
+            Ok(ref thing_key) if *thing_key == action_req.requester_key => Ok(()),
+            _ => Err(format!(
+                "Action request not signed with the key published by {}",
+                action_req.thing_id
+            )),
+        }
+    }
+
+    // private helper to check the requester is granted the access type of the action,
+    // the requests for actions not supported are rejected when validating them
+    fn check_action_access(
+        &self,
+        actions: &[ActionDef],
+        action_req: &ActionReq,
+        authorised: &AuthorisedKeys,
+        groups: &ThingGroups,
+    ) -> Result<(), String> {
+        let action_def = match actions.iter().find(|a| a.name == action_req.action) {
+            Some(action_def) => action_def,
+            None => return Ok(()),
+        };
+        let is_group_member = || {
+            self.is_group_member(
+                groups,
+                &action_def.groups,
+                &action_req.thing_id,
+                &action_req.requester_key,
+            )
+        };
+        if is_access_granted(
+            &action_def.access,
+            &action_req.requester_key,
+            self.sign_key(),
+            authorised,
+            is_group_member,
+        ) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Access to action '{}' is not authorised to {}",
+                action_req.action, action_req.thing_id
+            ))
+        }
+    }
+
+    // private helper
+    fn read_authorised(&self, thing_id: &str) -> ResultReturn<AuthorisedKeys> {
+        let authorised_str = self.safe_thing_comm.get_thing_authorised(thing_id)?;
+        serde_json::from_str(&authorised_str).map_err(|err| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid list of keys authorised by thing {}: {}",
+                    thing_id, err
+                )
+                .as_str(),
+            )
+        })
+    }
+
+    // private helper
+    fn check_thing_published(&self, thing_id: &str) -> ResultReturn<()> {
+        match self.safe_thing_comm.get_thing_status(thing_id)? {
+            ThingStatus::Published => Ok(()),
+            status => Err(Error::new(
+                ErrorCode::ThingNotPublished,
+                format!("Thing {} is not published, status: {:?}", thing_id, status).as_str(),
+            )),
+        }
+    }
+
+    // private helper
+    fn register_new_subscription(
+        &self,
+        thing_id: &str,
+        subscription: Subscription,
+    ) -> ResultReturn<()> {
+        self.update_subscriptions(|subscriptions| {
+            subscriptions
+                .entry(thing_id.to_string())
+                .or_default()
+                .push(subscription);
+            Ok(())
+        })?;
+        trace!("New subscription registered for thing: {}", thing_id);
+        Ok(())
+    }
+
+    /// Remove the subscriptions made to a topic or dynamic attribute of a SAFEthing
+    pub fn unsubscribe(&self, thing_id: &str, topic_or_attr: &str) -> ResultReturn<()> {
+        let sealed = sealed_topics(&self.subscriptions.lock().unwrap());
+        self.update_subscriptions(|subscriptions| {
+            let thing_subs = subscriptions.get_mut(thing_id).ok_or_else(|| {
+                Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!("There are no subscriptions to thing: {}", thing_id).as_str(),
+                )
+            })?;
+
+            let subs_count = thing_subs.len();
+            thing_subs.retain(|subscription| match subscription {
+                Subscription::Topic((topic_subs, _)) => topic_subs.topic != topic_or_attr,
+                Subscription::Attr((attr_subs, _)) => attr_subs.attr_name != topic_or_attr,
+            });
+            if thing_subs.len() == subs_count {
+                return Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "There is no subscription to '{}' of thing: {}",
+                        topic_or_attr, thing_id
+                    )
+                    .as_str(),
+                ));
+            }
+
+            if thing_subs.is_empty() {
+                let _ = subscriptions.remove(thing_id);
+            }
+            Ok(())
+        })?;
+        self.leave_sealed_topics(&sealed);
+        debug!(
+            "Unsubscribed from '{}' of thing: {}",
+            topic_or_attr, thing_id
+        );
+        Ok(())
+    }
+
+    /// Remove all the subscriptions made to topics and dynamic attributes of a SAFEthing
+    pub fn unsubscribe_all(&self, thing_id: &str) -> ResultReturn<()> {
+        let sealed = sealed_topics(&self.subscriptions.lock().unwrap());
+        self.update_subscriptions(|subscriptions| match subscriptions.remove(thing_id) {
+            Some(_) => Ok(()),
+            None => Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("There are no subscriptions to thing: {}", thing_id).as_str(),
+            )),
+        })?;
+        self.leave_sealed_topics(&sealed);
+        debug!(
+            "Unsubscribed from all topics and attributes of thing: {}",
+            thing_id
+        );
+        Ok(())
+    }
+
+    /// Get the list of subscriptions currently made by this SAFEthing
+    pub fn subscriptions(&self) -> Vec<SubscriptionInfo> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut list = vec![];
+        for (thing_id, thing_subs) in subscriptions.iter() {
+            list.extend(thing_subs.iter().map(|subscription| match subscription {
+                Subscription::Topic((topic_subs, _)) => SubscriptionInfo {
+                    thing_id: thing_id.clone(),
+                    name: topic_subs.topic.clone(),
+                    is_attr: false,
+                    filter_op: topic_subs.filter_op.clone(),
+                    filter_value: topic_subs.filter_value.clone(),
+                },
+                Subscription::Attr((attr_subs, _)) => SubscriptionInfo {
+                    thing_id: thing_id.clone(),
+                    name: attr_subs.attr_name.clone(),
+                    is_attr: true,
+                    filter_op: attr_subs.filter_op.clone(),
+                    filter_value: attr_subs.filter_value.clone(),
+                },
+            }));
+        }
+        list
+    }
+
+    // private helper
+    fn update_subscriptions<F>(&self, f: F) -> ResultReturn<()>
+    where
+        F: FnOnce(&mut RegisteredSubscriptions) -> ResultReturn<()>,
+    {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let mut updated_subs = subscriptions.clone();
+        f(&mut updated_subs)?;
+
+        // We update the subscriptions list on the network first, and then the list
+        // kept in memory, the thread which is monitoring them will take the
+        // changes in its next loop
+        self.store_subscriptions(&updated_subs)?;
+        *subscriptions = updated_subs;
+        Ok(())
+    }
+
+    // private helper to let the SAFEthings know they no longer need to seal the events
+    // of the topics which were unsubscribed from, out of the ones given
+    fn leave_sealed_topics(&self, sealed: &BTreeSet<(String, String)>) {
+        let current = sealed_topics(&self.subscriptions.lock().unwrap());
+        for (thing_id, topic) in sealed.difference(&current) {
+            if let Err(err) = self.send_topic_subscriber(thing_id, topic, "") {
+                warn!(
+                    "Failed to unsubscribe from the sealed events of topic '{}' of thing {}: {}",
+                    topic, thing_id, err
+                );
+            }
+        }
+    }
+
+    // private helper to let a SAFEthing know the key to seal the events of a topic for this
+    // SAFEthing with, or that it no longer needs to if the key is empty
+    fn send_topic_subscriber(
+        &self,
+        thing_id: &str,
+        topic: &str,
+        enc_key: &str,
+    ) -> ResultReturn<()> {
+        let request_id = self.safe_thing_comm.gen_request_id();
+        let mut subscriber = TopicSubscriber {
+            thing_id: self.thing_id.clone(),
+            requester_key: self.sign_key().to_string(),
+            topic: topic.to_string(),
+            enc_key: enc_key.to_string(),
+            signature: String::new(),
+        };
+        subscriber.signature = self.safe_thing_comm.sign(&topic_subscriber_signed_data(
+            thing_id,
+            request_id,
+            &subscriber,
+        ))?;
+        let subscriber_str = serde_json::to_string(&subscriber).unwrap();
+        self.safe_thing_comm
+            .send_topic_subscriber(thing_id, request_id, &subscriber_str)
+    }
+
+    // private helper to get the subscribers of a topic by key, only the latest entry
+    // of each of them with a valid signature is taken
+    fn topic_subscribers(&self, topic: &str) -> ResultReturn<BTreeMap<String, TopicSubscriber>> {
+        let mut subscribers: BTreeMap<String, (ActionReqId, TopicSubscriber)> = BTreeMap::new();
+        for (request_id, subscriber_str) in self.safe_thing_comm.get_topic_subscribers()? {
+            let subscriber = match serde_json::from_str::<TopicSubscriber>(&subscriber_str) {
+                Ok(ref subscriber) if subscriber.topic != topic => continue,
+                Ok(subscriber) => subscriber,
+                Err(err) => {
+                    debug!("Skipping invalid subscriber ({}): {}", request_id, err);
+                    continue;
+                }
+            };
+            let signed_data = topic_subscriber_signed_data(&self.thing_id, request_id, &subscriber);
+            match self.safe_thing_comm.verify(
+                &subscriber.requester_key,
+                &signed_data,
+                &subscriber.signature,
+            ) {
+                Ok(true) => (),
+                _ => {
+                    debug!(
+                        "Skipping subscriber with an invalid signature ({})",
+                        request_id
+                    );
+                    continue;
+                }
+            }
+            match subscribers.get(&subscriber.requester_key) {
+                Some((latest_id, _)) if *latest_id > request_id => (),
+                _ => {
+                    let _ = subscribers
+                        .insert(subscriber.requester_key.clone(), (request_id, subscriber));
+                }
+            }
+        }
+        Ok(subscribers
+            .into_iter()
+            .map(|(key, (_, subscriber))| (key, subscriber))
+            .collect())
+    }
+
+    // private helper
+    fn store_subscriptions(&self, subscriptions: &RegisteredSubscriptions) -> ResultReturn<()> {
+        let subscriptions_str: String = serde_json::to_string(subscriptions).unwrap();
+        self.safe_thing_comm
+            .set_subscriptions(subscriptions_str.as_str())
+    }
+
+    /// Notify of an event associated to an speficic topic.
+    /// Eventually this can support multiple topics.
+    pub fn notify(&self, topic: &str, data: &str) -> ResultReturn<()> {
+        if self.is_disabled() {
+            return Err(Error::new(
+                ErrorCode::ThingNotPublished,
+                format!(
+                    "SAFEthing {} is disabled, events cannot be notified",
+                    self.thing_id
+                )
+                .as_str(),
+            ));
+        }
+        info!("Notifying event for topic: {}, data: {}", topic, data);
+        let topics = self.get_thing_topics(&self.thing_id)?;
+        if let Some(topic_def) = topics
+            .iter()
+            .find(|t| t.name == topic && t.access != AccessType::All)
+        {
+            return self.notify_sealed(topic_def, data);
+        }
+
+        let events: String = self.safe_thing_comm.get_topic_events(topic)?;
+        let mut events_vec: Vec<(Timestamp, String)> = match serde_json::from_str(&events) {
+            Ok(vec) => vec,
+            Err(_) => vec![],
+        };
+        let timestamp = gen_timestamp();
+        events_vec.push((timestamp, data.to_string()));
+        let events_str: String = serde_json::to_string(&events_vec).unwrap();
+        self.safe_thing_comm
+            .set_topic_events(topic, events_str.as_str())?;
+        Ok(())
+    }
+
+    // private helper to notify an event of a topic which is not for All, it's sealed for
+    // each subscriber which is currently granted access to the topic
+    fn notify_sealed(&self, topic: &Topic, data: &str) -> ResultReturn<()> {
+        let authorised = self.authorised_keys()?;
+        let groups = self.groups()?;
+        let timestamp = gen_timestamp();
+        for (subscriber_key, subscriber) in self.topic_subscribers(&topic.name)? {
+            let is_group_member = || {
+                self.is_group_member(
+                    &groups,
+                    &topic.groups,
+                    &subscriber.thing_id,
+                    &subscriber_key,
+                )
+            };
+            if subscriber.enc_key.is_empty()
+                || !is_access_granted(
+                    &topic.access,
+                    &subscriber_key,
+                    self.sign_key(),
+                    &authorised,
+                    is_group_member,
+                )
+            {
+                continue;
+            }
+            let sealed = match self
+                .safe_thing_comm
+                .encrypt_sealed(&subscriber.enc_key, data)
+            {
+                Ok(sealed) => sealed,
+                Err(err) => {
+                    warn!(
+                        "Failed to seal the event for subscriber {}: {}",
+                        subscriber.thing_id, err
+                    );
+                    continue;
+                }
+            };
+            let events = self
+                .safe_thing_comm
+                .get_sealed_topic_events(&topic.name, &subscriber_key);
+            let mut events_vec: Vec<(Timestamp, String)> = match serde_json::from_str(&events) {
+                Ok(vec) => vec,
+                Err(_) => vec![],
+            };
+            events_vec.push((timestamp, sealed));
+            let events_str: String = serde_json::to_string(&events_vec).unwrap();
+            self.safe_thing_comm.set_sealed_topic_events(
+                &topic.name,
+                &subscriber_key,
+                &events_str,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Send an action request to a SAFEthing and monitor its state
+    /// Search on the network by thing_id
+    pub fn action_request<F>(
+        &self,
+        thing_id: &str,
+        action: &str,
+        args: &[&str],
+        cb: F,
+    ) -> ResultReturn<ActionReqId>
+    where
+        F: Fn(&str, Result<Option<&ActionValue>, &str>) -> bool + Send + Sync + 'static,
+    {
+        let handle = self.send_action_request(thing_id, action, args)?;
+        let req_id = handle.request_id;
+        let timeout = Duration::from_millis(ACTION_REQUEST_MONITORING_TIMEOUT);
+        let stop_signal = self.stop_signal();
+        spawn_action_req_monitoring_thread(handle, timeout, stop_signal, Box::new(cb));
+
+        Ok(req_id)
+    }
+
+    /// Send an action request to a SAFEthing, returning a handle to wait for its outcome
+    /// Search on the network by thing_id
+    pub fn send_action_request(
+        &self,
+        thing_id: &str,
+        action: &str,
+        args: &[&str],
+    ) -> ResultReturn<ActionReqHandle<S>> {
+        let mut args_vec = Vec::new();
+        args_vec.extend(args.iter().map(|&arg| arg.to_string()));
+        let mut action_req = ActionReq {
+            thing_id: self.thing_id.clone(),
+            requester_key: self.safe_thing_comm.sign_key().to_string(),
+            action: action.to_string(),
+            args: args_vec,
+            signature: String::new(),
+            state: ACTION_REQUEST_INIT_STATE.to_string(),
+            error: None,
+            result: None,
+        };
+        let req_id = self.safe_thing_comm.gen_request_id();
+        action_req.signature =
+            self.safe_thing_comm
+                .sign(&action_req_signed_data(thing_id, req_id, &action_req))?;
+        let action_req_str: String = serde_json::to_string(&action_req).unwrap();
+
+        self.safe_thing_comm
+            .send_action_request(thing_id, req_id, action_req_str.as_str())?;
+
+        Ok(self.action_request_handle(thing_id, req_id))
+    }
+
+    /// Get a handle to wait for the outcome of an action request previously
+    /// sent to a SAFEthing
+    pub fn action_request_handle(
+        &self,
+        thing_id: &str,
+        request_id: ActionReqId,
+    ) -> ActionReqHandle<S> {
+        ActionReqHandle {
+            thing_id: thing_id.to_string(),
+            request_id,
+            safe_thing_comm: Arc::clone(&self.safe_thing_comm),
+            poll_interval: Duration::from_millis(ACTION_REQUEST_MONITORING_FREQ),
+        }
+    }
+
+    /// Get the current state of an action request sent to a SAFEthing
+    /// Search on the network by thing_id
+    pub fn get_action_request_state(
+        &self,
+        thing_id: &str,
+        request_id: ActionReqId,
+    ) -> ResultReturn<String> {
+        let req_state = self.action_request_handle(thing_id, request_id).state()?;
+        Ok(req_state.state)
+    }
+
+    /// Update the state of an action request received, e.g. to report its progress
+    pub fn update_action_request_state(
+        &self,
+        request_id: ActionReqId,
+        new_state: &str,
+    ) -> ResultReturn<()> {
+        let mut action_req = self.read_action_req(request_id)?;
+        action_req.state = new_state.to_string();
+        self.store_action_req(request_id, &action_req)
+    }
+
+    // Read an action request received by this SAFEthing
+    fn read_action_req(&self, request_id: ActionReqId) -> ResultReturn<ActionReq> {
+        let action_req_str = self
+            .safe_thing_comm
+            .get_thing_action_request_state(&self.thing_id, request_id)?;
+        serde_json::from_str(&action_req_str).map_err(|_| {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                &format!("Action request ({}) not found", request_id),
+            )
+        })
+    }
+
+    fn store_action_req(
+        &self,
+        request_id: ActionReqId,
+        action_req: &ActionReq,
+    ) -> ResultReturn<()> {
+        let action_req_str: String = serde_json::to_string(action_req).unwrap();
+        self.safe_thing_comm
+            .set_action_request_state(request_id, &action_req_str)
+    }
+
+    /// Stop the background threads checking for notifications and action requests, waiting
+    /// for them to finish what they are doing, and optionally set the status to Disabled.
+    /// This is also done, without changing the status, when the last clone of the SAFEthing
+    /// held by the application is dropped.
+    /// The SAFEthing can be registered again to restart them.
+    pub fn shutdown(&self, disable: bool) -> ResultReturn<()> {
+        self.stop_workers();
+        if disable {
+            self.safe_thing_comm.set_disabled()?;
+        }
+        info!("SAFEthing shut down, ID: {}", self.thing_id);
+        Ok(())
+    }
+
+    fn stop_workers(&self) {
+        // They are joined without holding the lock, as they could be stopped from the
+        // callbacks invoked by them
+        let workers: Vec<_> = {
+            let mut workers = self.workers.lock().unwrap();
+            self.stop_signal().stop();
+            workers.drain(..).collect()
+        };
+        for worker in workers {
+            // It could be dropped from the callbacks invoked by the workers
+            if worker.thread().id() != thread::current().id() && worker.join().is_err() {
+                error!(
+                    "A background thread of SAFEthing {} panicked",
+                    self.thing_id
+                );
+            }
+        }
+    }
+
+    // Signal to stop the background threads currently running
+    fn stop_signal(&self) -> Arc<StopSignal> {
+        Arc::clone(&self.stop_signal.lock().unwrap())
+    }
+
+    // Instance given to the background threads, which doesn't keep them running
+    fn worker_clone(&self) -> SAFEthing<S> {
+        SAFEthing {
+            thing_id: self.thing_id.clone(),
+            safe_thing_comm: Arc::clone(&self.safe_thing_comm),
+            subscriptions: Arc::clone(&self.subscriptions),
+            notifs_cb: Arc::clone(&self.notifs_cb),
+            action_req_cb: Arc::clone(&self.action_req_cb),
+            stop_signal: Arc::clone(&self.stop_signal),
+            claim_code: Arc::clone(&self.claim_code),
+            workers: Arc::clone(&self.workers),
+            instances: Arc::clone(&self.instances),
+            counted: false,
+        }
+    }
+
+    /// Only for testing, to simulate a network disconnection event
+    pub fn simulate_net_disconnect(&self) {
+        self.safe_thing_comm.sim_net_disconnect();
+    }
+}
+
+/// Handle of an action request sent to a SAFEthing, it polls the network
+/// to retrieve the state of the request
+pub struct ActionReqHandle<S: ThingStore = SAFENet> {
+    pub thing_id: String,
+    pub request_id: ActionReqId,
+    safe_thing_comm: Arc<SAFEthingComm<S>>,
+    poll_interval: Duration,
+}
+
+impl<S: ThingStore> ActionReqHandle<S> {
+    /// Set how often the network is polled while waiting for the outcome of the request
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> ActionReqHandle<S> {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Get the current state of the action request
+    pub fn state(&self) -> ResultReturn<ActionReqState> {
+        let action_req_str = self
+            .safe_thing_comm
+            .get_thing_action_request_state(&self.thing_id, self.request_id)?;
+        match serde_json::from_str::<ActionReq>(&action_req_str) {
+            Ok(action_req) => Ok(ActionReqState {
+                state: action_req.state,
+                result: action_req.result,
+                error: action_req.error,
+            }),
+            Err(_) => Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Action request ({}) not found", self.request_id).as_str(),
+            )),
+        }
+    }
+
+    /// Block until the action request reaches a final state, or fail with a
+    /// `Timeout` error if it doesn't within the time given
+    pub fn wait(&self, timeout: Duration) -> ResultReturn<ActionReqState> {
+        let start = Instant::now();
+        loop {
+            let req_state = self.state()?;
+            if req_state.is_final() {
+                return Ok(req_state);
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(Error::new(
+                    ErrorCode::Timeout,
+                    &format!(
+                        "Action request ({}) is still in state '{}'",
+                        self.request_id, req_state.state
+                    ),
+                ));
+            }
+            thread::sleep(std::cmp::min(self.poll_interval, timeout - elapsed));
+        }
+    }
+}
+
+// Helper to generate timestamp (in nanos)
+fn gen_timestamp() -> Timestamp {
+    let now_timestamp = SystemTime::now();
+    let since_the_epoch = now_timestamp
+        .duration_since(UNIX_EPOCH)
+        .expect("Failed to get time since epoch");
+
+    since_the_epoch.as_nanos()
+}
+
+// spawn a thread which takes care of monitoring topics which the SAFEthing subcribed to
+fn spawn_check_subsc_thread<S: ThingStore>(safe_thing: SAFEthing<S>) -> JoinHandle<()> {
+    let notifs_cb = Arc::clone(&safe_thing.notifs_cb);
+    let stop_signal = safe_thing.stop_signal();
+    thread::spawn(move || {
+        loop {
+            trace!("Checking subscriptions...");
+            // We work on a copy so the subscriptions list is not locked while
+            // checking the remote things and invoking the callback
+            let mut subscriptions = safe_thing.subscriptions.lock().unwrap().clone();
+
+            for (thing_id, thing_subs) in subscriptions.iter_mut() {
+                for subscription in thing_subs.iter_mut() {
+                    match subscription {
+                        Subscription::Topic((topic_subs, last_report_timestamp)) => {
+                            check_topic_subs_and_notify(
+                                thing_id,
+                                &safe_thing,
+                                &*notifs_cb,
+                                topic_subs,
+                                last_report_timestamp,
+                            );
+                        }
+                        Subscription::Attr((attr_subs, last_val_reported)) => {
+                            check_attrs_subs_and_notify(
+                                thing_id,
+                                &safe_thing,
+                                &*notifs_cb,
+                                attr_subs,
+                                last_val_reported,
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Keep track of the last reports made, persisting them on the network
+            // so we neither miss events nor send duplicates after a restart
+            let mut current_subs = safe_thing.subscriptions.lock().unwrap();
+            let mut updated = false;
+            for (thing_id, thing_subs) in subscriptions.iter() {
+                if let Some(current) = current_subs.get_mut(thing_id) {
+                    for subscription in current.iter_mut() {
+                        for checked in thing_subs.iter() {
+                            updated |= subscription.update_last_report(checked);
+                        }
+                    }
+                }
+            }
+            if updated {
+                match safe_thing.store_subscriptions(&current_subs) {
+                    Ok(()) => trace!("Subscriptions last reports stored on the network"),
+                    Err(err) => error!("Failed to store subscriptions: {}", err),
+                }
+            }
+            drop(current_subs);
+
+            trace!("CHECKED SUBSCRIPTIONS....WAIT FOR NEXT LOOP");
+            if stop_signal.wait(Duration::from_millis(SUBSCRIPTIONS_CHECK_FREQ)) {
+                break;
+            }
+        }
+        debug!(
+            "Ending subscriptions thread of SAFEthing: {}",
+            safe_thing.thing_id
+        );
+    })
+}
+
+fn check_topic_subs_and_notify<S: ThingStore>(
+    thing_id: &String,
+    safe_thing: &SAFEthing<S>,
+    notifs_cb: &SubsNotifCallback<S>,
+    topic_subs: &mut TopicSubscription,
+    last_report_timestamp: &mut Timestamp,
+) {
+    let TopicSubscription {
+        topic,
+        filter_op,
+        filter_value,
+        sealed,
+    } = topic_subs;
+    trace!(
+        "CHECKING TOPIC EVENTS FROM (thingId -> topic): {} -> {}",
+        thing_id,
+        topic
+    );
+
+    // The connection is shared with the application, thus if it's currently failing
+    // we just try again in the next check as the last report is kept as is
+    let events = if *sealed {
+        safe_thing
+            .safe_thing_comm
+            .get_thing_sealed_topic_events(thing_id, topic)
+    } else {
+        safe_thing
+            .safe_thing_comm
+            .get_thing_topic_events(thing_id, topic)
+    };
+    let events = match events {
+        Ok(events) => events,
+        Err(err) => {
+            warn!(
+                "Failed to read the events of topic '{}' of thing {}: {}",
+                topic, thing_id, err
+            );
+            return;
+        }
+    };
+    let events_vec: Vec<(Timestamp, String)> = match serde_json::from_str(&events) {
+        Ok(vec) => vec,
+        Err(_) => vec![],
+    };
+    for (event_timestamp, event) in events_vec.iter() {
+        // The events sealed with the key of a previous session cannot be opened anymore
+        let event = if *sealed {
+            match safe_thing.safe_thing_comm.decrypt_sealed(event) {
+                Ok(event) => event,
+                Err(_) => {
+                    debug!("Skipping sealed event which cannot be opened");
+                    continue;
+                }
+            }
+        } else {
+            event.clone()
+        };
+        let do_eval = event_timestamp > last_report_timestamp;
+        if do_eval && filter_op.eval(&event, &filter_value) {
+            debug!(
+                "Event occurred for topic: {}, event: ({}, {})",
+                topic, event_timestamp, event
+            );
+            (notifs_cb)(
+                safe_thing,
+                thing_id.as_str(),
+                topic.as_str(),
+                event.as_str(),
+                *event_timestamp,
+            );
+
+            // update last_report_timestamp in the subscriptions list to not
+            // keep sending the notification for same (and already notified) event
+            *last_report_timestamp = *event_timestamp;
+        }
+    }
+}
+
+fn check_attrs_subs_and_notify<S: ThingStore>(
+    thing_id: &String,
+    safe_thing: &SAFEthing<S>,
+    notifs_cb: &SubsNotifCallback<S>,
+    attr_subs: &mut AttrSubscription,
+    last_val_reported: &mut String,
+) {
+    let AttrSubscription {
+        attr_name,
+        filter_op,
+        filter_value,
+    } = attr_subs;
+
+    trace!(
+        "CHECKING DYNAMIC ATTRIBUTES CHANGES FROM thingId: {} - {:?}",
+        thing_id,
+        attr_name
+    );
+
+    // As for the topics, if it fails we just try again in the next check
+    let attrs = match safe_thing.safe_thing_comm.get_thing_attrs(thing_id) {
+        Ok(attrs) => attrs,
+        Err(err) => {
+            warn!(
+                "Failed to read the attributes of thing {}: {}",
+                thing_id, err
+            );
+            return;
+        }
+    };
+    let attrs_vec: Vec<ThingAttr> = match serde_json::from_str(&attrs) {
+        Ok(vec) => vec,
+        Err(_) => vec![],
+    };
+    for ThingAttr {
+        attr,
+        value,
+        is_dynamic,
+        ..
+    } in attrs_vec
+    {
+        let do_eval = is_dynamic
+            && *attr_name == attr
+            && (last_val_reported.len() == 0 || !filter_op.eval(&value, last_val_reported));
+        if do_eval && filter_op.eval(&value, &filter_value) {
+            debug!(
+                "Dynamic attribute change occurred: {}, value: {}",
+                attr, value
+            );
+            (notifs_cb)(
+                safe_thing,
+                thing_id.as_str(),
+                attr.as_str(),
+                value.as_str(),
+                0,
+            );
+
+            // update last_val_reported in the subscriptions list to not
+            // keep sending the notification for same (and already notified) event
+            *last_val_reported = value;
+        }
+    }
+}
+
+// spawn a thread which takes care of monitoring for new action requests received
+fn spawn_check_new_action_reqs<S: ThingStore>(safe_thing: SAFEthing<S>) -> JoinHandle<()> {
+    let stop_signal = safe_thing.stop_signal();
+    thread::spawn(move || {
+        // Outcome of the requests already handled which couldn't be stored yet, they are
+        // stored in the next loops rather than handling the requests again
+        let mut unstored: BTreeMap<ActionReqId, ActionReq> = BTreeMap::new();
+        loop {
+            trace!("Checking for new claim and action requests...");
+            unstored.retain(|request_id, action_req| {
+                safe_thing
+                    .store_action_req(*request_id, action_req)
+                    .is_err()
+            });
+            if let Err(err) = safe_thing.check_claim_requests() {
+                warn!("Failed to process the claim requests received: {}", err);
+            }
+            if let Err(err) = safe_thing.check_enc_info_requests() {
+                warn!(
+                    "Failed to process the encryption info requests received: {}",
+                    err
+                );
+            }
+            // The connection is shared with the application, thus if it's currently
+            // failing we just try again in the next loop
+            let actions_reqs_vec = match safe_thing.safe_thing_comm.get_actions_requests() {
+                Ok(actions_reqs_vec) => actions_reqs_vec,
+                Err(err) => {
+                    warn!("Failed to read the action requests received: {}", err);
+                    if stop_signal.wait(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            trace!("Actions requested to process: {:?}", actions_reqs_vec);
+            // The requests are validated against the actions currently published, and the
+            // keys and groups currently authorised, if we cannot read them we just try again
+            // in the next loop
+            let actions = safe_thing.get_thing_actions(&safe_thing.thing_id);
+            let authorised = safe_thing.authorised_keys();
+            let groups = safe_thing.groups();
+            let (actions, authorised, groups) = match (actions, authorised, groups) {
+                (Ok(actions), Ok(authorised), Ok(groups)) => (actions, authorised, groups),
+                (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                    warn!(
+                        "Failed to read the actions, authorised keys or groups: {}",
+                        err
+                    );
+                    if stop_signal.wait(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let disabled = safe_thing.is_disabled();
+            for (request_id, action_req_str) in actions_reqs_vec.iter() {
+                // The requests already handled were stored, the rest are left for later
+                if stop_signal.is_stopped() {
+                    break;
+                }
+                if unstored.contains_key(request_id) {
+                    continue;
+                }
+                match serde_json::from_str::<ActionReq>(&action_req_str) {
+                    Ok(mut action_req) => {
+                        if action_req.state == ACTION_REQUEST_INIT_STATE {
+                            debug!("Action requested: {:?}", action_req.action);
+                            let validation = if disabled {
+                                Err(format!("SAFEthing {} is disabled", safe_thing.thing_id))
+                            } else {
+                                safe_thing
+                                    .verify_requester(*request_id, &action_req)
+                                    .and_then(|()| {
+                                        safe_thing.check_action_access(
+                                            &actions,
+                                            &action_req,
+                                            &authorised,
+                                            &groups,
+                                        )
+                                    })
+                                    .and_then(|()| validate_action_req(&actions, &action_req))
+                            };
+                            match validation {
+                                Ok(action_args) => {
+                                    let result = (safe_thing.action_req_cb)(
+                                        &safe_thing,
+                                        *request_id,
+                                        action_req.thing_id.as_str(),
+                                        action_req.action.as_str(),
+                                        &action_args,
+                                    );
+                                    // The state may have been updated while handling the request
+                                    if let Ok(current) = safe_thing.read_action_req(*request_id) {
+                                        action_req = current;
+                                    }
+                                    set_action_result(&actions, &mut action_req, result);
+                                    debug!(
+                                        "Action request handled by SAFEthing. Updating new state to {}",
+                                        action_req.state
+                                    );
+                                }
+                                Err(reason) => {
+                                    warn!("Action request {} rejected: {}", request_id, reason);
+                                    action_req.state = ACTION_REQUEST_REJECTED_STATE.to_string();
+                                    action_req.error = Some(reason);
+                                }
+                            }
+                            if let Err(err) = safe_thing.store_action_req(*request_id, &action_req)
+                            {
+                                warn!(
+                                    "Failed to store the state of action request {}, retrying: {}",
+                                    request_id, err
+                                );
+                                let _ = unstored.insert(*request_id, action_req);
+                            }
+                        }
+                    }
+                    Err(err) => error!("Action request is invalid, thus ignoring it: {}", err),
+                };
+
+                // TODO: keep track of the actions requests that were already notified
+            }
+            trace!("CHECKED ACTIONS....WAIT FOR NEXT LOOP");
+            if stop_signal.wait(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+                break;
+            }
+        }
+        debug!(
+            "Ending action requests thread of SAFEthing: {}",
+            safe_thing.thing_id
+        );
+    })
+}
+
+// Map the status stored on the network to the one exposed by the API
+fn to_status(status: ThingStatus) -> Status {
+    match status {
+        ThingStatus::Unknown => Status::Unknown,
+        ThingStatus::Connected => Status::Connected,
+        ThingStatus::Published => Status::Published,
+        ThingStatus::Disabled => Status::Disabled,
+    }
+}
+
+// Check if the access type is granted to a requester, the SAFEthing itself is always
+// granted access, and the keys authorised as Owner are also granted the Group access.
+// The membership to the groups is only checked if needed, since it may require reading
+// from the network.
+fn is_access_granted<F>(
+    access: &AccessType,
+    requester_key: &str,
+    thing_key: &str,
+    authorised: &AuthorisedKeys,
+    is_group_member: F,
+) -> bool
+where
+    F: FnOnce() -> bool,
+{
+    if requester_key == thing_key {
+        return true;
+    }
+    let granted = authorised.get(requester_key);
+    match access {
+        AccessType::All => true,
+        AccessType::Thing => false,
+        AccessType::Owner => granted == Some(&AccessType::Owner),
+        AccessType::Group => {
+            granted == Some(&AccessType::Owner)
+                || granted == Some(&AccessType::Group)
+                || is_group_member()
+        }
+    }
+}
+
+// Error returned when a group is not found
+fn no_such_group(group: &str) -> Error {
+    Error::new(
+        ErrorCode::InvalidArgument,
+        format!("Group '{}' doesn't exist", group).as_str(),
+    )
+}
+
+// The data signed by the requester of an action, it includes the SAFEthing the request
+// is sent to and the ID of the request so the signature cannot be reused for another one
+fn action_req_signed_data(
+    target_id: &str,
+    request_id: ActionReqId,
+    action_req: &ActionReq,
+) -> String {
+    serde_json::to_string(&(
+        target_id,
+        request_id.to_string(),
+        &action_req.thing_id,
+        &action_req.requester_key,
+        &action_req.action,
+        &action_req.args,
+    ))
+    .unwrap()
+}
+
+// The data signed by the requester of a claim, as for the action requests
+fn claim_req_signed_data(target_id: &str, request_id: ActionReqId, claim_req: &ClaimReq) -> String {
+    serde_json::to_string(&(
+        target_id,
+        request_id.to_string(),
+        &claim_req.thing_id,
+        &claim_req.requester_key,
+        &claim_req.proof,
+    ))
+    .unwrap()
+}
+
+// The data signed by the requester of the encryption info, as for the action requests
+fn enc_info_req_signed_data(
+    target_id: &str,
+    request_id: ActionReqId,
+    enc_info_req: &EncInfoReq,
+) -> String {
+    serde_json::to_string(&(
+        target_id,
+        request_id.to_string(),
+        &enc_info_req.thing_id,
+        &enc_info_req.requester_key,
+        &enc_info_req.enc_key,
+    ))
+    .unwrap()
+}
+
+// The outcome of a claim or a request for the encryption info which wasn't accepted,
+// it's only known once it was rejected
+fn rejected_outcome<T>(state: String, error: Option<String>) -> ResultReturn<Option<T>> {
+    if state == ACTION_REQUEST_REJECTED_STATE {
+        Err(Error::new(
+            ErrorCode::AccessDenied,
+            error.unwrap_or_default().as_str(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+// The data signed by a subscriber of a topic which is not for All, as for the action requests
+fn topic_subscriber_signed_data(
+    target_id: &str,
     request_id: ActionReqId,
-    safething_comm: SAFEthingComm,
-    cb: &'static (Fn(&str) -> bool + std::marker::Send + std::marker::Sync),
+    subscriber: &TopicSubscriber,
+) -> String {
+    serde_json::to_string(&(
+        target_id,
+        request_id.to_string(),
+        &subscriber.thing_id,
+        &subscriber.requester_key,
+        &subscriber.topic,
+        &subscriber.enc_key,
+    ))
+    .unwrap()
+}
+
+// The topics of other SAFEthings whose events are sealed for this one, by SAFEthing ID
+fn sealed_topics(subscriptions: &RegisteredSubscriptions) -> BTreeSet<(String, String)> {
+    let mut sealed = BTreeSet::new();
+    for (thing_id, thing_subs) in subscriptions.iter() {
+        for subscription in thing_subs.iter() {
+            if let Subscription::Topic((topic_subs, _)) = subscription {
+                if topic_subs.sealed {
+                    let _ = sealed.insert((thing_id.clone(), topic_subs.topic.clone()));
+                }
+            }
+        }
+    }
+    sealed
+}
+
+// The data sealed as proof of knowing the code to claim a SAFEthing
+fn claim_proof_data(code: &str, requester_key: &str) -> String {
+    format!("{}:{}", code, requester_key)
+}
+
+// Check the action requested is supported and the arguments are valid, returning them
+// coerced according to the action's parameters, with the defaults for those not provided
+fn validate_action_req(
+    actions: &[ActionDef],
+    action_req: &ActionReq,
+) -> Result<Vec<ActionValue>, String> {
+    let action_def = match actions.iter().find(|a| a.name == action_req.action) {
+        Some(action_def) => action_def,
+        None => return Err(format!("Action '{}' is not supported", action_req.action)),
+    };
+
+    let max_args = action_def.params.len();
+    let min_args = action_def
+        .params
+        .iter()
+        .take_while(|p| p.default.is_none())
+        .count();
+    let num_args = action_req.args.len();
+    if num_args < min_args || num_args > max_args {
+        let expected = if min_args == max_args {
+            max_args.to_string()
+        } else {
+            format!("{} to {}", min_args, max_args)
+        };
+        return Err(format!(
+            "Action '{}' expects {} argument(s) but {} were provided",
+            action_req.action, expected, num_args
+        ));
+    }
+
+    action_def
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let value = match (action_req.args.get(i), &param.default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => default,
+                (None, None) => unreachable!(),
+            };
+            param
+                .value_type
+                .coerce(value)
+                .map_err(|err| format!("Invalid argument '{}': {}", param.name, err))
+        })
+        .collect()
+}
+
+// Store the outcome of an action in its request, validating and coercing the output
+fn set_action_result(actions: &[ActionDef], action_req: &mut ActionReq, result: ActionResult) {
+    let output_type = actions
+        .iter()
+        .find(|a| a.name == action_req.action)
+        .and_then(|a| a.output.as_ref());
+    let outcome = match (result, output_type) {
+        (Ok(None), _) => Ok(None),
+        (Ok(Some(output)), Some(output_type)) => output_type
+            .coerce(&output)
+            .map(Some)
+            .map_err(|err| format!("Invalid output of action '{}': {}", action_req.action, err)),
+        (Ok(Some(_)), None) => Err(format!(
+            "Action '{}' doesn't define an output",
+            action_req.action
+        )),
+        (Err(err), _) => Err(err),
+    };
+
+    match outcome {
+        Ok(output) => {
+            action_req.state = ACTION_REQUEST_DONE_STATE.to_string();
+            action_req.result = output;
+        }
+        Err(err) => {
+            warn!("Action '{}' failed: {}", action_req.action, err);
+            action_req.state = ACTION_REQUEST_FAILED_STATE.to_string();
+            action_req.error = Some(err);
+        }
+    }
+}
+
+// spawn a thread to check for a change in the state of an action request sent
+fn spawn_action_req_monitoring_thread<S: ThingStore>(
+    handle: ActionReqHandle<S>,
+    timeout: Duration,
+    stop_signal: Arc<StopSignal>,
+    cb: Box<ActionReqStateCallback>,
 ) {
     let mut current_state = ACTION_REQUEST_INIT_STATE.to_string();
-    let mut keep_checking = true;
-    let start_timestamp = SystemTime::now();
-    let mut timeout = false;
+    let start = Instant::now();
+
+    thread::spawn(move || {
+        let request_id = handle.request_id;
+        loop {
+            trace!("Checking action request state...");
+            match handle.state() {
+                Ok(req_state) => {
+                    trace!(
+                        "Action request new state obtained, request id: {}, new state: {}",
+                        request_id,
+                        req_state.state
+                    );
+                    if req_state.state != current_state {
+                        current_state = req_state.state.clone();
+                        debug!(
+                        "Callback to notify action request new state, request id: {}, new state: {}",
+                        request_id, req_state.state);
+                        let keep_checking = (cb)(&req_state.state, req_state.outcome());
+                        debug!("Keep checking sent action request state? {}", keep_checking);
+                        if !keep_checking || req_state.is_final() {
+                            break;
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!(
+                        "Action request ({}) current state couldn't be read: {}",
+                        request_id, err
+                    );
+                }
+            };
+
+            if start.elapsed() > timeout {
+                warn!(
+                    "Monitoring thread for action request {} timed out",
+                    request_id
+                );
+                break;
+            }
+
+            trace!("CHECKED ACTION REQUEST STATE....WAIT FOR NEXT LOOP");
+            if stop_signal.wait(handle.poll_interval) {
+                break;
+            }
+        }
+
+        debug!(
+            "Ending monitoring thread for sent action request: {}",
+            request_id
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+
+    // Max time to wait for the background threads to report a notification
+    const NOTIF_TIMEOUT: u64 = 30_000;
+
+    type Notif = (String, String, String);
+    type ActionReqNotif = (String, String, Vec<ActionValue>);
+
+    // Helper to create and register a SAFEthing on an in-memory network, the notifications
+    // it receives are sent through the returned channels
+    fn new_thing(
+        thing_id: &str,
+        network: &str,
+        attrs: &[ThingAttr],
+        topics: &[Topic],
+        actions: &[ActionDef],
+    ) -> (
+        SAFEthing<MemStore>,
+        Receiver<Notif>,
+        Receiver<ActionReqNotif>,
+    ) {
+        let (notif_tx, notif_rx) = mpsc::channel();
+        let notif_tx = Mutex::new(notif_tx);
+        let notifs_cb = move |_: &SAFEthing<MemStore>,
+                              thing_id: &str,
+                              topic: &str,
+                              data: &str,
+                              _: Timestamp| {
+            let notif = (thing_id.to_string(), topic.to_string(), data.to_string());
+            let _ = notif_tx.lock().unwrap().send(notif);
+        };
+
+        let (action_tx, action_rx) = mpsc::channel();
+        let action_tx = Mutex::new(action_tx);
+        let action_req_cb = move |_: &SAFEthing<MemStore>,
+                                  _: ActionReqId,
+                                  thing_id: &str,
+                                  action: &str,
+                                  args: &[ActionValue]| {
+            let req = (thing_id.to_string(), action.to_string(), args.to_vec());
+            let _ = action_tx.lock().unwrap().send(req);
+            Ok(None)
+        };
+
+        let safe_thing =
+            SAFEthing::with_store(thing_id, network, notifs_cb, action_req_cb).unwrap();
+        safe_thing.register(attrs, topics, actions).unwrap();
+        safe_thing.publish().unwrap();
+
+        (safe_thing, notif_rx, action_rx)
+    }
+
+    fn recv<T>(rx: &Receiver<T>) -> T {
+        rx.recv_timeout(Duration::from_millis(NOTIF_TIMEOUT))
+            .expect("Timed out waiting for notification")
+    }
+
+    #[test]
+    fn thing_id_too_short() {
+        let res = SAFEthing::<MemStore>::with_store(
+            "abc",
+            "test_thing_id_too_short",
+            |_, _, _, _, _| {},
+            |_, _, _, _, _| Ok(None),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn register_and_publish() {
+        let network = "test_register_and_publish";
+        let attrs = [
+            ThingAttr::new("name", "Test Device", false),
+            ThingAttr::new("level", "10", true),
+        ];
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (device, _, _) = new_thing("test-device", network, &attrs, &topics, &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        match device.status().unwrap() {
+            Status::Published => (),
+            status => panic!("Unexpected status: {}", status),
+        };
+        match controller.get_thing_status("test-device").unwrap() {
+            Status::Published => (),
+            status => panic!("Unexpected status: {}", status),
+        };
+        match controller.get_thing_status("test-unregistered").unwrap() {
+            Status::NonConnected => (),
+            status => panic!("Unexpected status: {}", status),
+        };
+
+        let attrs = controller.get_thing_attrs("test-device").unwrap();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[1].attr, "level");
+        assert_eq!(attrs[1].value, "10");
+        assert!(attrs[1].is_dynamic);
+
+        let topics = controller.get_thing_topics("test-device").unwrap();
+        assert_eq!(topics.len(), 1);
+        assert_eq!(topics[0].name, "Alarm");
+
+        let actions = controller.get_thing_actions("test-device").unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].name, "Open");
+        assert_eq!(
+            actions[0].params,
+            vec![ActionParam::new("psi", ValueType::String, None)]
+        );
+
+        device.set_attr_value("level", "11").unwrap();
+        let attrs = controller.get_thing_attrs("test-device").unwrap();
+        assert_eq!(attrs[1].value, "11");
+    }
+
+    #[test]
+    fn subscribe_to_attr() {
+        let network = "test_subscribe_to_attr";
+        let attrs = [ThingAttr::new("level", "6.5", true)];
+        let (device, _, _) = new_thing("test-device", network, &attrs, &[], &[]);
+        let (controller, notif_rx, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        controller
+            .subscribe_to_attr("test-device", "level", FilterOperator::LessThan, "5.0")
+            .unwrap();
+        device.set_attr_value("level", "4.5").unwrap();
+
+        let notif = recv(&notif_rx);
+        assert_eq!(
+            notif,
+            (
+                "test-device".to_string(),
+                "level".to_string(),
+                "4.5".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn subscribe_to_topic() {
+        let network = "test_subscribe_to_topic";
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let (device, _, _) = new_thing("test-device", network, &[], &topics, &[]);
+        let (controller, notif_rx, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        controller
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Equal, "on")
+            .unwrap();
+        device.notify("Alarm", "off").unwrap();
+        device.notify("Alarm", "on").unwrap();
+
+        let notif = recv(&notif_rx);
+        assert_eq!(
+            notif,
+            (
+                "test-device".to_string(),
+                "Alarm".to_string(),
+                "on".to_string()
+            )
+        );
+        match notif_rx.recv_timeout(Duration::from_millis(SUBSCRIPTIONS_CHECK_FREQ * 2)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected notification: {:?}", other),
+        };
+    }
+
+    #[test]
+    fn subscriptions_validation() {
+        let network = "test_subscriptions_validation";
+        let attrs = [
+            ThingAttr::new("name", "Test Device", false),
+            ThingAttr::new("level", "10", true),
+        ];
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let _ = new_thing("test-device", network, &attrs, &topics, &[]);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        let unpublished = SAFEthing::<MemStore>::with_store(
+            "test-unpublished",
+            network,
+            |_, _, _, _, _| {},
+            |_, _, _, _, _| Ok(None),
+        )
+        .unwrap();
+        unpublished.register(&attrs, &topics, &[]).unwrap();
 
-    thread::spawn(move || {
-        while keep_checking && current_state != ACTION_REQUEST_DONE_STATE && !timeout {
-            trace!("Checking action request state...");
-            let action_req_str = safething_comm
-                .get_thing_action_request_state(&thing_id, request_id)
+        let err_code = |res: ResultReturn<()>| match res {
+            Ok(()) => panic!("Subscription was unexpectedly accepted"),
+            Err(err) => format!("{:?}", err.code()),
+        };
+
+        let res = controller.subscribe_to_topic("test-device", "Alarmm", FilterOperator::Any, "");
+        assert_eq!(err_code(res), "TopicNotFound");
+        let res = controller.subscribe_to_attr("test-device", "name", FilterOperator::Any, "");
+        assert_eq!(err_code(res), "AttrNotDynamic");
+        let res = controller.subscribe_to_attr("test-device", "lvl", FilterOperator::Any, "");
+        assert_eq!(err_code(res), "AttrNotFound");
+        let res =
+            controller.subscribe_to_topic("test-unpublished", "Alarm", FilterOperator::Any, "");
+        assert_eq!(err_code(res), "ThingNotPublished");
+        assert!(controller.subscriptions().is_empty());
+
+        controller
+            .subscribe_to_attr("test-device", "level", FilterOperator::Any, "")
+            .unwrap();
+        assert_eq!(controller.subscriptions().len(), 1);
+    }
+
+    #[test]
+    fn subscriptions_restored_after_restart() {
+        let network = "test_subscriptions_restored";
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let (device, _, _) = new_thing("test-device", network, &[], &topics, &[]);
+        let (controller, notif_rx, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        controller
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        device.notify("Alarm", "first").unwrap();
+        assert_eq!(recv(&notif_rx).2, "first");
+
+        // Give some time to the monitoring thread to store the last report made
+        thread::sleep(Duration::from_millis(1_000));
+        drop(controller);
+        let (_restarted, restarted_rx, _) = new_thing("test-controller", network, &[], &[], &[]);
+        device.notify("Alarm", "second").unwrap();
+
+        // The already reported event is not notified again after the restart
+        assert_eq!(recv(&restarted_rx).2, "second");
+    }
+
+    #[test]
+    fn legacy_and_invalid_actions() {
+        let network = "test_legacy_actions";
+        let (device, _, _) = new_thing("test-device", network, &[], &[], &[]);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        // actions registered before the parameters had types
+        device
+            .safe_thing_comm
+            .set_actions(r#"[{"name":"Open","access":"All","params":["psi"]}]"#)
+            .unwrap();
+        let actions = controller.get_thing_actions("test-device").unwrap();
+        assert_eq!(
+            actions[0].params,
+            vec![ActionParam::new("psi", ValueType::String, None)]
+        );
+
+        device.safe_thing_comm.set_actions("not json").unwrap();
+        match controller
+            .get_thing_actions("test-device")
+            .unwrap_err()
+            .code()
+        {
+            ErrorCode::InvalidArgument => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+    }
+
+    #[test]
+    fn invalid_subscriptions_stored() {
+        let network = "test_invalid_subscriptions";
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        controller
+            .safe_thing_comm
+            .set_subscriptions("not json")
+            .unwrap();
+        drop(controller);
+
+        // the restarted SAFEthing fails to register rather than panicking
+        let restarted = SAFEthing::<MemStore>::with_store(
+            "test-controller",
+            network,
+            |_: &SAFEthing<MemStore>, _: &str, _: &str, _: &str, _: Timestamp| {},
+            |_: &SAFEthing<MemStore>, _: ActionReqId, _: &str, _: &str, _: &[ActionValue]| Ok(None),
+        )
+        .unwrap();
+        match restarted.register(&[], &[], &[]).unwrap_err().code() {
+            ErrorCode::InvalidArgument => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+
+        // nor it loses the subscriptions stored if they cannot be retrieved
+        restarted.safe_thing_comm.sim_net_disconnect();
+        assert!(restarted.safe_thing_comm.get_subscriptions().is_err());
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let network = "test_unsubscribe";
+        let attrs = [ThingAttr::new("level", "6.5", true)];
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let (device, _, _) = new_thing("test-device", network, &attrs, &topics, &[]);
+        let (controller, notif_rx, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        controller
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        controller
+            .subscribe_to_attr("test-device", "level", FilterOperator::LessThan, "5.0")
+            .unwrap();
+        let subscriptions = controller.subscriptions();
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(subscriptions[0].name, "Alarm");
+        assert!(!subscriptions[0].is_attr);
+        assert_eq!(subscriptions[1].name, "level");
+        assert!(subscriptions[1].is_attr);
+
+        controller.unsubscribe("test-device", "Alarm").unwrap();
+        assert!(controller.unsubscribe("test-device", "Alarm").is_err());
+        let subscriptions = controller.subscriptions();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].name, "level");
+
+        controller.unsubscribe_all("test-device").unwrap();
+        assert!(controller.subscriptions().is_empty());
+        assert!(controller.unsubscribe_all("test-device").is_err());
+        assert_eq!(
+            controller.safe_thing_comm.get_subscriptions().unwrap(),
+            "{}"
+        );
+
+        device.notify("Alarm", "on").unwrap();
+        device.set_attr_value("level", "4.5").unwrap();
+        match notif_rx.recv_timeout(Duration::from_millis(SUBSCRIPTIONS_CHECK_FREQ * 2)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected notification: {:?}", other),
+        };
+    }
+
+    #[test]
+    fn action_request() {
+        let network = "test_action_request";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (_device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        let (state_tx, state_rx) = mpsc::channel();
+        let state_tx = Mutex::new(state_tx);
+        let state_cb = move |state: &str, _: Result<Option<&ActionValue>, &str>| {
+            state_tx.lock().unwrap().send(state.to_string()).unwrap();
+            true
+        };
+        controller
+            .action_request("test-device", "Open", &["60"], state_cb)
+            .unwrap();
+
+        let req = recv(&action_rx);
+        assert_eq!(
+            req,
+            (
+                "test-controller".to_string(),
+                "Open".to_string(),
+                vec![ActionValue::Text("60".to_string())]
+            )
+        );
+        assert_eq!(recv(&state_rx), ACTION_REQUEST_DONE_STATE);
+    }
+
+    #[test]
+    fn action_request_rejected() {
+        let network = "test_action_request_rejected";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (_device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        let (state_tx, state_rx) = mpsc::channel();
+        let state_tx = Arc::new(Mutex::new(state_tx));
+        let state_cb = move |state: &str, _: Result<Option<&ActionValue>, &str>| {
+            state_tx.lock().unwrap().send(state.to_string()).unwrap();
+            true
+        };
+
+        // unknown action
+        let req_id = controller
+            .action_request("test-device", "Close", &[], state_cb.clone())
+            .unwrap();
+        assert_eq!(recv(&state_rx), ACTION_REQUEST_REJECTED_STATE);
+        let action_req_str = controller
+            .safe_thing_comm
+            .get_thing_action_request_state("test-device", req_id)
+            .unwrap();
+        let action_req: ActionReq = serde_json::from_str(&action_req_str).unwrap();
+        assert_eq!(
+            action_req.error,
+            Some("Action 'Close' is not supported".to_string())
+        );
+
+        // wrong number of arguments
+        let req_id = controller
+            .action_request("test-device", "Open", &["60", "10"], state_cb)
+            .unwrap();
+        assert_eq!(recv(&state_rx), ACTION_REQUEST_REJECTED_STATE);
+        assert_eq!(
+            controller
+                .get_action_request_state("test-device", req_id)
+                .unwrap(),
+            ACTION_REQUEST_REJECTED_STATE
+        );
+
+        // the device callback is never invoked for rejected requests
+        match action_rx.recv_timeout(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected action request: {:?}", other),
+        };
+    }
+
+    #[test]
+    fn value_type_coercion() {
+        let number = ValueType::Number {
+            min: Some(0.0),
+            max: Some(10.0),
+        };
+        assert_eq!(number.coerce(" 7.5 "), Ok(ActionValue::Number(7.5)));
+        assert!(number.coerce("11").is_err());
+        assert!(number.coerce("-1").is_err());
+        assert!(number.coerce("seven").is_err());
+        assert!(number.coerce("NaN").is_err());
+
+        assert_eq!(ValueType::Bool.coerce("On"), Ok(ActionValue::Bool(true)));
+        assert_eq!(ValueType::Bool.coerce("0"), Ok(ActionValue::Bool(false)));
+        assert!(ValueType::Bool.coerce("maybe").is_err());
+
+        let colour = ValueType::Enum(vec!["red".to_string(), "green".to_string()]);
+        assert_eq!(
+            colour.coerce("red"),
+            Ok(ActionValue::Text("red".to_string()))
+        );
+        assert!(colour.coerce("blue").is_err());
+
+        assert_eq!(ActionValue::Number(60.0).to_string(), "60");
+        assert_eq!(
+            serde_json::to_string(&ActionValue::Bool(true)).unwrap(),
+            "true"
+        );
+
+        // invalid action definitions
+        let bad_default = ActionDef::with_types(
+            "Paint",
+            AccessType::All,
+            &[ActionParam::new("colour", colour.clone(), Some("blue"))],
+            None,
+        );
+        assert!(bad_default.validate().is_err());
+        let missing_default = ActionDef::with_types(
+            "Paint",
+            AccessType::All,
+            &[
+                ActionParam::new("colour", colour.clone(), Some("red")),
+                ActionParam::new("coats", number.clone(), None),
+            ],
+            None,
+        );
+        assert!(missing_default.validate().is_err());
+        let bad_range = ValueType::Number {
+            min: Some(1.0),
+            max: Some(0.0),
+        };
+        let bad_output = ActionDef::with_types("Paint", AccessType::All, &[], Some(bad_range));
+        assert!(bad_output.validate().is_err());
+    }
+
+    #[test]
+    fn typed_action_request() {
+        let network = "test_typed_action_request";
+        let steps = ValueType::Number {
+            min: Some(0.0),
+            max: Some(10.0),
+        };
+        let actions = [ActionDef::with_types(
+            "Move",
+            AccessType::All,
+            &[
+                ActionParam::new("steps", steps.clone(), None),
+                ActionParam::new("fast", ValueType::Bool, Some("false")),
+            ],
+            Some(steps),
+        )];
+
+        // the device moves twice the steps requested and reports it as the output
+        let (action_tx, action_rx) = mpsc::channel();
+        let action_tx = Mutex::new(action_tx);
+        let action_req_cb = move |_: &SAFEthing<MemStore>,
+                                  _: ActionReqId,
+                                  _: &str,
+                                  _: &str,
+                                  args: &[ActionValue]| {
+            action_tx.lock().unwrap().send(args.to_vec()).unwrap();
+            let moved = args[0].as_f64().unwrap() * 2.0;
+            Ok(Some(moved.to_string()))
+        };
+        let device =
+            SAFEthing::with_store("test-device", network, |_, _, _, _, _| {}, action_req_cb)
                 .unwrap();
-            match serde_json::from_str(&action_req_str) {
-                Ok(ActionReq {
-                    thing_id: _,
-                    action: _,
-                    args: _,
-                    state,
-                }) => {
-                    trace!(
-                        "Action request new state obtained, request id: {}, new state: {}",
-                        request_id,
-                        state
-                    );
-                    if state != current_state {
-                        current_state = state.clone();
-                        debug!(
-                        "Callback to notify action request new state, request id: {}, new state: {}",
-                        request_id, state);
-                        keep_checking = (cb)(state.as_str());
-                        debug!("Keep checking sent action request state? {}", keep_checking);
-                    }
+        device.register(&[], &[], &actions).unwrap();
+        device.publish().unwrap();
+
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        let (state_tx, state_rx) = mpsc::channel();
+        let state_tx = Arc::new(Mutex::new(state_tx));
+        let state_cb = move |state: &str, outcome: Result<Option<&ActionValue>, &str>| {
+            let outcome = outcome.map(|r| r.cloned()).map_err(|e| e.to_string());
+            let update = (state.to_string(), outcome);
+            state_tx.lock().unwrap().send(update).unwrap();
+            true
+        };
+
+        // the default value is used for the missing argument
+        controller
+            .action_request("test-device", "Move", &["4"], state_cb.clone())
+            .unwrap();
+        assert_eq!(
+            recv(&action_rx),
+            vec![ActionValue::Number(4.0), ActionValue::Bool(false)]
+        );
+        assert_eq!(
+            recv(&state_rx),
+            (
+                ACTION_REQUEST_DONE_STATE.to_string(),
+                Ok(Some(ActionValue::Number(8.0)))
+            )
+        );
+
+        // an argument out of range is rejected
+        controller
+            .action_request("test-device", "Move", &["11", "yes"], state_cb.clone())
+            .unwrap();
+        let (state, outcome) = recv(&state_rx);
+        assert_eq!(state, ACTION_REQUEST_REJECTED_STATE);
+        assert!(outcome.unwrap_err().starts_with("Invalid argument 'steps'"));
+        match action_rx.recv_timeout(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected action request: {:?}", other),
+        };
+
+        // the output is validated against its type
+        controller
+            .action_request("test-device", "Move", &["6"], state_cb)
+            .unwrap();
+        let _ = recv(&action_rx);
+        let (state, outcome) = recv(&state_rx);
+        assert_eq!(state, ACTION_REQUEST_FAILED_STATE);
+        assert!(outcome
+            .unwrap_err()
+            .starts_with("Invalid output of action 'Move'"));
+    }
+
+    #[test]
+    fn action_request_outcome() {
+        let network = "test_action_request_outcome";
+        let actions = [
+            ActionDef::new("Print", AccessType::All, &[]),
+            ActionDef::new("Scan", AccessType::All, &[]),
+        ];
+
+        // the device reports its progress while printing, and it cannot scan
+        let action_req_cb = |thing: &SAFEthing<MemStore>,
+                             request_id: ActionReqId,
+                             _: &str,
+                             action: &str,
+                             _: &[ActionValue]| {
+            match action {
+                "Print" => {
+                    thing
+                        .update_action_request_state(request_id, "Printing")
+                        .unwrap();
+                    Ok(None)
                 }
-                Err(_) => {
-                    error!(
-                        "Actin request ({}) current state couldn't be read",
-                        request_id
-                    );
+                _ => Err("Scanner not available".to_string()),
+            }
+        };
+        let device =
+            SAFEthing::with_store("test-device", network, |_, _, _, _, _| {}, action_req_cb)
+                .unwrap();
+        device.register(&[], &[], &actions).unwrap();
+        device.publish().unwrap();
+
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        let (state_tx, state_rx) = mpsc::channel();
+        let state_tx = Arc::new(Mutex::new(state_tx));
+        let state_cb = move |state: &str, outcome: Result<Option<&ActionValue>, &str>| {
+            let outcome = outcome.map(|r| r.cloned()).map_err(|e| e.to_string());
+            state_tx
+                .lock()
+                .unwrap()
+                .send((state.to_string(), outcome))
+                .unwrap();
+            true
+        };
+
+        // updating the state keeps the rest of the request intact
+        let req_id = controller
+            .action_request("test-device", "Print", &[], state_cb.clone())
+            .unwrap();
+        assert_eq!(
+            recv(&state_rx),
+            (ACTION_REQUEST_DONE_STATE.to_string(), Ok(None))
+        );
+        let action_req = device.read_action_req(req_id).unwrap();
+        assert_eq!(action_req.action, "Print");
+        assert_eq!(action_req.thing_id, "test-controller");
+
+        // the error returned by the device is sent back to the requester
+        controller
+            .action_request("test-device", "Scan", &[], state_cb)
+            .unwrap();
+        assert_eq!(
+            recv(&state_rx),
+            (
+                ACTION_REQUEST_FAILED_STATE.to_string(),
+                Err("Scanner not available".to_string())
+            )
+        );
+
+        assert!(device.update_action_request_state(12345, "Done").is_err());
+    }
+
+    #[test]
+    fn thing_events() {
+        let network = "test_thing_events";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (device, events) =
+            SAFEthing::<MemStore>::with_store_events("test-device", network).unwrap();
+        device.register(&[], &[], &actions).unwrap();
+        device.publish().unwrap();
+        let topics = [Topic::new("alarm", AccessType::All)];
+        let (controller, _, _) = new_thing("test-controller", network, &[], &topics, &[]);
+
+        device
+            .subscribe_to_topic("test-controller", "alarm", FilterOperator::Any, "")
+            .unwrap();
+        controller.notify("alarm", "fire").unwrap();
+        match events.recv_timeout(Duration::from_millis(NOTIF_TIMEOUT)) {
+            Ok(ThingEvent::Notification {
+                thing_id,
+                topic,
+                data,
+                ..
+            }) => assert_eq!(
+                (thing_id, topic, data),
+                (
+                    "test-controller".to_string(),
+                    "alarm".to_string(),
+                    "fire".to_string()
+                )
+            ),
+            other => panic!("Unexpected event: {:?}", other),
+        };
+
+        // the outcome is the one replied from the events receiver
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap();
+        match events.recv_timeout(Duration::from_millis(NOTIF_TIMEOUT)) {
+            Ok(ThingEvent::ActionRequest {
+                request_id,
+                action,
+                args,
+                reply,
+                ..
+            }) => {
+                assert_eq!(request_id, handle.request_id);
+                assert_eq!(action, "Open");
+                assert_eq!(args, vec![ActionValue::Text("60".to_string())]);
+                reply.send(Err("Valve stuck".to_string()));
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        };
+        let req_state = handle.wait(Duration::from_millis(NOTIF_TIMEOUT)).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_FAILED_STATE);
+        assert_eq!(req_state.outcome(), Err("Valve stuck"));
+
+        // the request fails if it's not replied
+        let handle = controller
+            .send_action_request("test-device", "Open", &["10"])
+            .unwrap();
+        match events.recv_timeout(Duration::from_millis(NOTIF_TIMEOUT)) {
+            Ok(ThingEvent::ActionRequest { reply, .. }) => drop(reply),
+            other => panic!("Unexpected event: {:?}", other),
+        };
+        let req_state = handle.wait(Duration::from_millis(NOTIF_TIMEOUT)).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_FAILED_STATE);
+    }
+
+    #[test]
+    fn shared_connection() {
+        let network = "test_shared_connection";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        // the background workers use the same connection as the device
+        device.simulate_net_disconnect();
+        assert!(device.status().is_err());
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap();
+        let res = handle.wait(Duration::from_millis(2 * ACTION_REQUEST_CHECK_FREQ));
+        assert!(res.is_err());
+        match action_rx.recv_timeout(Duration::from_millis(0)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected action request: {:?}", other),
+        };
+    }
+
+    #[test]
+    fn shutdown() {
+        let network = "test_shutdown";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+
+        // registering again doesn't spawn other workers to handle the requests
+        device.register(&[], &[], &actions).unwrap();
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap();
+        let _ = recv(&action_rx);
+        assert_eq!(
+            handle.wait(timeout).unwrap().state,
+            ACTION_REQUEST_DONE_STATE
+        );
+        match action_rx.recv_timeout(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected action request: {:?}", other),
+        };
+
+        // the workers are stopped without waiting for their next check
+        let start = Instant::now();
+        device.shutdown(true).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(ACTION_REQUEST_CHECK_FREQ));
+        match device.status().unwrap() {
+            Status::Disabled => (),
+            _ => panic!("Unexpected status after shutdown"),
+        };
+        let handle = controller
+            .send_action_request("test-device", "Open", &["10"])
+            .unwrap();
+        let res = handle.wait(Duration::from_millis(2 * ACTION_REQUEST_CHECK_FREQ));
+        assert!(res.is_err());
+
+        // the pending request is handled once it's registered again
+        device.register(&[], &[], &actions).unwrap();
+        assert_eq!(
+            handle.wait(timeout).unwrap().state,
+            ACTION_REQUEST_DONE_STATE
+        );
+    }
+
+    #[test]
+    fn register_on_clone() {
+        let network = "test_register_on_clone";
+        let actions = [ActionDef::new("Reload", AccessType::All, &[])];
+        let (reload_tx, reload_rx) = mpsc::channel();
+        let reload_tx = Mutex::new(reload_tx);
+        let action_req_cb = move |safe_thing: &SAFEthing<MemStore>,
+                                  _: ActionReqId,
+                                  _: &str,
+                                  _: &str,
+                                  _: &[ActionValue]| {
+            let actions = [ActionDef::new("Reload", AccessType::All, &[])];
+            let res = safe_thing.register(&[], &[], &actions);
+            let _ = reload_tx.lock().unwrap().send(res.is_ok());
+            Ok(None)
+        };
+        let device = SAFEthing::with_store(
+            "test-device",
+            network,
+            |_: &SAFEthing<MemStore>, _: &str, _: &str, _: &str, _: Timestamp| {},
+            action_req_cb,
+        )
+        .unwrap();
+        device.register(&[], &[], &actions).unwrap();
+        device.publish().unwrap();
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+
+        // neither registering a clone nor registering from the callbacks spawns other workers
+        let clone = device.clone();
+        clone.register(&[], &[], &actions).unwrap();
+        drop(clone);
+        assert_eq!(device.workers.lock().unwrap().len(), 2);
+        let handle = controller
+            .send_action_request("test-device", "Reload", &[])
+            .unwrap();
+        assert!(recv(&reload_rx));
+        assert_eq!(
+            handle.wait(timeout).unwrap().state,
+            ACTION_REQUEST_DONE_STATE
+        );
+        assert_eq!(device.workers.lock().unwrap().len(), 2);
+
+        // the workers kept running after dropping the clone, and any clone can stop them
+        let handle = controller
+            .send_action_request("test-device", "Reload", &[])
+            .unwrap();
+        assert!(recv(&reload_rx));
+        device.clone().shutdown(false).unwrap();
+        assert!(device.workers.lock().unwrap().is_empty());
+        assert_eq!(
+            handle.wait(timeout).unwrap().state,
+            ACTION_REQUEST_DONE_STATE
+        );
+    }
+
+    #[test]
+    fn disable_enable_unregister() {
+        let network = "test_disable_enable";
+        let attrs = [ThingAttr::new("name", "Test Device", false)];
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (device, _, action_rx) = new_thing("test-device", network, &attrs, &topics, &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+
+        device.disable().unwrap();
+        match controller.get_thing_status("test-device").unwrap() {
+            Status::Disabled => (),
+            _ => panic!("Unexpected status after disabling"),
+        };
+        assert!(device.notify("Alarm", "on").is_err());
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap();
+        let req_state = handle.wait(timeout).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_REJECTED_STATE);
+        assert!(req_state.error.unwrap().contains("disabled"));
+
+        device.enable().unwrap();
+        match device.status().unwrap() {
+            Status::Published => (),
+            _ => panic!("Unexpected status after enabling"),
+        };
+        // enabling it again doesn't change its status
+        device.enable().unwrap();
+        match device.status().unwrap() {
+            Status::Published => (),
+            _ => panic!("Unexpected status after enabling twice"),
+        };
+        device.notify("Alarm", "on").unwrap();
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap();
+        assert_eq!(
+            handle.wait(timeout).unwrap().state,
+            ACTION_REQUEST_DONE_STATE
+        );
+        assert_eq!(recv(&action_rx).1, "Open");
+
+        device.unregister().unwrap();
+        assert!(controller
+            .get_thing_attrs("test-device")
+            .unwrap()
+            .is_empty());
+        assert!(controller
+            .get_thing_topics("test-device")
+            .unwrap()
+            .is_empty());
+        assert!(controller
+            .get_thing_actions("test-device")
+            .unwrap()
+            .is_empty());
+        match device.status().unwrap() {
+            Status::Disabled => (),
+            _ => panic!("Unexpected status after unregistering"),
+        };
+
+        // a SAFEthing which was only registered is not published when enabled
+        device.register(&attrs, &topics, &actions).unwrap();
+        device.disable().unwrap();
+        device.enable().unwrap();
+        match device.status().unwrap() {
+            Status::Connected => (),
+            _ => panic!("Unexpected status after enabling a registered thing"),
+        };
+    }
+
+    #[test]
+    fn access_type() {
+        let network = "test_access_type";
+        let topics = [
+            Topic::new("Public", AccessType::All),
+            Topic::new("Alarm", AccessType::Group),
+        ];
+        let actions = [
+            ActionDef::new("Status", AccessType::All, &[]),
+            ActionDef::new("Light", AccessType::Group, &[]),
+            ActionDef::new("Open", AccessType::Owner, &[]),
+            ActionDef::new("Reset", AccessType::Thing, &[]),
+        ];
+        let attrs = [ThingAttr::new("Location", "home", true).with_access(AccessType::Owner)];
+        let (device, _, _) = new_thing("test-device", network, &attrs, &topics, &actions);
+        let (owner, _, _) = new_thing("test-owner", network, &[], &[], &[]);
+        let (member, _, _) = new_thing("test-member", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+
+        assert!(device.authorise(owner.sign_key(), AccessType::All).is_err());
+        device
+            .authorise(owner.sign_key(), AccessType::Owner)
+            .unwrap();
+        device
+            .authorise(member.sign_key(), AccessType::Group)
+            .unwrap();
+        assert_eq!(device.authorised_keys().unwrap().len(), 2);
+
+        stranger
+            .subscribe_to_topic("test-device", "Public", FilterOperator::Any, "")
+            .unwrap();
+        let res = stranger.subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "");
+        match res.unwrap_err().code() {
+            ErrorCode::AccessDenied => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+        member
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        owner
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        let res = member.subscribe_to_attr("test-device", "Location", FilterOperator::Any, "");
+        match res.unwrap_err().code() {
+            ErrorCode::AccessDenied => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+        owner
+            .subscribe_to_attr("test-device", "Location", FilterOperator::Any, "")
+            .unwrap();
+
+        let requests = [
+            (&stranger, "Status", ACTION_REQUEST_DONE_STATE),
+            (&stranger, "Light", ACTION_REQUEST_REJECTED_STATE),
+            (&member, "Light", ACTION_REQUEST_DONE_STATE),
+            (&member, "Open", ACTION_REQUEST_REJECTED_STATE),
+            (&owner, "Light", ACTION_REQUEST_DONE_STATE),
+            (&owner, "Open", ACTION_REQUEST_DONE_STATE),
+            (&owner, "Reset", ACTION_REQUEST_REJECTED_STATE),
+            (&device, "Reset", ACTION_REQUEST_DONE_STATE),
+        ];
+        let handles: Vec<_> = requests
+            .iter()
+            .map(|(requester, action, _)| {
+                requester
+                    .send_action_request("test-device", action, &[])
+                    .unwrap()
+            })
+            .collect();
+        for (handle, (_, action, state)) in handles.iter().zip(requests.iter()) {
+            let req_state = handle.wait(timeout).unwrap();
+            assert_eq!(req_state.state, *state, "Unexpected state of {}", action);
+        }
+
+        device.revoke(owner.sign_key()).unwrap();
+        assert!(device.revoke(owner.sign_key()).is_err());
+        let handle = owner
+            .send_action_request("test-device", "Open", &[])
+            .unwrap();
+        let req_state = handle.wait(timeout).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_REJECTED_STATE);
+    }
+
+    #[test]
+    fn sealed_topic_events() {
+        let network = "test_sealed_topic_events";
+        let topics = [Topic::new("Alarm", AccessType::Group)];
+        let (device, _, _) = new_thing("test-device", network, &[], &topics, &[]);
+        let (member, notif_rx, _) = new_thing("test-member", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        let sealed_events = || {
+            let events = device
+                .safe_thing_comm
+                .get_sealed_topic_events("Alarm", member.sign_key());
+            serde_json::from_str::<Vec<(Timestamp, String)>>(&events).unwrap()
+        };
+
+        device
+            .authorise(member.sign_key(), AccessType::Group)
+            .unwrap();
+        member
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        device.notify("Alarm", "on").unwrap();
+        let notif = recv(&notif_rx);
+        assert_eq!(notif.2, "on");
+
+        // the events are neither published in plain nor can be opened by anyone else
+        let plain_events = stranger
+            .safe_thing_comm
+            .get_thing_topic_events("test-device", "Alarm")
+            .unwrap();
+        assert_eq!(plain_events, "[]");
+        let events = sealed_events();
+        assert_eq!(events.len(), 1);
+        assert!(stranger
+            .safe_thing_comm
+            .decrypt_sealed(&events[0].1)
+            .is_err());
+        assert!(device.safe_thing_comm.decrypt_sealed(&events[0].1).is_err());
+
+        // no more events are sealed for it once it's revoked or it unsubscribes
+        device.revoke(member.sign_key()).unwrap();
+        device.notify("Alarm", "off").unwrap();
+        assert_eq!(sealed_events().len(), 1);
+        match notif_rx.recv_timeout(Duration::from_millis(SUBSCRIPTIONS_CHECK_FREQ * 2)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected notification: {:?}", other),
+        };
+        device
+            .authorise(member.sign_key(), AccessType::Group)
+            .unwrap();
+        member.unsubscribe("test-device", "Alarm").unwrap();
+        device.notify("Alarm", "on").unwrap();
+        assert_eq!(sealed_events().len(), 1);
+    }
+
+    #[test]
+    fn signed_action_request() {
+        let network = "test_signed_action_request";
+        let actions = [ActionDef::new("Open", AccessType::Owner, &[])];
+        let (device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (owner, _, _) = new_thing("test-owner", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+        device
+            .authorise(owner.sign_key(), AccessType::Owner)
+            .unwrap();
+
+        // the callback receives the ID of the SAFEthing which signed the request
+        let handle = owner
+            .send_action_request("test-device", "Open", &[])
+            .unwrap();
+        let req_state = handle.wait(timeout).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_DONE_STATE);
+        assert_eq!(recv(&action_rx).0, "test-owner");
+
+        // the stranger writes requests directly into the device's MutableData
+        let comm = &stranger.safe_thing_comm;
+        let forge = |action_req: &ActionReq| {
+            let req_id = comm.gen_request_id();
+            let action_req_str = serde_json::to_string(action_req).unwrap();
+            comm.send_action_request("test-device", req_id, &action_req_str)
+                .unwrap();
+            stranger.action_request_handle("test-device", req_id)
+        };
+        let owner_req_str = comm
+            .get_thing_action_request_state("test-device", handle.request_id)
+            .unwrap();
+        let mut owner_req: ActionReq = serde_json::from_str(&owner_req_str).unwrap();
+        owner_req.state = ACTION_REQUEST_INIT_STATE.to_string();
+
+        // replaying the owner's signed request
+        let replayed = forge(&owner_req);
+        // impersonating the owner with the owner's key but its own signature
+        let mut impersonated = owner_req.clone();
+        impersonated.signature = comm
+            .sign(&action_req_signed_data("test-device", 0, &impersonated))
+            .unwrap();
+        let impersonated = forge(&impersonated);
+        // claiming to be the owner while signing with its own key
+        let req_id = comm.gen_request_id();
+        let mut own_key = owner_req.clone();
+        own_key.requester_key = stranger.sign_key().to_string();
+        own_key.signature = comm
+            .sign(&action_req_signed_data("test-device", req_id, &own_key))
+            .unwrap();
+        let action_req_str = serde_json::to_string(&own_key).unwrap();
+        comm.send_action_request("test-device", req_id, &action_req_str)
+            .unwrap();
+        let own_key = stranger.action_request_handle("test-device", req_id);
+
+        let errors = [
+            (replayed, "Invalid signature of the action request"),
+            (impersonated, "Invalid signature of the action request"),
+            (
+                own_key,
+                "Action request not signed with the key published by test-owner",
+            ),
+        ];
+        for (handle, error) in errors.iter() {
+            let req_state = handle.wait(timeout).unwrap();
+            assert_eq!(req_state.state, ACTION_REQUEST_REJECTED_STATE);
+            assert_eq!(req_state.error, Some(error.to_string()));
+        }
+
+        // the device callback is never invoked for the forged requests
+        match action_rx.recv_timeout(Duration::from_millis(ACTION_REQUEST_CHECK_FREQ)) {
+            Err(RecvTimeoutError::Timeout) => (),
+            other => panic!("Unexpected action request: {:?}", other),
+        };
+    }
+
+    #[test]
+    fn claim() {
+        let network = "test_claim";
+        let (device, _, _) = new_thing("test-device", network, &[], &[], &[]);
+        let (unclaimable, _, _) = new_thing("test-unclaimable", network, &[], &[], &[]);
+        let (owner, _, _) = new_thing("test-owner", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        let assert_denied = |res: ResultReturn<()>| match res.unwrap_err().code() {
+            ErrorCode::AccessDenied => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+
+        device.set_claim_code("123456");
+        assert_eq!(device.owner().unwrap(), None);
+        assert_denied(stranger.claim("test-device", "654321"));
+        assert_denied(owner.claim("test-unclaimable", "123456"));
+
+        // the stranger writes claim requests directly into the device's MutableData
+        let comm = &stranger.safe_thing_comm;
+        let pub_enc_key = comm.get_thing_pub_enc_key("test-device").unwrap();
+        let forge = |requester_key: &str, proof_key: &str| {
+            let req_id = comm.gen_request_id();
+            let mut claim_req = ClaimReq {
+                thing_id: "test-stranger".to_string(),
+                requester_key: requester_key.to_string(),
+                proof: comm
+                    .encrypt_sealed(&pub_enc_key, &claim_proof_data("123456", proof_key))
+                    .unwrap(),
+                signature: String::new(),
+                state: ACTION_REQUEST_INIT_STATE.to_string(),
+                error: None,
+            };
+            claim_req.signature = comm
+                .sign(&claim_req_signed_data("test-device", req_id, &claim_req))
+                .unwrap();
+            let claim_req_str = serde_json::to_string(&claim_req).unwrap();
+            comm.send_claim_request("test-device", req_id, &claim_req_str)
+                .unwrap();
+            req_id
+        };
+        let errors = [
+            // reusing a code sealed along with the owner's key
+            (
+                forge(stranger.sign_key(), owner.sign_key()),
+                "Invalid claim code",
+            ),
+            // claiming on behalf of the owner's key while signing with its own key
+            (
+                forge(owner.sign_key(), owner.sign_key()),
+                "Invalid signature of the claim request",
+            ),
+        ];
+        for (req_id, error) in errors.iter() {
+            let start = Instant::now();
+            let claim_req = loop {
+                let claim_req_str = comm
+                    .get_thing_claim_request_state("test-device", *req_id)
+                    .unwrap();
+                let claim_req: ClaimReq = serde_json::from_str(&claim_req_str).unwrap();
+                if claim_req.state != ACTION_REQUEST_INIT_STATE
+                    || start.elapsed() > Duration::from_millis(NOTIF_TIMEOUT)
+                {
+                    break claim_req;
                 }
+                thread::sleep(Duration::from_millis(PEER_REQUEST_CHECK_FREQ));
             };
+            assert_eq!(claim_req.state, ACTION_REQUEST_REJECTED_STATE);
+            assert_eq!(claim_req.error, Some(error.to_string()));
+        }
+        assert_eq!(device.owner().unwrap(), None);
 
-            trace!("CHECKED ACTION REQUEST STATE....WAIT FOR NEXT LOOP");
-            thread::sleep(Duration::from_millis(ACTION_REQUEST_MONITORING_FREQ));
-            timeout = match start_timestamp.elapsed() {
-                Ok(elapsed) => elapsed > Duration::from_millis(ACTION_REQUEST_MONITORING_TIMEOUT),
-                Err(_) => false,
-            };
+        owner.claim("test-device", "123456").unwrap();
+        assert_eq!(device.owner().unwrap(), Some(owner.sign_key().to_string()));
+        assert_eq!(
+            stranger.get_thing_owner("test-device").unwrap(),
+            Some(owner.sign_key().to_string())
+        );
+        assert_eq!(
+            device.authorised_keys().unwrap().get(owner.sign_key()),
+            Some(&AccessType::Owner)
+        );
 
-            if timeout {
-                warn!(
-                    "Monitoring thread for action request {} timed out",
-                    request_id
-                );
-            }
+        // the code can only be used once, even if it's set again after a restart
+        device.set_claim_code("123456");
+        assert_denied(stranger.claim("test-device", "123456"));
+        assert_eq!(unclaimable.owner().unwrap(), None);
+    }
+
+    #[test]
+    fn claim_rejections() {
+        let network = "test_claim_rejections";
+        let (device, _, _) = new_thing("test-device", network, &[], &[], &[]);
+        let (owner, _, _) = new_thing("test-owner", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+
+        device.set_claim_code("123456");
+        for _ in 0..MAX_CLAIM_REJECTIONS {
+            assert!(stranger.claim("test-device", "000000").is_err());
         }
+        // the code was discarded after guessing it too many times
+        assert!(owner.claim("test-device", "123456").is_err());
+        assert_eq!(device.owner().unwrap(), None);
 
-        debug!(
-            "Ending monitoring thread for sent action request: {}",
-            request_id
+        device.set_claim_code("123456");
+        owner.claim("test-device", "123456").unwrap();
+        assert_eq!(device.owner().unwrap(), Some(owner.sign_key().to_string()));
+    }
+
+    #[test]
+    fn groups() {
+        let network = "test_groups";
+        let topics = [
+            Topic::new("Alarm", AccessType::Group).for_groups(&["family"]),
+            Topic::new("Presence", AccessType::Group),
+        ];
+        let actions = [
+            ActionDef::new("Light", AccessType::Group, &[]).for_groups(&["family"]),
+            ActionDef::new("Open", AccessType::Group, &[]).for_groups(&["admins"]),
+        ];
+        let (device, _, _) = new_thing("test-device", network, &[], &topics, &actions);
+        let (member, _, _) = new_thing("test-member", network, &[], &[], &[]);
+        let (guest, _, _) = new_thing("test-guest", network, &[], &[], &[]);
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        let timeout = Duration::from_millis(NOTIF_TIMEOUT);
+
+        device.create_group("family").unwrap();
+        assert!(device.create_group("family").is_err());
+        device.add_group_member("family", "test-member").unwrap();
+        device.create_group("guests").unwrap();
+        device.add_group_member("guests", guest.sign_key()).unwrap();
+        assert!(device.add_group_member("admins", "test-member").is_err());
+        let groups = member.get_thing_groups("test-device").unwrap();
+        assert_eq!(groups.len(), 2);
+        assert!(groups["family"].contains("test-member"));
+
+        member
+            .subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "")
+            .unwrap();
+        let res = guest.subscribe_to_topic("test-device", "Alarm", FilterOperator::Any, "");
+        match res.unwrap_err().code() {
+            ErrorCode::AccessDenied => (),
+            code => panic!("Unexpected error code: {:?}", code),
+        };
+        guest
+            .subscribe_to_topic("test-device", "Presence", FilterOperator::Any, "")
+            .unwrap();
+        let res = stranger.subscribe_to_topic("test-device", "Presence", FilterOperator::Any, "");
+        assert!(res.is_err());
+
+        let requests = [
+            (&member, "Light", ACTION_REQUEST_DONE_STATE),
+            (&member, "Open", ACTION_REQUEST_REJECTED_STATE),
+            (&guest, "Light", ACTION_REQUEST_REJECTED_STATE),
+        ];
+        for (requester, action, state) in requests.iter() {
+            let handle = requester
+                .send_action_request("test-device", action, &[])
+                .unwrap();
+            let req_state = handle.wait(timeout).unwrap();
+            assert_eq!(req_state.state, *state, "Unexpected state of {}", action);
+        }
+
+        device.remove_group_member("family", "test-member").unwrap();
+        assert!(device.remove_group_member("family", "test-member").is_err());
+        let handle = member
+            .send_action_request("test-device", "Light", &[])
+            .unwrap();
+        let req_state = handle.wait(timeout).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_REJECTED_STATE);
+
+        device.delete_group("guests").unwrap();
+        assert!(device.delete_group("guests").is_err());
+        assert_eq!(device.groups().unwrap().len(), 1);
+
+        let other = SAFEthing::<MemStore>::with_store(
+            "test-other",
+            network,
+            |_, _, _, _, _| {},
+            |_, _, _, _, _| Ok(None),
+        )
+        .unwrap();
+        let topics = [Topic {
+            groups: vec!["family".to_string()],
+            ..Topic::new("Alarm", AccessType::All)
+        }];
+        assert!(other.register(&[], &topics, &[]).is_err());
+    }
+
+    #[test]
+    fn private_thing() {
+        let network = "test_private_thing";
+        let attrs = [ThingAttr::new("name", "Printer", false)];
+        let actions = [ActionDef::new("Print", AccessType::All, &["doc"])];
+        let printer = SAFEthing::<MemStore>::with_store(
+            "test-printer",
+            network,
+            |_, _, _, _, _| {},
+            |_, _, _, _, _| Ok(None),
+        )
+        .unwrap();
+        let enc_info = printer.gen_enc_info().unwrap();
+        printer
+            .register_private(&attrs, &[], &actions, &enc_info)
+            .unwrap();
+        printer.publish().unwrap();
+        assert_eq!(
+            printer.get_thing_enc_info("test-printer"),
+            Some(enc_info.clone())
         );
-    });
-}
 
-#[cfg(test)]
-mod tests {
+        let (stranger, _, _) = new_thing("test-stranger", network, &[], &[], &[]);
+        assert!(stranger.get_thing_attrs("test-printer").is_err());
+        // anyone can insert entries which cannot be decrypted, they are just skipped
+        let handle = stranger
+            .send_action_request("test-printer", "Print", &["doc"])
+            .unwrap();
+        assert!(handle.state().is_err());
+
+        // the encryption info is only sealed for the peers authorised
+        match stranger.fetch_thing_enc_info("test-printer") {
+            Err(ref err) => match err.code() {
+                ErrorCode::AccessDenied => (),
+                other => panic!("Unexpected error code: {:?}", other),
+            },
+            Ok(enc_info) => panic!("Unexpected encryption info: {}", enc_info),
+        };
+        assert_eq!(stranger.get_thing_enc_info("test-printer"), None);
+
+        let (sender, _, _) = new_thing("test-sender", network, &[], &[], &[]);
+        printer.create_group("office").unwrap();
+        printer.add_group_member("office", "test-sender").unwrap();
+        assert_eq!(
+            sender.fetch_thing_enc_info("test-printer").unwrap(),
+            enc_info
+        );
+        match sender.get_thing_status("test-printer").unwrap() {
+            Status::Published => (),
+            status => panic!("Unexpected status: {}", status),
+        };
+        let attrs = sender.get_thing_attrs("test-printer").unwrap();
+        assert_eq!(attrs[0].value, "Printer");
+
+        let handle = sender
+            .send_action_request("test-printer", "Print", &["doc"])
+            .unwrap();
+        let req_state = handle.wait(Duration::from_millis(NOTIF_TIMEOUT)).unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_DONE_STATE);
+    }
+
     #[test]
-    fn it_works() {}
+    fn action_request_wait() {
+        let network = "test_action_request_wait";
+        let actions = [ActionDef::new("Open", AccessType::All, &["psi"])];
+        let (_device, _, action_rx) = new_thing("test-device", network, &[], &[], &actions);
+        let (controller, _, _) = new_thing("test-controller", network, &[], &[], &[]);
+
+        // it times out if the request is not handled in time
+        let handle = controller
+            .send_action_request("test-device", "Open", &["60"])
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(100));
+        match handle.wait(Duration::from_millis(0)) {
+            Err(ref err) => match err.code() {
+                ErrorCode::Timeout => (),
+                other => panic!("Unexpected error code: {:?}", other),
+            },
+            Ok(req_state) => panic!("Unexpected state: {:?}", req_state),
+        };
+
+        let req_state = handle.wait(Duration::from_millis(NOTIF_TIMEOUT)).unwrap();
+        assert_eq!(
+            req_state,
+            ActionReqState {
+                state: ACTION_REQUEST_DONE_STATE.to_string(),
+                result: None,
+                error: None,
+            }
+        );
+        let _ = recv(&action_rx);
+
+        // the outcome of a rejected request is available through a new handle too
+        let req_id = controller
+            .send_action_request("test-device", "Close", &[])
+            .unwrap()
+            .request_id;
+        let req_state = controller
+            .action_request_handle("test-device", req_id)
+            .wait(Duration::from_millis(NOTIF_TIMEOUT))
+            .unwrap();
+        assert_eq!(req_state.state, ACTION_REQUEST_REJECTED_STATE);
+        assert_eq!(req_state.outcome(), Err("Action 'Close' is not supported"));
+    }
 }
@@ -16,9 +16,779 @@
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
 // WebService REST API
+//
+// It exposes the SAFEthing API on a RESTful interface for smart home devices.
+// Each SAFEthing registered through the web service is kept by the daemon, and
+// it's then used to interact with any other SAFEthing on the network:
+//
+// GET  /things
+//      list SAFEthings registered in this daemon
+// POST /things
+//      register a SAFEthing
+// POST /things/{id}/publish
+//      publish a SAFEthing
+// POST /things/{id}/disable
+//      disable a SAFEthing
+// POST /things/{id}/enable
+//      enable a disabled SAFEthing
+// DELETE /things/{id}
+//      unregister a SAFEthing
+// GET  /things/{id}/status
+//      get status of a SAFEthing
+// POST /things/{id}/action_requests/{req}/reply
+//      set the outcome of an action request received
+// PUT  /things/{id}/claim_code
+//      set the one-time code to claim a SAFEthing
+// PUT  /things/{id}/attributes/{attr}
+//      set the value of an attribute
+// POST /things/{id}/topics/{topic}/notify
+//      notify an event for a topic
+// GET  /things/{id}/peers/{peer}/status
+//      get status of another SAFEthing
+// POST /things/{id}/peers/{peer}/claim
+//      claim the ownership of another SAFEthing
+// GET  /things/{id}/peers/{peer}/owner
+//      get the key of the owner of another SAFEthing
+// PUT  /things/{id}/peers/{peer}/enc_info
+//      set the encryption info to access another private SAFEthing
+// GET  /things/{id}/peers/{peer}/enc_info
+//      get the encryption info known for a private SAFEthing
+// POST /things/{id}/peers/{peer}/enc_info/fetch
+//      request the encryption info of a private SAFEthing which authorised this one
+// GET  /things/{id}/peers/{peer}/attributes
+//      get attributes of another SAFEthing
+// GET  /things/{id}/peers/{peer}/topics
+//      get topics of another SAFEthing
+// GET  /things/{id}/peers/{peer}/actions
+//      get actions of another SAFEthing
+// POST /things/{id}/peers/{peer}/actions/{action}
+//      send an action request to another SAFEthing
+// GET  /things/{id}/peers/{peer}/action_requests/{req}
+//      get the state of an action request sent, along with its outcome
+// POST /things/{id}/peers/{peer}/topics/{topic}/subscribe
+//      subscribe to a topic of another SAFEthing
+// POST /things/{id}/peers/{peer}/attributes/{attr}/subscribe
+//      subscribe to a dynamic attribute of another SAFEthing
+// GET  /things/{id}/events
+//      open a WebSocket to receive events
+//
+// Every notification received from the subscriptions made by a SAFEthing, and every action
+// request received by it, is sent as a JSON text frame to all the WebSocket clients
+// connected to its events endpoint, e.g.:
+// {"event":"notification","thing_id":"...","topic":"...","data":"...","timestamp":"..."}
+// {"event":"action_request","request_id":"...","thing_id":"...","action":"...","args":[...]}
+//
+// The outcome of an action request is then set by any of the clients with its reply
+// endpoint, the request fails if it's not replied within a timeout, or right away if
+// no client is connected to receive it.
+
+use log::{debug, info};
+use rouille::{input::json_input, router, try_or_400, websocket, Request, Response};
+use safe_thing::{
+    ActionDef, ActionResult, ActionValue, Error, ErrorCode, FilterOperator, ResultReturn, SAFENet,
+    SAFEthing, ThingAttr, ThingStore, Topic,
+};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Address the web service listens on, unless a different one is provided as argument
+const DEFAULT_LISTEN_ADDR: &str = "localhost:41806";
+
+// Time to wait for the clients to reply to an action request received
+const ACTION_REPLY_TIMEOUT: u64 = 30_000;
+
+#[derive(Deserialize)]
+struct RegisterThingReq {
+    thing_id: String,
+    #[serde(default)]
+    auth_uri: String,
+    #[serde(default)]
+    attributes: Vec<ThingAttr>,
+    #[serde(default)]
+    topics: Vec<Topic>,
+    #[serde(default)]
+    actions: Vec<ActionDef>,
+    // The SAFEthing is registered as private if the encryption info is provided
+    enc_info: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClaimCodeReq {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct SetEncInfoReq {
+    enc_info: String,
+}
+
+#[derive(Deserialize)]
+struct SetAttrValueReq {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct NotifyReq {
+    #[serde(default)]
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct ActionReqReq {
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ActionReplyReq {
+    result: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SubscribeReq {
+    filter_op: Option<FilterOperator>,
+    #[serde(default)]
+    filter_value: String,
+}
+
+/// List of channels to send the events frames through, one for each WebSocket client connected
+type EventsListeners = Arc<Mutex<Vec<Sender<String>>>>;
+
+/// Channels to send the outcome of the action requests being handled through, by request ID
+type PendingReplies = Arc<Mutex<BTreeMap<u128, Sender<ActionResult>>>>;
+
+/// The SAFEthing API only needs shared references, thus the requests for the same
+/// SAFEthing are not serialised, e.g. a blocking `claim` doesn't hold the others
+struct ThingEntry<S: ThingStore> {
+    thing: SAFEthing<S>,
+    listeners: EventsListeners,
+    pending_replies: PendingReplies,
+}
+
+type SharedThing<S> = Arc<ThingEntry<S>>;
+
+/// The web service keeps the SAFEthing instances registered through it, by thing id.
+/// They are connected to the SAFE Network unless another backend is used.
+struct WebService<S: ThingStore = SAFENet> {
+    things: Mutex<BTreeMap<String, SharedThing<S>>>,
+}
+
+impl<S: ThingStore> Default for WebService<S> {
+    fn default() -> WebService<S> {
+        WebService {
+            things: Mutex::default(),
+        }
+    }
+}
+
+impl<S: ThingStore> WebService<S> {
+    fn handle(&self, request: &Request) -> Response {
+        debug!("Request received: {} {}", request.method(), request.url());
+        router!(request,
+            (GET) (/things) => {
+                let things = self.things.lock().unwrap();
+                Response::json(&things.keys().collect::<Vec<&String>>())
+            },
+            (POST) (/things) => {
+                self.register_thing(request)
+            },
+            (POST) (/things/{id: String}/publish) => {
+                self.with_thing(&id, |thing| thing.publish().map(|()| json!({})))
+            },
+            (POST) (/things/{id: String}/disable) => {
+                self.with_thing(&id, |thing| thing.disable().map(|()| json!({})))
+            },
+            (POST) (/things/{id: String}/enable) => {
+                self.with_thing(&id, |thing| thing.enable().map(|()| json!({})))
+            },
+            (DELETE) (/things/{id: String}) => {
+                self.unregister_thing(&id)
+            },
+            (GET) (/things/{id: String}/status) => {
+                self.with_thing(&id, |thing| {
+                    thing.status().map(|status| json!({ "status": status.to_string() }))
+                })
+            },
+            (POST) (/things/{id: String}/action_requests/{request_id: u128}/reply) => {
+                let req: ActionReplyReq = try_or_400!(json_input(request));
+                self.reply_action_request(&id, request_id, req)
+            },
+            (PUT) (/things/{id: String}/claim_code) => {
+                let req: ClaimCodeReq = try_or_400!(json_input(request));
+                self.with_thing(&id, |thing| {
+                    thing.set_claim_code(&req.code);
+                    Ok(json!({}))
+                })
+            },
+            (PUT) (/things/{id: String}/attributes/{attr: String}) => {
+                let req: SetAttrValueReq = try_or_400!(json_input(request));
+                self.with_thing(&id, |thing| {
+                    thing.set_attr_value(&attr, &req.value).map(|()| json!({}))
+                })
+            },
+            (POST) (/things/{id: String}/topics/{topic: String}/notify) => {
+                let req: NotifyReq = try_or_400!(json_input(request));
+                self.with_thing(&id, |thing| thing.notify(&topic, &req.data).map(|()| json!({})))
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/status) => {
+                self.with_thing(&id, |thing| {
+                    thing
+                        .get_thing_status(&peer)
+                        .map(|status| json!({ "status": status.to_string() }))
+                })
+            },
+            (POST) (/things/{id: String}/peers/{peer: String}/claim) => {
+                let req: ClaimCodeReq = try_or_400!(json_input(request));
+                self.with_thing(&id, |thing| thing.claim(&peer, &req.code).map(|()| json!({})))
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/owner) => {
+                self.with_thing(&id, |thing| {
+                    thing.get_thing_owner(&peer).map(|owner| json!({ "owner": owner }))
+                })
+            },
+            (PUT) (/things/{id: String}/peers/{peer: String}/enc_info) => {
+                let req: SetEncInfoReq = try_or_400!(json_input(request));
+                self.with_thing(&id, |thing| {
+                    thing.set_thing_enc_info(&peer, &req.enc_info);
+                    Ok(json!({}))
+                })
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/enc_info) => {
+                self.with_thing(&id, |thing| match thing.get_thing_enc_info(&peer) {
+                    Some(enc_info) => Ok(json!({ "enc_info": enc_info })),
+                    None => Err(Error::new(
+                        ErrorCode::NoSuchData,
+                        &format!("No encryption info known for SAFEthing: {}", peer),
+                    )),
+                })
+            },
+            (POST) (/things/{id: String}/peers/{peer: String}/enc_info/fetch) => {
+                self.with_thing(&id, |thing| {
+                    thing
+                        .fetch_thing_enc_info(&peer)
+                        .map(|enc_info| json!({ "enc_info": enc_info }))
+                })
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/attributes) => {
+                self.with_thing(&id, |thing| thing.get_thing_attrs(&peer).map(|attrs| json!(attrs)))
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/topics) => {
+                self.with_thing(&id, |thing| thing.get_thing_topics(&peer).map(|topics| json!(topics)))
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/actions) => {
+                self.with_thing(&id, |thing| {
+                    thing.get_thing_actions(&peer).map(|actions| json!(actions))
+                })
+            },
+            (POST) (/things/{id: String}/peers/{peer: String}/actions/{action: String}) => {
+                let req: ActionReqReq = try_or_400!(json_input(request));
+                let args: Vec<&str> = req.args.iter().map(|arg| arg.as_str()).collect();
+                // The state of the action request is polled by the client, thus
+                // we don't need to monitor it from here
+                self.with_thing(&id, |thing| {
+                    thing
+                        .send_action_request(&peer, &action, &args)
+                        .map(|handle| json!({ "request_id": handle.request_id.to_string() }))
+                })
+            },
+            (GET) (/things/{id: String}/peers/{peer: String}/action_requests/{request_id: u128}) => {
+                self.with_thing(&id, |thing| {
+                    thing.action_request_handle(&peer, request_id).state().map(|req_state| {
+                        json!({
+                            "state": req_state.state,
+                            "result": req_state.result,
+                            "error": req_state.error,
+                        })
+                    })
+                })
+            },
+            (POST) (/things/{id: String}/peers/{peer: String}/topics/{topic: String}/subscribe) => {
+                let SubscribeReq { filter_op, filter_value } = try_or_400!(json_input(request));
+                let filter_op = filter_op.unwrap_or(FilterOperator::Any);
+                self.with_thing(&id, |thing| {
+                    thing
+                        .subscribe_to_topic(&peer, &topic, filter_op, &filter_value)
+                        .map(|()| json!({}))
+                })
+            },
+            (POST) (/things/{id: String}/peers/{peer: String}/attributes/{attr: String}/subscribe) => {
+                let SubscribeReq { filter_op, filter_value } = try_or_400!(json_input(request));
+                let filter_op = filter_op.unwrap_or(FilterOperator::Any);
+                self.with_thing(&id, |thing| {
+                    thing
+                        .subscribe_to_attr(&peer, &attr, filter_op, &filter_value)
+                        .map(|()| json!({}))
+                })
+            },
+            (GET) (/things/{id: String}/events) => {
+                self.open_events_stream(&id, request)
+            },
+            _ => Response::empty_404()
+        )
+    }
+
+    fn register_thing(&self, request: &Request) -> Response {
+        let req: RegisterThingReq = try_or_400!(json_input(request));
+        if self.things.lock().unwrap().contains_key(&req.thing_id) {
+            return already_registered(&req.thing_id);
+        }
+
+        // The callbacks forward all events to the WebSocket clients. They need to live
+        // as long as the SAFEthing, which is kept by the web service until it's shut down.
+        let listeners = EventsListeners::default();
+        let notif_listeners = listeners.clone();
+        let notifs_cb =
+            move |_: &SAFEthing<S>, thing_id: &str, topic: &str, data: &str, timestamp: u128| {
+                let event = json!({
+                    "event": "notification",
+                    "thing_id": thing_id,
+                    "topic": topic,
+                    "data": data,
+                    "timestamp": timestamp.to_string(),
+                });
+                send_event(&notif_listeners, &event);
+            };
+        let action_req_listeners = listeners.clone();
+        let pending_replies = PendingReplies::default();
+        let action_req_replies = pending_replies.clone();
+        let action_req_cb = move |_: &SAFEthing<S>,
+                                  request_id: u128,
+                                  thing_id: &str,
+                                  action: &str,
+                                  args: &[ActionValue]| {
+            let event = json!({
+                "event": "action_request",
+                "request_id": request_id.to_string(),
+                "thing_id": thing_id,
+                "action": action,
+                "args": args,
+            });
+            // The action requests are handled by the WebSocket clients, thus we wait
+            // for any of them to reply with the outcome
+            let (reply_tx, reply_rx) = mpsc::channel();
+            let _ = action_req_replies
+                .lock()
+                .unwrap()
+                .insert(request_id, reply_tx);
+            let result = if send_event(&action_req_listeners, &event) == 0 {
+                Err("No client connected to handle the action request".to_string())
+            } else {
+                reply_rx
+                    .recv_timeout(Duration::from_millis(ACTION_REPLY_TIMEOUT))
+                    .unwrap_or_else(|_| Err("The action request was not replied".to_string()))
+            };
+            let _ = action_req_replies.lock().unwrap().remove(&request_id);
+            result
+        };
+
+        // The SAFEthing is connected and registered without holding the lock of the list,
+        // thus the requests for other SAFEthings are not blocked meanwhile
+        let registered = SAFEthing::with_store(
+            &req.thing_id,
+            &req.auth_uri,
+            notifs_cb,
+            action_req_cb,
+        )
+        .and_then(|thing| {
+            match req.enc_info {
+                Some(ref enc_info) => {
+                    thing.register_private(&req.attributes, &req.topics, &req.actions, enc_info)?
+                }
+                None => thing.register(&req.attributes, &req.topics, &req.actions)?,
+            };
+            Ok(thing)
+        });
+
+        match registered {
+            Ok(thing) => {
+                let mut things = self.things.lock().unwrap();
+                // It may have been registered by another request in the meantime
+                if things.contains_key(&req.thing_id) {
+                    return already_registered(&req.thing_id);
+                }
+                info!("SAFEthing registered through web service: {}", req.thing_id);
+                let entry = ThingEntry {
+                    thing,
+                    listeners,
+                    pending_replies,
+                };
+                let _ = things.insert(req.thing_id.clone(), Arc::new(entry));
+                Response::json(&json!({ "thing_id": req.thing_id })).with_status_code(201)
+            }
+            Err(err) => safe_thing_error_response(&err),
+        }
+    }
+
+    // Helper to unregister a SAFEthing and forget it once it succeeded
+    fn unregister_thing(&self, thing_id: &str) -> Response {
+        let response = self.with_thing(thing_id, |thing| thing.unregister().map(|()| json!({})));
+        // It's forgotten once its information was cleared from the network
+        if response.is_success() {
+            self.things.lock().unwrap().remove(thing_id);
+        }
+        response
+    }
+
+    // Helper to invoke a function on a SAFEthing registered in this web service,
+    // and convert its outcome into a response
+    fn with_thing<F>(&self, thing_id: &str, f: F) -> Response
+    where
+        F: FnOnce(&SAFEthing<S>) -> ResultReturn<Value>,
+    {
+        let entry = match self.things.lock().unwrap().get(thing_id) {
+            Some(entry) => entry.clone(),
+            None => return error_response(404, &format!("SAFEthing not registered: {}", thing_id)),
+        };
+
+        match f(&entry.thing) {
+            Ok(value) => Response::json(&value),
+            Err(err) => safe_thing_error_response(&err),
+        }
+    }
+
+    fn reply_action_request(
+        &self,
+        thing_id: &str,
+        request_id: u128,
+        req: ActionReplyReq,
+    ) -> Response {
+        let pending_replies = match self.things.lock().unwrap().get(thing_id) {
+            Some(entry) => entry.pending_replies.clone(),
+            None => return error_response(404, &format!("SAFEthing not registered: {}", thing_id)),
+        };
+
+        let reply_tx = pending_replies.lock().unwrap().remove(&request_id);
+        let outcome = match req.error {
+            Some(error) => Err(error),
+            None => Ok(req.result),
+        };
+        match reply_tx {
+            Some(reply_tx) if reply_tx.send(outcome).is_ok() => Response::json(&json!({})),
+            _ => error_response(
+                404,
+                &format!("Action request not being handled: {}", request_id),
+            ),
+        }
+    }
+
+    fn open_events_stream(&self, thing_id: &str, request: &Request) -> Response {
+        let listeners = match self.things.lock().unwrap().get(thing_id) {
+            Some(entry) => entry.listeners.clone(),
+            None => return error_response(404, &format!("SAFEthing not registered: {}", thing_id)),
+        };
+
+        let (response, websocket) = try_or_400!(websocket::start::<&str>(request, None));
+        let thing_id = thing_id.to_string();
+        thread::spawn(move || {
+            // The WebSocket is available only once the response was sent to the client
+            let mut websocket = match websocket.recv() {
+                Ok(websocket) => websocket,
+                Err(_) => return,
+            };
+
+            let (tx, rx) = mpsc::channel();
+            listeners.lock().unwrap().push(tx);
+            debug!("Events stream opened for SAFEthing: {}", thing_id);
+            for frame in rx.iter() {
+                if websocket.send_text(&frame).is_err() {
+                    break;
+                }
+            }
+            debug!("Events stream closed for SAFEthing: {}", thing_id);
+        });
+
+        response
+    }
+}
+
+// Send an event to all the WebSocket clients, dropping those which are not connected anymore,
+// and return the number of clients it was sent to
+fn send_event(listeners: &EventsListeners, event: &Value) -> usize {
+    let frame = event.to_string();
+    let mut listeners = listeners.lock().unwrap();
+    listeners.retain(|tx| tx.send(frame.clone()).is_ok());
+    listeners.len()
+}
+
+fn already_registered(thing_id: &str) -> Response {
+    error_response(409, &format!("SAFEthing already registered: {}", thing_id))
+}
+
+fn error_response(status_code: u16, msg: &str) -> Response {
+    Response::json(&json!({ "error": msg })).with_status_code(status_code)
+}
+
+fn safe_thing_error_response(err: &Error) -> Response {
+    let status_code = match err.code() {
+        ErrorCode::InvalidArgument | ErrorCode::AttrNotDynamic => 400,
+        ErrorCode::TopicNotFound | ErrorCode::AttrNotFound | ErrorCode::NoSuchData => 404,
+        ErrorCode::AccessDenied => 403,
+        ErrorCode::ThingNotPublished => 409,
+        ErrorCode::ConnectionErr | ErrorCode::NetworkErr => 502,
+        ErrorCode::Timeout => 504,
+    };
+    error_response(status_code, &err.to_string())
+}
 
 fn main() {
-    println!("Starting up web service...");
-    // TODO: implement web service which exposes SAFEthing API on
-    // a RESTful interface for smart home devices
+    env_logger::init();
+    let listen_addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_LISTEN_ADDR.to_string());
+
+    println!("Starting up web service on http://{} ...", listen_addr);
+    let web_service: WebService = WebService::default();
+    rouille::start_server(listen_addr, move |request| web_service.handle(request));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_thing::{AccessType, MemStore};
+    use std::io::Read;
+    use std::sync::mpsc::Receiver;
+    use std::time::Instant;
+
+    // Max time to wait for the background threads to report an event or update a request
+    const EVENT_TIMEOUT: u64 = 30_000;
+
+    // Helper to send a request to the web service, along with a JSON body if provided,
+    // and return the status code and the JSON body of the response
+    fn call(
+        web_service: &WebService<MemStore>,
+        method: &str,
+        url: &str,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let (headers, data) = match body {
+            Some(body) => (
+                vec![("Content-Type".to_string(), "application/json".to_string())],
+                body.to_string().into_bytes(),
+            ),
+            None => (vec![], vec![]),
+        };
+        let request = Request::fake_http(method, url, headers, data);
+        let response = web_service.handle(&request);
+        let status_code = response.status_code;
+        let (mut reader, _) = response.data.into_reader_and_size();
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&body).unwrap()
+        };
+        (status_code, body)
+    }
+
+    // Helper to register a SAFEthing on an in-memory network through the web service
+    fn register(
+        web_service: &WebService<MemStore>,
+        thing_id: &str,
+        network: &str,
+        attrs: &[ThingAttr],
+        topics: &[Topic],
+        actions: &[ActionDef],
+    ) {
+        let body = json!({
+            "thing_id": thing_id,
+            "auth_uri": network,
+            "attributes": attrs,
+            "topics": topics,
+            "actions": actions,
+        });
+        let (status_code, body) = call(web_service, "POST", "/things", Some(body));
+        assert_eq!(status_code, 201, "Failed to register: {}", body);
+        assert_eq!(body["thing_id"], thing_id);
+    }
+
+    // Helper to receive the events sent to the WebSocket clients of a SAFEthing
+    fn listen(web_service: &WebService<MemStore>, thing_id: &str) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        let things = web_service.things.lock().unwrap();
+        things[thing_id].listeners.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn recv_event(rx: &Receiver<String>) -> Value {
+        let frame = rx
+            .recv_timeout(Duration::from_millis(EVENT_TIMEOUT))
+            .expect("Timed out waiting for event");
+        serde_json::from_str(&frame).unwrap()
+    }
+
+    // Helper to poll the state of an action request until it's no longer pending
+    fn wait_action_request(web_service: &WebService<MemStore>, url: &str) -> Value {
+        let start = Instant::now();
+        loop {
+            let (status_code, body) = call(web_service, "GET", url, None);
+            assert_eq!(status_code, 200, "Failed to get the state: {}", body);
+            if body["state"] != "Requested" {
+                return body;
+            }
+            assert!(start.elapsed() < Duration::from_millis(EVENT_TIMEOUT));
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    #[test]
+    fn register_and_publish() {
+        let web_service = WebService::<MemStore>::default();
+        let network = "test_ws_register_and_publish";
+        register(&web_service, "test-device", network, &[], &[], &[]);
+
+        let (status_code, body) = call(&web_service, "GET", "/things", None);
+        assert_eq!(status_code, 200);
+        assert_eq!(body, json!(["test-device"]));
+        let (_, body) = call(&web_service, "GET", "/things/test-device/status", None);
+        assert_eq!(body["status"], "Connected");
+
+        let (status_code, _) = call(&web_service, "POST", "/things/test-device/publish", None);
+        assert_eq!(status_code, 200);
+        let (_, body) = call(&web_service, "GET", "/things/test-device/status", None);
+        assert_eq!(body["status"], "Published");
+
+        let (status_code, _) = call(&web_service, "DELETE", "/things/test-device", None);
+        assert_eq!(status_code, 200);
+        let (_, body) = call(&web_service, "GET", "/things", None);
+        assert_eq!(body, json!([]));
+    }
+
+    #[test]
+    fn attributes() {
+        let web_service = WebService::<MemStore>::default();
+        let network = "test_ws_attributes";
+        let attrs = [ThingAttr::new("Location", "home", true)];
+        register(&web_service, "test-device", network, &attrs, &[], &[]);
+        register(&web_service, "test-controller", network, &[], &[], &[]);
+
+        let body = json!({ "value": "office" });
+        let url = "/things/test-device/attributes/Location";
+        let (status_code, _) = call(&web_service, "PUT", url, Some(body));
+        assert_eq!(status_code, 200);
+
+        let url = "/things/test-controller/peers/test-device/attributes";
+        let (status_code, body) = call(&web_service, "GET", url, None);
+        assert_eq!(status_code, 200);
+        let attrs: Vec<ThingAttr> = serde_json::from_value(body).unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].attr, "Location");
+        assert_eq!(attrs[0].value, "office");
+    }
+
+    #[test]
+    fn notify() {
+        let web_service = WebService::<MemStore>::default();
+        let network = "test_ws_notify";
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        register(&web_service, "test-device", network, &[], &topics, &[]);
+        register(&web_service, "test-controller", network, &[], &[], &[]);
+        let _ = call(&web_service, "POST", "/things/test-device/publish", None);
+
+        let body = json!({ "filter_op": "Equal", "filter_value": "on" });
+        let url = "/things/test-controller/peers/test-device/topics/Alarm/subscribe";
+        let (status_code, _) = call(&web_service, "POST", url, Some(body));
+        assert_eq!(status_code, 200);
+        let events_rx = listen(&web_service, "test-controller");
+
+        let url = "/things/test-device/topics/Alarm/notify";
+        let (status_code, _) = call(&web_service, "POST", url, Some(json!({ "data": "on" })));
+        assert_eq!(status_code, 200);
+        let event = recv_event(&events_rx);
+        assert_eq!(event["event"], "notification");
+        assert_eq!(event["thing_id"], "test-device");
+        assert_eq!(event["topic"], "Alarm");
+        assert_eq!(event["data"], "on");
+    }
+
+    #[test]
+    fn action_request() {
+        let web_service = WebService::<MemStore>::default();
+        let network = "test_ws_action_request";
+        let actions = [ActionDef::new("Print", AccessType::All, &[])];
+        register(&web_service, "test-printer", network, &[], &[], &actions);
+        register(&web_service, "test-sender", network, &[], &[], &[]);
+        let _ = call(&web_service, "POST", "/things/test-printer/publish", None);
+        let events_rx = listen(&web_service, "test-printer");
+
+        let replies = [
+            (json!({}), "Done", Value::Null),
+            (
+                json!({ "error": "Out of paper" }),
+                "Failed",
+                json!("Out of paper"),
+            ),
+        ];
+        for (reply, state, error) in replies.iter() {
+            let url = "/things/test-sender/peers/test-printer/actions/Print";
+            let (status_code, body) = call(&web_service, "POST", url, Some(json!({})));
+            assert_eq!(status_code, 200, "Failed to send the request: {}", body);
+            let request_id = body["request_id"].as_str().unwrap().to_string();
+
+            let event = recv_event(&events_rx);
+            assert_eq!(event["event"], "action_request");
+            assert_eq!(event["request_id"], request_id.as_str());
+            assert_eq!(event["action"], "Print");
+            let url = format!("/things/test-printer/action_requests/{}/reply", request_id);
+            let (status_code, _) = call(&web_service, "POST", &url, Some(reply.clone()));
+            assert_eq!(status_code, 200);
+
+            let url = format!(
+                "/things/test-sender/peers/test-printer/action_requests/{}",
+                request_id
+            );
+            let req_state = wait_action_request(&web_service, &url);
+            assert_eq!(req_state["state"], *state);
+            assert_eq!(req_state["error"], *error);
+        }
+    }
+
+    #[test]
+    fn error_status_codes() {
+        let web_service = WebService::<MemStore>::default();
+        let network = "test_ws_error_status_codes";
+        let topics = [Topic::new("Alarm", AccessType::All)];
+        register(&web_service, "test-device", network, &[], &topics, &[]);
+        register(&web_service, "test-controller", network, &[], &[], &[]);
+
+        let body = json!({ "thing_id": "test-device", "auth_uri": network });
+        let (status_code, _) = call(&web_service, "POST", "/things", Some(body));
+        assert_eq!(status_code, 409);
+        let (status_code, _) = call(&web_service, "POST", "/things", None);
+        assert_eq!(status_code, 400);
+        let body = json!({ "thing_id": "abc", "auth_uri": network });
+        let (status_code, _) = call(&web_service, "POST", "/things", Some(body));
+        assert_eq!(status_code, 400);
+        let (status_code, _) = call(&web_service, "GET", "/things/test-unknown/status", None);
+        assert_eq!(status_code, 404);
+        let (status_code, _) = call(&web_service, "GET", "/unknown", None);
+        assert_eq!(status_code, 404);
+
+        let url = "/things/test-controller/peers/test-unknown/attributes";
+        let (status_code, _) = call(&web_service, "GET", url, None);
+        assert_eq!(status_code, 404);
+        let url = "/things/test-device/action_requests/1/reply";
+        let (status_code, _) = call(&web_service, "POST", url, Some(json!({})));
+        assert_eq!(status_code, 404);
+        let url = "/things/test-controller/peers/test-device/topics/Alarm/subscribe";
+        let (status_code, _) = call(&web_service, "POST", url, Some(json!({})));
+        assert_eq!(status_code, 409);
+
+        let _ = call(
+            &web_service,
+            "PUT",
+            "/things/test-device/claim_code",
+            Some(json!({ "code": "123456" })),
+        );
+        let url = "/things/test-controller/peers/test-device/claim";
+        let (status_code, _) = call(&web_service, "POST", url, Some(json!({ "code": "654321" })));
+        assert_eq!(status_code, 403);
+    }
 }
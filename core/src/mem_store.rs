@@ -0,0 +1,541 @@
+// Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+//
+// This file is part of the SAFEthing Framework.
+//
+// The SAFEthing Framework is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// The SAFEthing Framework is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+use lazy_static::lazy_static;
+use log::{debug, trace};
+use rust_sodium::crypto::{box_, sealedbox, sign};
+
+use crate::errors::{Error, ErrorCode, ResultReturn};
+use crate::thing_store::ThingStore;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Name of the in-memory network used when no auth URI is provided
+const MEM_STORE_DEFAULT_NETWORK: &str = "safe_thing_mem_network";
+
+type XorName = [u8; 32];
+
+/// Set of permissions granted on a MutableData to apps other than its owner.
+/// Reading is always allowed as all MutableData are public.
+#[derive(Clone, Copy, Debug)]
+struct PermissionSet {
+    insert: bool,
+    update: bool,
+}
+
+/// A MutableData kept in memory, each entry value is kept with its version.
+/// The entries of a private MutableData are not really encrypted, but they can only
+/// be accessed with a handle which has the same encryption info it was created with.
+/// Each entry also keeps the encryption info of the handle it was inserted with, as on the
+/// SAFE Network anyone can insert entries which cannot be decrypted with the right one.
+#[derive(Debug)]
+struct MemMData {
+    owner: String,
+    anyone_perms: PermissionSet,
+    enc_info: Option<String>,
+    entries: BTreeMap<String, (String, u64, Option<String>)>,
+}
+
+impl MemMData {
+    // private helper
+    fn check_enc_info(&self, mdata: &MemMutableData) -> ResultReturn<()> {
+        if self.enc_info == mdata.enc_info {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorCode::NetworkErr,
+                "Failed to decrypt the entries of the MutableData",
+            ))
+        }
+    }
+}
+
+/// An in-memory network is just the set of MutableData stored on it, shared by all
+/// the apps connected to it
+type MemNetwork = Arc<Mutex<BTreeMap<(XorName, u64), MemMData>>>;
+
+lazy_static! {
+    // All the in-memory networks created in this process, by name
+    static ref MEM_NETWORKS: Mutex<HashMap<String, MemNetwork>> = Mutex::new(HashMap::new());
+    // Signing keys of the apps which connected to any in-memory network, by app id,
+    // as if they were given by an authenticator
+    static ref MEM_APP_KEYS: Mutex<HashMap<String, (sign::PublicKey, sign::SecretKey)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Handle to a MutableData stored on the in-memory network
+#[derive(Clone, Default, Debug)]
+pub struct MemMutableData {
+    name: XorName,
+    type_tag: u64,
+    enc_info: Option<String>,
+}
+
+/// In-process storage backend which keeps all the MutableData in shared memory.
+/// The auth URI provided to connect is used as the name of the in-memory network to join,
+/// thus all the SAFEthings in the same process connecting with the same auth URI can
+/// interact with each other just as if they were connected to the SAFE Network.
+/// This is mainly intended for testing SAFEthings without the need of a SAFE Network.
+pub struct MemStore {
+    app_id: String,
+    network: MemNetwork,
+    connected: bool,
+    sign_keys: (sign::PublicKey, sign::SecretKey),
+    enc_keys: (box_::PublicKey, box_::SecretKey),
+}
+
+impl MemStore {
+    // private helper
+    fn new_mutable_data(
+        &self,
+        xor_name: XorName,
+        type_tag: u64,
+        enc_info: Option<&str>,
+    ) -> ResultReturn<MemMutableData> {
+        self.check_connected()?;
+        let mut network = self.network.lock().unwrap();
+        if network.contains_key(&(xor_name, type_tag)) {
+            debug!("MutableData already exits");
+        } else {
+            // Owner is allowed to do anything, other apps can only read and insert
+            let mdata = MemMData {
+                owner: self.app_id.clone(),
+                anyone_perms: PermissionSet {
+                    insert: true,
+                    update: false,
+                },
+                enc_info: enc_info.map(String::from),
+                entries: BTreeMap::new(),
+            };
+            let _ = network.insert((xor_name, type_tag), mdata);
+        }
+
+        Ok(MemMutableData {
+            name: xor_name,
+            type_tag,
+            enc_info: enc_info.map(String::from),
+        })
+    }
+
+    // private helper
+    fn check_connected(&self) -> ResultReturn<()> {
+        if self.connected {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorCode::ConnectionErr,
+                "Not connected to the in-memory network",
+            ))
+        }
+    }
+}
+
+impl ThingStore for MemStore {
+    type MData = MemMutableData;
+
+    fn gen_auth_request(_thing_id: &str) -> ResultReturn<String> {
+        Ok(MEM_STORE_DEFAULT_NETWORK.to_string())
+    }
+
+    fn connect(app_id: &str, auth_uri: &str) -> ResultReturn<MemStore> {
+        let mut networks = MEM_NETWORKS.lock().unwrap();
+        let network = networks
+            .entry(auth_uri.to_string())
+            .or_insert_with(MemNetwork::default)
+            .clone();
+        debug!("Connected to in-memory network: {}", auth_uri);
+        let sign_keys = MEM_APP_KEYS
+            .lock()
+            .unwrap()
+            .entry(app_id.to_string())
+            .or_insert_with(sign::gen_keypair)
+            .clone();
+
+        Ok(MemStore {
+            app_id: app_id.to_string(),
+            network,
+            connected: true,
+            sign_keys,
+            enc_keys: box_::gen_keypair(),
+        })
+    }
+
+    fn pub_sign_key(&self) -> ResultReturn<String> {
+        Ok(to_hex(&(self.sign_keys.0).0))
+    }
+
+    fn sign(&self, data: &str) -> ResultReturn<String> {
+        let signature = sign::sign_detached(data.as_bytes(), &self.sign_keys.1);
+        Ok(to_hex(&signature.0))
+    }
+
+    fn verify(&self, pub_sign_key: &str, data: &str, signature: &str) -> ResultReturn<bool> {
+        let pub_sign_key =
+            match from_hex(pub_sign_key).and_then(|k| sign::PublicKey::from_slice(&k)) {
+                Some(pub_sign_key) => pub_sign_key,
+                None => {
+                    return Err(Error::new(
+                        ErrorCode::InvalidArgument,
+                        format!("Invalid public sign key: {}", pub_sign_key).as_str(),
+                    ))
+                }
+            };
+        match from_hex(signature).and_then(|s| sign::Signature::from_slice(&s)) {
+            Some(signature) => Ok(sign::verify_detached(
+                &signature,
+                data.as_bytes(),
+                &pub_sign_key,
+            )),
+            None => Ok(false),
+        }
+    }
+
+    fn pub_enc_key(&self) -> ResultReturn<String> {
+        Ok(to_hex(&(self.enc_keys.0).0))
+    }
+
+    fn encrypt_sealed(&self, pub_enc_key: &str, data: &str) -> ResultReturn<String> {
+        match from_hex(pub_enc_key).and_then(|k| box_::PublicKey::from_slice(&k)) {
+            Some(pub_enc_key) => Ok(to_hex(&sealedbox::seal(data.as_bytes(), &pub_enc_key))),
+            None => Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid public encryption key: {}", pub_enc_key).as_str(),
+            )),
+        }
+    }
+
+    fn decrypt_sealed(&self, ciphertext: &str) -> ResultReturn<String> {
+        from_hex(ciphertext)
+            .and_then(|c| sealedbox::open(&c, &self.enc_keys.0, &self.enc_keys.1).ok())
+            .and_then(|plaintext| String::from_utf8(plaintext).ok())
+            .ok_or_else(|| Error::new(ErrorCode::InvalidArgument, "Failed to decrypt data"))
+    }
+
+    fn gen_xor_name(&self, in_str: &str) -> XorName {
+        let mut arr: XorName = Default::default();
+        for (i, chunk) in arr.chunks_mut(8).enumerate() {
+            let mut hasher = DefaultHasher::new();
+            (in_str, i).hash(&mut hasher);
+            chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+        }
+        arr
+    }
+
+    // There is no real encryption in memory, the info is only derived to be unique
+    fn gen_enc_info(&self) -> ResultReturn<String> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Failed to get time since epoch")
+            .as_nanos();
+        let info = self.gen_xor_name(&format!("enc-info-{}-{}", self.app_id, nanos));
+        Ok(info.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    fn new_pub_mutable_data(
+        &self,
+        xor_name: XorName,
+        type_tag: u64,
+    ) -> ResultReturn<MemMutableData> {
+        self.new_mutable_data(xor_name, type_tag, None)
+    }
+
+    fn get_pub_mutable_data(
+        &self,
+        xor_name: XorName,
+        type_tag: u64,
+    ) -> ResultReturn<MemMutableData> {
+        Ok(MemMutableData {
+            name: xor_name,
+            type_tag,
+            enc_info: None,
+        })
+    }
+
+    fn new_priv_mutable_data(
+        &self,
+        xor_name: XorName,
+        type_tag: u64,
+        enc_info: &str,
+    ) -> ResultReturn<MemMutableData> {
+        self.new_mutable_data(xor_name, type_tag, Some(enc_info))
+    }
+
+    fn get_priv_mutable_data(
+        &self,
+        xor_name: XorName,
+        type_tag: u64,
+        enc_info: &str,
+    ) -> ResultReturn<MemMutableData> {
+        Ok(MemMutableData {
+            name: xor_name,
+            type_tag,
+            enc_info: Some(enc_info.to_string()),
+        })
+    }
+
+    fn mutable_data_get_value(&self, mdata: &MemMutableData, key: &str) -> ResultReturn<String> {
+        self.check_connected()?;
+        trace!("Getting entry with key {}", key);
+        let network = self.network.lock().unwrap();
+        let md = match network.get(&(mdata.name, mdata.type_tag)) {
+            Some(md) => md,
+            None => {
+                return Err(Error::new(
+                    ErrorCode::NoSuchData,
+                    format!("Failed to retrieve value from MutableData: {}", key).as_str(),
+                ))
+            }
+        };
+        md.check_enc_info(mdata)?;
+        match md.entries.get(key) {
+            Some((value, version, enc_info)) if *enc_info == md.enc_info => {
+                trace!("Got entry (version {}) with value: {}", version, value);
+                Ok(value.clone())
+            }
+            _ => {
+                trace!("Entry not found with key {}", key);
+                Err(Error::new(
+                    ErrorCode::NoSuchData,
+                    format!("Failed to retrieve value from MutableData: {}", key).as_str(),
+                ))
+            }
+        }
+    }
+
+    fn mutable_data_set_value(
+        &self,
+        mdata: &MemMutableData,
+        key: &str,
+        value: &str,
+    ) -> ResultReturn<()> {
+        self.check_connected()?;
+        let mut network = self.network.lock().unwrap();
+        let md = match network.get_mut(&(mdata.name, mdata.type_tag)) {
+            Some(md) => md,
+            None => {
+                return Err(Error::new(
+                    ErrorCode::NetworkErr,
+                    "Failed to mutate MutableData: no such data",
+                ))
+            }
+        };
+
+        // The entry is written even if it cannot be decrypted with the MutableData's encryption info
+        let is_owner = md.owner == self.app_id;
+        let new_version = match md.entries.get(key) {
+            Some((_, version, _)) if is_owner || md.anyone_perms.update => version + 1,
+            None if is_owner || md.anyone_perms.insert => 0,
+            _ => {
+                return Err(Error::new(
+                    ErrorCode::NetworkErr,
+                    format!(
+                        "Failed to mutate MutableData entry '{}': access denied",
+                        key
+                    )
+                    .as_str(),
+                ))
+            }
+        };
+
+        trace!(
+            "Setting entry: '{}' => '{}' (version {})",
+            key,
+            value,
+            new_version
+        );
+        let entry = (value.to_string(), new_version, mdata.enc_info.clone());
+        let _ = md.entries.insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    fn mutable_data_get_entries(
+        &self,
+        mdata: &MemMutableData,
+    ) -> ResultReturn<Vec<(String, String)>> {
+        self.check_connected()?;
+        trace!("Getting entries from MutableData");
+        let network = self.network.lock().unwrap();
+        match network.get(&(mdata.name, mdata.type_tag)) {
+            Some(md) => {
+                md.check_enc_info(mdata)?;
+                Ok(md
+                    .entries
+                    .iter()
+                    .filter_map(|(key, (value, _, enc_info))| {
+                        if *enc_info == md.enc_info {
+                            Some((key.clone(), value.clone()))
+                        } else {
+                            debug!("Skipping entry which cannot be read: {}", key);
+                            None
+                        }
+                    })
+                    .collect())
+            }
+            None => Err(Error::new(
+                ErrorCode::NoSuchData,
+                "Failed to retrieve entries from MutableData",
+            )),
+        }
+    }
+
+    fn sim_net_disconnect(&mut self) {
+        debug!("Simulating disconnection from in-memory network");
+        self.connected = false;
+    }
+}
+
+// Hex encoding of the keys, signatures and sealed data
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || hex.len() % 2 == 1 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_values() {
+        let store = MemStore::connect("mem-store-owner", "mem_store_set_and_get").unwrap();
+        let xor_name = store.gen_xor_name("some-thing");
+        let mdata = store.new_pub_mutable_data(xor_name, 15000).unwrap();
+        assert!(store.mutable_data_get_value(&mdata, "key").is_err());
+
+        store
+            .mutable_data_set_value(&mdata, "key", "value1")
+            .unwrap();
+        store
+            .mutable_data_set_value(&mdata, "key", "value2")
+            .unwrap();
+        assert_eq!(
+            store.mutable_data_get_value(&mdata, "key").unwrap(),
+            "value2"
+        );
+
+        let entries = store.mutable_data_get_entries(&mdata).unwrap();
+        assert_eq!(entries, vec![("key".to_string(), "value2".to_string())]);
+    }
+
+    #[test]
+    fn others_can_only_insert() {
+        let owner = MemStore::connect("mem-store-owner", "mem_store_perms").unwrap();
+        let other = MemStore::connect("mem-store-other", "mem_store_perms").unwrap();
+        let xor_name = owner.gen_xor_name("some-thing");
+        let mdata = owner.new_pub_mutable_data(xor_name, 15000).unwrap();
+        owner
+            .mutable_data_set_value(&mdata, "key", "value")
+            .unwrap();
+
+        let other_mdata = other.get_pub_mutable_data(xor_name, 15000).unwrap();
+        assert_eq!(
+            other.mutable_data_get_value(&other_mdata, "key").unwrap(),
+            "value"
+        );
+        assert!(other
+            .mutable_data_set_value(&other_mdata, "key", "new")
+            .is_err());
+        other
+            .mutable_data_set_value(&other_mdata, "other-key", "new")
+            .unwrap();
+        assert_eq!(
+            owner.mutable_data_get_value(&mdata, "other-key").unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn networks_are_isolated() {
+        let store_a = MemStore::connect("mem-store-owner", "mem_store_net_a").unwrap();
+        let store_b = MemStore::connect("mem-store-owner", "mem_store_net_b").unwrap();
+        let xor_name = store_a.gen_xor_name("some-thing");
+        let mdata = store_a.new_pub_mutable_data(xor_name, 15000).unwrap();
+        store_a
+            .mutable_data_set_value(&mdata, "key", "value")
+            .unwrap();
+
+        let mdata_b = store_b.get_pub_mutable_data(xor_name, 15000).unwrap();
+        match store_b.mutable_data_get_value(&mdata_b, "key") {
+            Err(err) => match err.code() {
+                ErrorCode::NoSuchData => (),
+                code => panic!("Unexpected error code: {:?}", code),
+            },
+            Ok(value) => panic!("Unexpected value found: {}", value),
+        };
+    }
+
+    #[test]
+    fn private_mutable_data() {
+        let owner = MemStore::connect("mem-store-owner", "mem_store_private").unwrap();
+        let other = MemStore::connect("mem-store-other", "mem_store_private").unwrap();
+        let xor_name = owner.gen_xor_name("some-thing");
+        let enc_info = owner.gen_enc_info().unwrap();
+        assert_ne!(enc_info, owner.gen_enc_info().unwrap());
+        let mdata = owner
+            .new_priv_mutable_data(xor_name, 15000, &enc_info)
+            .unwrap();
+        owner
+            .mutable_data_set_value(&mdata, "key", "value")
+            .unwrap();
+
+        let pub_mdata = other.get_pub_mutable_data(xor_name, 15000).unwrap();
+        assert!(other.mutable_data_get_value(&pub_mdata, "key").is_err());
+        assert!(other.mutable_data_get_entries(&pub_mdata).is_err());
+
+        let priv_mdata = other
+            .get_priv_mutable_data(xor_name, 15000, &enc_info)
+            .unwrap();
+        assert_eq!(
+            other.mutable_data_get_value(&priv_mdata, "key").unwrap(),
+            "value"
+        );
+        other
+            .mutable_data_set_value(&priv_mdata, "other-key", "new")
+            .unwrap();
+        assert_eq!(owner.mutable_data_get_entries(&mdata).unwrap().len(), 2);
+
+        // entries inserted without the encryption info cannot be read, but they are skipped
+        other
+            .mutable_data_set_value(&pub_mdata, "garbage", "value")
+            .unwrap();
+        assert!(owner.mutable_data_get_value(&mdata, "garbage").is_err());
+        assert_eq!(owner.mutable_data_get_entries(&mdata).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn disconnected_store_fails() {
+        let mut store = MemStore::connect("mem-store-owner", "mem_store_disconnect").unwrap();
+        let xor_name = store.gen_xor_name("some-thing");
+        let mdata = store.new_pub_mutable_data(xor_name, 15000).unwrap();
+        store.sim_net_disconnect();
+        assert!(store
+            .mutable_data_set_value(&mdata, "key", "value")
+            .is_err());
+    }
+}
@@ -17,14 +17,14 @@
 
 use log::{debug, trace, warn};
 
-use safe_app::ffi::crypto::{app_pub_sign_key, sha3_hash};
-use safe_app::ffi::object_cache::{
-    MDataEntryActionsHandle, MDataPermissionsHandle, SignPubKeyHandle,
+use safe_app::ffi::crypto::{
+    app_pub_sign_key, decrypt_sealed_box, enc_generate_key_pair, enc_pub_key_free, enc_pub_key_get,
+    enc_pub_key_new, encrypt_sealed_box, sha3_hash, sign, sign_pub_key_free, sign_pub_key_get,
+    sign_pub_key_new, verify, SIGN_WITH_APP,
+};
+use safe_app::ffi::mdata_info::{
+    mdata_info_decrypt, mdata_info_encrypt_entry_key, mdata_info_encrypt_entry_value,
 };
-#[cfg(feature = "fake-auth")]
-use safe_app::test_utils::create_app;
-use safe_app::App;
-//use safe_app::ffi::mdata_info::{mdata_info_new_private};
 use safe_app::ffi::mutable_data::entry_actions::{
     mdata_entry_actions_insert, mdata_entry_actions_new, mdata_entry_actions_update,
 };
@@ -32,15 +32,23 @@ use safe_app::ffi::mutable_data::permissions::{
     mdata_permissions_insert, mdata_permissions_new, USER_ANYONE,
 };
 use safe_app::ffi::mutable_data::{mdata_mutate_entries, mdata_put, ENTRIES_EMPTY};
+use safe_app::ffi::object_cache::{
+    EncryptPubKeyHandle, EncryptSecKeyHandle, MDataEntryActionsHandle, MDataPermissionsHandle,
+    SignPubKeyHandle,
+};
+#[cfg(feature = "fake-auth")]
+use safe_app::test_utils::create_app;
+use safe_app::App;
 
+use ffi_utils::test_utils::{call_0, call_1, call_2 /*, call_vec*/, call_vec_u8};
 #[cfg(feature = "use-mock-routing")]
 use safe_app::ffi::test_utils::test_simulate_network_disconnect;
-use safe_core::ffi::MDataInfo;
-//use safe_core::ffi::arrays::{SymSecretKey, SymNonce};
-use ffi_utils::test_utils::{call_0, call_1 /*, call_vec*/, call_vec_u8};
+use safe_core::ffi::arrays::{SymNonce, SymSecretKey};
 use safe_core::ffi::ipc::req::PermissionSet;
+use safe_core::ffi::MDataInfo;
 #[cfg(not(feature = "fake-auth"))]
 use safe_core::ipc::{AppExchangeInfo, AuthReq, IpcReq};
+use safe_core::utils::generate_random_vector;
 
 #[cfg(not(feature = "fake-auth"))]
 use std::collections::HashMap;
@@ -52,12 +60,25 @@ use std::{fmt, str};
 use reqwest::get as httpget;
 
 use crate::errors::{Error, ErrorCode, ResultReturn};
+use crate::thing_store::ThingStore;
 
 // TODO: these should be imported from safe_app::errors::codes
 // but `errors` module is currently private
+const ERR_NO_SUCH_DATA: i32 = -103;
 const ERR_DATA_EXISTS: i32 = -104;
 const ERR_NO_SUCH_ENTRY: i32 = -106;
 
+// Length of the key and nonce used to encrypt the entries of private MutableData
+const SYM_KEY_LEN: usize = 32;
+const SYM_NONCE_LEN: usize = 24;
+
+// Length of the public signing keys and of the signatures
+const SIGN_PUB_KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+// Length of the public encryption keys
+const ENC_PUB_KEY_LEN: usize = 32;
+
 // URL where to send a GET request to the authenticator webservice for authorising the SAFE app
 #[cfg(not(feature = "fake-auth"))]
 const SAFE_AUTH_WEBSERVICE_BASE_URL: &str = "http://localhost:41805/authorise/";
@@ -106,54 +127,15 @@ impl fmt::Display for ConnStatus {
     }
 }
 
+/// Backend storing the SAFEthings on the SAFE Network, it's the one used by default
 pub struct SAFENet {
     safe_app: Option<App>,
     conn_status: ConnStatus,
     sign_pub_key_h: SignPubKeyHandle,
+    enc_key_pair_h: (EncryptPubKeyHandle, EncryptSecKeyHandle),
 }
 
 impl SAFENet {
-    // Generate an authorisation request string that can be sent to a SAFE Authenticator
-    #[cfg(not(feature = "fake-auth"))]
-    pub fn gen_auth_request(thing_id: &str) -> ResultReturn<String> {
-        // TODO: allow the caller to provide the name and vendor strings
-        let ipc_req = IpcReq::Auth(AuthReq {
-            app: AppExchangeInfo {
-                id: thing_id.to_string(),
-                scope: None,
-                name: "SAFEthing-".to_string() + thing_id,
-                vendor: "SAFEthing Framework".to_string(),
-            },
-            app_container: false,
-            containers: HashMap::new(),
-        });
-
-        match SAFENetHelpers::encode_ipc_msg(ipc_req) {
-            Ok(auth_req_str) => {
-                trace!(
-                    "Authorisation request generated successfully: {}",
-                    auth_req_str
-                );
-                let authenticator_webservice_url =
-                    SAFE_AUTH_WEBSERVICE_BASE_URL.to_string() + &auth_req_str;
-                let mut res = httpget(&authenticator_webservice_url).unwrap();
-                let mut auth_res = String::new();
-                res.read_to_string(&mut auth_res).unwrap();
-                debug!("Authorisation response: {}", auth_res);
-                Ok(auth_res)
-            }
-            Err(e) => Err(Error::new(
-                ErrorCode::InvalidArgument,
-                format!("Failed encoding the auth URI: {:?}", e).as_str(),
-            )),
-        }
-    }
-
-    #[cfg(feature = "fake-auth")]
-    pub fn gen_auth_request(thing_id: &str) -> ResultReturn<String> {
-        Ok(thing_id.to_string())
-    }
-
     // private helper function
     #[cfg(feature = "fake-auth")]
     fn register(&mut self, _: &str, _: &str) -> ResultReturn<()> {
@@ -195,44 +177,133 @@ impl SAFENet {
         }
     }
 
-    // Connect to the SAFE Network using the provided app id and auth URI
-    pub fn connect(app_id: &str, auth_uri: &str) -> ResultReturn<SAFENet> {
-        let mut safe_net = SAFENet {
-            safe_app: None,
-            conn_status: ConnStatus::Init,
-            sign_pub_key_h: Default::default(),
+    #[allow(dead_code)]
+    pub fn get_conn_status(&self) -> &ConnStatus {
+        &self.conn_status
+    }
+
+    // private helper to build the info of a MutableData, it's private
+    // if the key and nonce to encrypt its entries are provided
+    fn mdata_info(
+        xor_name: [u8; 32],
+        type_tag: u64,
+        enc: Option<(SymSecretKey, SymNonce)>,
+    ) -> MDataInfo {
+        let (has_enc_info, enc_key, enc_nonce) = match enc {
+            Some((enc_key, enc_nonce)) => (true, enc_key, enc_nonce),
+            None => (false, Default::default(), Default::default()),
         };
+        MDataInfo {
+            name: xor_name,
+            type_tag: type_tag,
+            has_enc_info,
+            enc_key,
+            enc_nonce,
+            has_new_enc_info: false,
+            new_enc_key: Default::default(),
+            new_enc_nonce: Default::default(),
+        }
+    }
 
-        safe_net.register(&app_id, &auth_uri)?;
+    // private helper to parse the encryption info of a private MutableData,
+    // which is the key followed by the nonce, hex encoded
+    fn parse_enc_info(enc_info: &str) -> ResultReturn<(SymSecretKey, SymNonce)> {
+        let invalid = || {
+            Error::new(
+                ErrorCode::InvalidArgument,
+                "Invalid encryption info for a private MutableData",
+            )
+        };
+        let bytes =
+            SAFENet::decode_hex(enc_info, SYM_KEY_LEN + SYM_NONCE_LEN).ok_or_else(invalid)?;
+        let mut enc_key: SymSecretKey = Default::default();
+        let mut enc_nonce: SymNonce = Default::default();
+        enc_key.copy_from_slice(&bytes[..SYM_KEY_LEN]);
+        enc_nonce.copy_from_slice(&bytes[SYM_KEY_LEN..]);
+        Ok((enc_key, enc_nonce))
+    }
 
-        // Retrieve app's public sign key
-        let app: *const App = safe_net.safe_app.as_ref().unwrap();
-        safe_net.sign_pub_key_h =
-            unsafe { call_1(|ud, cb| app_pub_sign_key(app, ud, cb)).unwrap() };
-        Ok(safe_net)
+    // private helper to decode an hex string of the length expected
+    fn decode_hex(hex: &str, len: usize) -> Option<Vec<u8>> {
+        if !hex.is_ascii() || hex.len() != 2 * len {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect()
     }
 
-    #[allow(dead_code)]
-    pub fn get_conn_status(&self) -> &ConnStatus {
-        &self.conn_status
+    // private helper to encrypt the key of an entry if the MutableData is private,
+    // the same key is always encrypted the same way so entries can be looked up
+    fn encrypt_key(mdata: &MDataInfo, key: &str) -> ResultReturn<Vec<u8>> {
+        if !mdata.has_enc_info {
+            return Ok(key.as_bytes().to_vec());
+        }
+        unsafe {
+            call_vec_u8(|ud, cb| {
+                mdata_info_encrypt_entry_key(mdata, key.as_ptr(), key.len(), ud, cb)
+            })
+            .map_err(|error_code| {
+                Error::new(
+                    ErrorCode::NetworkErr,
+                    format!("Failed to encrypt MutableData entry key: {:?}", error_code).as_str(),
+                )
+            })
+        }
     }
 
-    pub fn gen_xor_name(&self, in_str: &str) -> [u8; 32] {
-        let sha3 = unsafe {
-            call_vec_u8(|ud, cb| sha3_hash(in_str.as_ptr(), in_str.len(), ud, cb)).unwrap()
-        };
-        let mut arr: [u8; 32] = Default::default();
-        for i in 0..32 {
-            arr[i] = sha3[i];
+    // private helper to encrypt the value of an entry if the MutableData is private
+    fn encrypt_value(mdata: &MDataInfo, value: &str) -> ResultReturn<Vec<u8>> {
+        if !mdata.has_enc_info {
+            return Ok(value.as_bytes().to_vec());
+        }
+        unsafe {
+            call_vec_u8(|ud, cb| {
+                mdata_info_encrypt_entry_value(mdata, value.as_ptr(), value.len(), ud, cb)
+            })
+            .map_err(|error_code| {
+                Error::new(
+                    ErrorCode::NetworkErr,
+                    format!(
+                        "Failed to encrypt MutableData entry value: {:?}",
+                        error_code
+                    )
+                    .as_str(),
+                )
+            })
         }
-        arr
     }
 
-    pub fn new_pub_mutable_data(
-        &self,
-        xor_name: [u8; 32],
-        type_tag: u64,
-    ) -> ResultReturn<MutableData> {
+    // private helper to decrypt the key or value of an entry if the MutableData is private
+    fn decrypt(mdata: &MDataInfo, data: &[u8]) -> ResultReturn<Vec<u8>> {
+        if !mdata.has_enc_info {
+            return Ok(data.to_vec());
+        }
+        unsafe {
+            call_vec_u8(|ud, cb| mdata_info_decrypt(mdata, data.as_ptr(), data.len(), ud, cb))
+                .map_err(|error_code| {
+                    Error::new(
+                        ErrorCode::NetworkErr,
+                        format!("Failed to decrypt MutableData entry: {:?}", error_code).as_str(),
+                    )
+                })
+        }
+    }
+
+    // private helper to decrypt the key or value of an entry into a string
+    fn decrypt_str(mdata: &MDataInfo, data: &[u8]) -> ResultReturn<String> {
+        String::from_utf8(SAFENet::decrypt(mdata, data)?).map_err(|err| {
+            Error::new(
+                ErrorCode::NetworkErr,
+                format!("Invalid MutableData entry: {}", err).as_str(),
+            )
+        })
+    }
+
+    // private helper to commit a new MutableData to the network, it's
+    // not an error if it already exists
+    fn put_mutable_data(&self, md_info: MDataInfo) -> ResultReturn<MutableData> {
         let app: *const App = self.safe_app.as_ref().unwrap();
 
         // Create permissions object
@@ -253,7 +324,8 @@ impl SAFENet {
             })
             .unwrap();
 
-            // Now add permissions for other apps/users
+            // Now add permissions for other apps/users, they need to insert entries to send
+            // requests, thus the events of the topics which are not for All are sealed
             let perm_set = PermissionSet {
                 read: true,
                 insert: true,
@@ -265,25 +337,14 @@ impl SAFENet {
                 .unwrap();
         };
 
-        // Create an empty public MD
-        let md_info_pub = MDataInfo {
-            name: xor_name,
-            type_tag: type_tag,
-            has_enc_info: false,
-            enc_key: Default::default(),
-            enc_nonce: Default::default(),
-            has_new_enc_info: false,
-            new_enc_key: Default::default(),
-            new_enc_nonce: Default::default(),
-        };
-
+        // Create an empty MD
         unsafe {
-            match call_0(|ud, cb| mdata_put(app, &md_info_pub, perms_h, ENTRIES_EMPTY, ud, cb)) {
-                Ok(()) => Ok(MutableData(md_info_pub)),
+            match call_0(|ud, cb| mdata_put(app, &md_info, perms_h, ENTRIES_EMPTY, ud, cb)) {
+                Ok(()) => Ok(MutableData(md_info)),
                 Err(error_code) => {
                     if error_code == ERR_DATA_EXISTS {
                         debug!("MutableData already exits");
-                        Ok(MutableData(md_info_pub))
+                        Ok(MutableData(md_info))
                     } else {
                         Err(Error::new(
                             ErrorCode::NetworkErr,
@@ -298,56 +359,285 @@ impl SAFENet {
             }
         }
     }
+}
 
-    pub fn get_pub_mutable_data(
+impl ThingStore for SAFENet {
+    type MData = MutableData;
+
+    // Generate an authorisation request string that can be sent to a SAFE Authenticator
+    #[cfg(not(feature = "fake-auth"))]
+    fn gen_auth_request(thing_id: &str) -> ResultReturn<String> {
+        // TODO: allow the caller to provide the name and vendor strings
+        let ipc_req = IpcReq::Auth(AuthReq {
+            app: AppExchangeInfo {
+                id: thing_id.to_string(),
+                scope: None,
+                name: "SAFEthing-".to_string() + thing_id,
+                vendor: "SAFEthing Framework".to_string(),
+            },
+            app_container: false,
+            containers: HashMap::new(),
+        });
+
+        match SAFENetHelpers::encode_ipc_msg(ipc_req) {
+            Ok(auth_req_str) => {
+                trace!(
+                    "Authorisation request generated successfully: {}",
+                    auth_req_str
+                );
+                let authenticator_webservice_url =
+                    SAFE_AUTH_WEBSERVICE_BASE_URL.to_string() + &auth_req_str;
+                let mut res = httpget(&authenticator_webservice_url).unwrap();
+                let mut auth_res = String::new();
+                res.read_to_string(&mut auth_res).unwrap();
+                debug!("Authorisation response: {}", auth_res);
+                Ok(auth_res)
+            }
+            Err(e) => Err(Error::new(
+                ErrorCode::InvalidArgument,
+                format!("Failed encoding the auth URI: {:?}", e).as_str(),
+            )),
+        }
+    }
+
+    #[cfg(feature = "fake-auth")]
+    fn gen_auth_request(thing_id: &str) -> ResultReturn<String> {
+        Ok(thing_id.to_string())
+    }
+
+    // Connect to the SAFE Network using the provided app id and auth URI
+    fn connect(app_id: &str, auth_uri: &str) -> ResultReturn<SAFENet> {
+        let mut safe_net = SAFENet {
+            safe_app: None,
+            conn_status: ConnStatus::Init,
+            sign_pub_key_h: Default::default(),
+            enc_key_pair_h: Default::default(),
+        };
+
+        safe_net.register(&app_id, &auth_uri)?;
+
+        // Retrieve app's public sign key
+        let app: *const App = safe_net.safe_app.as_ref().unwrap();
+        safe_net.sign_pub_key_h =
+            unsafe { call_1(|ud, cb| app_pub_sign_key(app, ud, cb)).unwrap() };
+
+        // Generate the encryption key pair for this session, the app's secret
+        // encryption key cannot be retrieved to decrypt sealed data
+        safe_net.enc_key_pair_h =
+            unsafe { call_2(|ud, cb| enc_generate_key_pair(app, ud, cb)).unwrap() };
+        Ok(safe_net)
+    }
+
+    fn pub_sign_key(&self) -> ResultReturn<String> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let key: [u8; 32] = unsafe {
+            call_1(|ud, cb| sign_pub_key_get(app, self.sign_pub_key_h, ud, cb)).map_err(|err| {
+                Error::new(
+                    ErrorCode::NetworkErr,
+                    format!("Failed to retrieve the app's public sign key: {:?}", err).as_str(),
+                )
+            })?
+        };
+        Ok(key.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    fn sign(&self, data: &str) -> ResultReturn<String> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let signed_data = unsafe {
+            call_vec_u8(|ud, cb| sign(app, data.as_ptr(), data.len(), SIGN_WITH_APP, ud, cb))
+                .map_err(|err| {
+                    Error::new(
+                        ErrorCode::NetworkErr,
+                        format!("Failed to sign data: {:?}", err).as_str(),
+                    )
+                })?
+        };
+        // The signed data is the signature followed by the data itself
+        Ok(signed_data[..SIGNATURE_LEN]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    fn verify(&self, pub_sign_key: &str, data: &str, signature: &str) -> ResultReturn<bool> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let mut key: [u8; SIGN_PUB_KEY_LEN] = Default::default();
+        match SAFENet::decode_hex(pub_sign_key, SIGN_PUB_KEY_LEN) {
+            Some(bytes) => key.copy_from_slice(&bytes),
+            None => {
+                return Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid public sign key: {}", pub_sign_key).as_str(),
+                ))
+            }
+        }
+        let mut signed_data = match SAFENet::decode_hex(signature, SIGNATURE_LEN) {
+            Some(bytes) => bytes,
+            None => return Ok(false),
+        };
+        signed_data.extend_from_slice(data.as_bytes());
+
+        unsafe {
+            let key_h: SignPubKeyHandle = call_1(|ud, cb| sign_pub_key_new(app, &key, ud, cb))
+                .map_err(|err| {
+                    Error::new(
+                        ErrorCode::NetworkErr,
+                        format!("Failed to load public sign key: {:?}", err).as_str(),
+                    )
+                })?;
+            let verified = call_vec_u8(|ud, cb| {
+                verify(app, signed_data.as_ptr(), signed_data.len(), key_h, ud, cb)
+            });
+            let _ = call_0(|ud, cb| sign_pub_key_free(app, key_h, ud, cb));
+            Ok(verified.is_ok())
+        }
+    }
+
+    fn pub_enc_key(&self) -> ResultReturn<String> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let key: [u8; ENC_PUB_KEY_LEN] = unsafe {
+            call_1(|ud, cb| enc_pub_key_get(app, self.enc_key_pair_h.0, ud, cb)).map_err(|err| {
+                Error::new(
+                    ErrorCode::NetworkErr,
+                    format!("Failed to retrieve the public encryption key: {:?}", err).as_str(),
+                )
+            })?
+        };
+        Ok(key.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    fn encrypt_sealed(&self, pub_enc_key: &str, data: &str) -> ResultReturn<String> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let mut key: [u8; ENC_PUB_KEY_LEN] = Default::default();
+        match SAFENet::decode_hex(pub_enc_key, ENC_PUB_KEY_LEN) {
+            Some(bytes) => key.copy_from_slice(&bytes),
+            None => {
+                return Err(Error::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid public encryption key: {}", pub_enc_key).as_str(),
+                ))
+            }
+        }
+
+        unsafe {
+            let key_h: EncryptPubKeyHandle = call_1(|ud, cb| enc_pub_key_new(app, &key, ud, cb))
+                .map_err(|err| {
+                    Error::new(
+                        ErrorCode::NetworkErr,
+                        format!("Failed to load public encryption key: {:?}", err).as_str(),
+                    )
+                })?;
+            let ciphertext = call_vec_u8(|ud, cb| {
+                encrypt_sealed_box(app, data.as_ptr(), data.len(), key_h, ud, cb)
+            });
+            let _ = call_0(|ud, cb| enc_pub_key_free(app, key_h, ud, cb));
+            match ciphertext {
+                Ok(ciphertext) => Ok(ciphertext.iter().map(|b| format!("{:02x}", b)).collect()),
+                Err(err) => Err(Error::new(
+                    ErrorCode::NetworkErr,
+                    format!("Failed to encrypt data: {:?}", err).as_str(),
+                )),
+            }
+        }
+    }
+
+    fn decrypt_sealed(&self, ciphertext: &str) -> ResultReturn<String> {
+        let app: *const App = self.safe_app.as_ref().unwrap();
+        let invalid = || Error::new(ErrorCode::InvalidArgument, "Failed to decrypt data");
+        let ciphertext =
+            SAFENet::decode_hex(ciphertext, ciphertext.len() / 2).ok_or_else(invalid)?;
+        let (pub_key_h, sec_key_h) = self.enc_key_pair_h;
+        let plaintext = unsafe {
+            call_vec_u8(|ud, cb| {
+                decrypt_sealed_box(
+                    app,
+                    ciphertext.as_ptr(),
+                    ciphertext.len(),
+                    pub_key_h,
+                    sec_key_h,
+                    ud,
+                    cb,
+                )
+            })
+            .map_err(|_| invalid())?
+        };
+        String::from_utf8(plaintext).map_err(|_| invalid())
+    }
+
+    fn gen_xor_name(&self, in_str: &str) -> [u8; 32] {
+        let sha3 = unsafe {
+            call_vec_u8(|ud, cb| sha3_hash(in_str.as_ptr(), in_str.len(), ud, cb)).unwrap()
+        };
+        let mut arr: [u8; 32] = Default::default();
+        for i in 0..32 {
+            arr[i] = sha3[i];
+        }
+        arr
+    }
+
+    fn gen_enc_info(&self) -> ResultReturn<String> {
+        let enc_info =
+            generate_random_vector::<u8>(SYM_KEY_LEN + SYM_NONCE_LEN).map_err(|err| {
+                Error::new(
+                    ErrorCode::NetworkErr,
+                    format!("Failed to generate encryption info: {:?}", err).as_str(),
+                )
+            })?;
+        Ok(enc_info.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    fn new_pub_mutable_data(&self, xor_name: [u8; 32], type_tag: u64) -> ResultReturn<MutableData> {
+        self.put_mutable_data(SAFENet::mdata_info(xor_name, type_tag, None))
+    }
+
+    fn get_pub_mutable_data(&self, xor_name: [u8; 32], type_tag: u64) -> ResultReturn<MutableData> {
+        Ok(MutableData(SAFENet::mdata_info(xor_name, type_tag, None)))
+    }
+
+    fn new_priv_mutable_data(
         &self,
         xor_name: [u8; 32],
         type_tag: u64,
+        enc_info: &str,
     ) -> ResultReturn<MutableData> {
-        // Create a public MutableData object
-        let md_info_pub = MDataInfo {
-            name: xor_name,
-            type_tag: type_tag,
-            has_enc_info: false,
-            enc_key: Default::default(),
-            enc_nonce: Default::default(),
-            has_new_enc_info: false,
-            new_enc_key: Default::default(),
-            new_enc_nonce: Default::default(),
-        };
-
-        Ok(MutableData(md_info_pub))
+        let enc = SAFENet::parse_enc_info(enc_info)?;
+        self.put_mutable_data(SAFENet::mdata_info(xor_name, type_tag, Some(enc)))
     }
 
-    #[allow(dead_code)]
-    pub fn new_priv_mutable_data(
+    fn get_priv_mutable_data(
         &self,
-        _xor_name: [u8; 32],
-        _type_tag: u64,
+        xor_name: [u8; 32],
+        type_tag: u64,
+        enc_info: &str,
     ) -> ResultReturn<MutableData> {
-        /*
-        let mut ctx = CallbackContext::new(self.safe_app);
-        let _ctx_ptr = &mut ctx as *mut _ as *mut c_void;
-        unsafe {
-            mdata_info_new_private(self.safe_app, &xor_name, type_tag, ctx_ptr, new_md_callback);
-        };
-        */
-        Ok(Default::default())
+        let enc = SAFENet::parse_enc_info(enc_info)?;
+        Ok(MutableData(SAFENet::mdata_info(
+            xor_name,
+            type_tag,
+            Some(enc),
+        )))
     }
 
-    pub fn mutable_data_get_value(&self, mdata: &MutableData, key: &str) -> ResultReturn<String> {
+    fn mutable_data_get_value(&self, mdata: &MutableData, key: &str) -> ResultReturn<String> {
         let app = self.safe_app.as_ref().unwrap();
         trace!("Getting entry with key {}", key);
-        match SAFENetHelpers::mdata_get(app, &mdata.0, key) {
+        let entry_key = SAFENet::encrypt_key(&mdata.0, key)?;
+        match SAFENetHelpers::mdata_get(app, &mdata.0, &entry_key) {
             Ok((value, version)) => {
-                let val = String::from_utf8(value).unwrap();
+                let val = SAFENet::decrypt_str(&mdata.0, &value)?;
                 trace!("Got entry (version {}) with value: {}", version, val);
                 Ok(val)
             }
             Err(error_code) => {
                 trace!("Entry not found with key {}", key);
+                // The MutableData may not exist at all, e.g. a SAFEthing never registered
+                let code = if error_code == ERR_NO_SUCH_DATA || error_code == ERR_NO_SUCH_ENTRY {
+                    ErrorCode::NoSuchData
+                } else {
+                    ErrorCode::NetworkErr
+                };
                 Err(Error::new(
-                    ErrorCode::NetworkErr,
+                    code,
                     format!(
                         "Failed to retrieve value from MutableData: {:?}",
                         error_code
@@ -358,7 +648,7 @@ impl SAFENet {
         }
     }
 
-    pub fn mutable_data_set_value(
+    fn mutable_data_set_value(
         &self,
         mdata: &MutableData,
         key: &str,
@@ -367,10 +657,13 @@ impl SAFENet {
         let app = self.safe_app.as_ref().unwrap();
         let mdata_actions_h: MDataEntryActionsHandle =
             unsafe { call_1(|ud, cb| mdata_entry_actions_new(app, ud, cb)).unwrap() };
+        // The entries of a private MutableData are stored encrypted
+        let entry_key = SAFENet::encrypt_key(&mdata.0, key)?;
+        let entry_value = SAFENet::encrypt_value(&mdata.0, value)?;
 
-        match SAFENetHelpers::mdata_get(app, &mdata.0, key) {
+        match SAFENetHelpers::mdata_get(app, &mdata.0, &entry_key) {
             Ok((v, version)) => {
-                let str: String = String::from_utf8(v).unwrap();
+                let str: String = SAFENet::decrypt_str(&mdata.0, &v)?;
                 trace!(
                     "Entry already exists: '{}' => '{}' (version {})",
                     key,
@@ -387,10 +680,10 @@ impl SAFENet {
                         mdata_entry_actions_update(
                             app,
                             mdata_actions_h,
-                            key.as_ptr(),
-                            key.len(),
-                            value.as_ptr(),
-                            value.len(),
+                            entry_key.as_ptr(),
+                            entry_key.len(),
+                            entry_value.as_ptr(),
+                            entry_value.len(),
                             version + 1,
                             ud,
                             cb,
@@ -407,10 +700,10 @@ impl SAFENet {
                             mdata_entry_actions_insert(
                                 app,
                                 mdata_actions_h,
-                                key.as_ptr(),
-                                key.len(),
-                                value.as_ptr(),
-                                value.len(),
+                                entry_key.as_ptr(),
+                                entry_key.len(),
+                                entry_value.as_ptr(),
+                                entry_value.len(),
                                 ud,
                                 cb,
                             )
@@ -438,26 +731,28 @@ impl SAFENet {
     }
 
     /// Retrieve the list of all entries from a MutableData
-    pub fn mutable_data_get_entries(
-        &self,
-        mdata: &MutableData,
-    ) -> ResultReturn<Vec<(String, String)>> {
+    fn mutable_data_get_entries(&self, mdata: &MutableData) -> ResultReturn<Vec<(String, String)>> {
         let app = self.safe_app.as_ref().unwrap();
         trace!("Getting entries from MutableData");
         match SAFENetHelpers::mdata_get_entries(app, &mdata.0) {
-            Ok(entries) => {
-                let entries_list = entries
-                    .iter()
-                    .map(|(key, value)| {
-                        let k = String::from_utf8(key.to_vec()).unwrap();
-                        let val = String::from_utf8(value.to_vec()).unwrap();
-                        trace!("Got entry with key {}: and value: {}", k, val);
-                        (k, val)
-                    })
-                    .collect();
-
-                Ok(entries_list)
-            }
+            Ok(entries) => Ok(entries
+                .iter()
+                .filter_map(|(key, value)| {
+                    // anyone can insert entries, thus those which cannot be decrypted are skipped
+                    let k = SAFENet::decrypt_str(&mdata.0, key);
+                    let val = SAFENet::decrypt_str(&mdata.0, value);
+                    match (k, val) {
+                        (Ok(k), Ok(val)) => {
+                            trace!("Got entry with key {}: and value: {}", k, val);
+                            Some((k, val))
+                        }
+                        (Err(err), _) | (_, Err(err)) => {
+                            debug!("Skipping entry which cannot be read: {}", err);
+                            None
+                        }
+                    }
+                })
+                .collect()),
             Err(error_code) => {
                 trace!("Failed to retrieve list of entries, error {}", error_code);
                 Err(Error::new(
@@ -474,7 +769,7 @@ impl SAFENet {
 
     // The following functions are mainly utilities for developers
     #[cfg(feature = "use-mock-routing")]
-    pub fn sim_net_disconnect(&mut self) {
+    fn sim_net_disconnect(&mut self) {
         if cfg!(not(feature = "fake-auth")) {
             let app: *mut App = self.safe_app.as_mut().unwrap();
             unsafe {
@@ -486,7 +781,7 @@ impl SAFENet {
     }
 
     #[cfg(not(feature = "use-mock-routing"))]
-    pub fn sim_net_disconnect(&mut self) {
+    fn sim_net_disconnect(&mut self) {
         panic!(
             "Function `sim_net_disconnect` is only available with `use-mock-routing` feature on"
         );
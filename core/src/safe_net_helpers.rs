@@ -61,7 +61,7 @@ pub fn decode_ipc_msg(ipc_msg: &str) -> Result<AuthGranted, IpcError> {
 }
 
 // Retrieve the value mapped to the provided key from a MutableData
-pub fn mdata_get(app: &App, mdata: &MDataInfo, key: &str) -> Result<(Vec<u8>, u64), i32> {
+pub fn mdata_get(app: &App, mdata: &MDataInfo, key: &[u8]) -> Result<(Vec<u8>, u64), i32> {
     extern "C" fn get_value_cb(
         user_data: *mut c_void,
         res: *const FfiResult,
@@ -0,0 +1,106 @@
+// Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+//
+// This file is part of the SAFEthing Framework.
+//
+// The SAFEthing Framework is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// The SAFEthing Framework is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::errors::ResultReturn;
+
+/// Storage backend the SAFEthing framework keeps all the SAFEthings' data on.
+/// Each SAFEthing is stored as a MutableData located at an XoR name, and all its
+/// information (attributes, topics, events, action requests, etc.) is kept as entries of it.
+/// The SAFE Network is the default backend (see `SAFENet`), but any other storage
+/// can be plugged in by implementing this trait.
+pub trait ThingStore: Sized + Send + 'static {
+    /// Handle to a MutableData stored on this backend
+    type MData: Clone + Default + Send;
+
+    /// Generate the authorisation credentials for the SAFEthing app,
+    /// used when no auth URI was provided by the application
+    fn gen_auth_request(thing_id: &str) -> ResultReturn<String>;
+
+    /// Connect to the backend using the provided app id and auth URI
+    fn connect(app_id: &str, auth_uri: &str) -> ResultReturn<Self>;
+
+    /// Get the public signing key of the app connected, hex encoded
+    fn pub_sign_key(&self) -> ResultReturn<String>;
+
+    /// Sign some data with the secret signing key of the app connected,
+    /// returning the signature hex encoded
+    fn sign(&self, data: &str) -> ResultReturn<String>;
+
+    /// Check if the signature of some data was made with the secret key
+    /// matching the public signing key provided, hex encoded
+    fn verify(&self, pub_sign_key: &str, data: &str, signature: &str) -> ResultReturn<bool>;
+
+    /// Get the public encryption key of the app connected, hex encoded.
+    /// The data sealed with it can only be opened by this app during this session
+    fn pub_enc_key(&self) -> ResultReturn<String>;
+
+    /// Encrypt some data so only the owner of the public encryption key provided,
+    /// hex encoded, can decrypt it, returning the ciphertext hex encoded
+    fn encrypt_sealed(&self, pub_enc_key: &str, data: &str) -> ResultReturn<String>;
+
+    /// Decrypt some data, hex encoded, which was sealed with the public
+    /// encryption key of the app connected
+    fn decrypt_sealed(&self, ciphertext: &str) -> ResultReturn<String>;
+
+    /// Generate the XoR name a string maps to
+    fn gen_xor_name(&self, in_str: &str) -> [u8; 32];
+
+    /// Create a new public MutableData, or just return it if it already exists
+    fn new_pub_mutable_data(&self, xor_name: [u8; 32], type_tag: u64) -> ResultReturn<Self::MData>;
+
+    /// Get a handle to an existing public MutableData
+    fn get_pub_mutable_data(&self, xor_name: [u8; 32], type_tag: u64) -> ResultReturn<Self::MData>;
+
+    /// Generate new encryption info for a private MutableData, hex encoded
+    fn gen_enc_info(&self) -> ResultReturn<String>;
+
+    /// Create a new private MutableData whose entries are encrypted with the
+    /// encryption info provided, or just return it if it already exists
+    fn new_priv_mutable_data(
+        &self,
+        xor_name: [u8; 32],
+        type_tag: u64,
+        enc_info: &str,
+    ) -> ResultReturn<Self::MData>;
+
+    /// Get a handle to an existing private MutableData, its entries are
+    /// decrypted with the encryption info provided
+    fn get_priv_mutable_data(
+        &self,
+        xor_name: [u8; 32],
+        type_tag: u64,
+        enc_info: &str,
+    ) -> ResultReturn<Self::MData>;
+
+    /// Retrieve the value mapped to the provided key from a MutableData,
+    /// it fails with `NoSuchData` if either the MutableData or the entry don't exist
+    fn mutable_data_get_value(&self, mdata: &Self::MData, key: &str) -> ResultReturn<String>;
+
+    /// Insert or update the value mapped to the provided key in a MutableData
+    fn mutable_data_set_value(
+        &self,
+        mdata: &Self::MData,
+        key: &str,
+        value: &str,
+    ) -> ResultReturn<()>;
+
+    /// Retrieve the list of all entries from a MutableData
+    fn mutable_data_get_entries(&self, mdata: &Self::MData) -> ResultReturn<Vec<(String, String)>>;
+
+    /// Only for testing, to simulate a network disconnection event
+    fn sim_net_disconnect(&mut self);
+}
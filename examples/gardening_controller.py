@@ -0,0 +1,76 @@
+# Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+#
+# This file is part of the SAFEthing Framework.
+#
+# The SAFEthing Framework is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# The SAFEthing Framework is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+# Python version of the gardening controller example (see core/examples/gardening_controller.rs)
+
+import os
+import sys
+import time
+
+sys.path.append(os.path.join(os.path.dirname(__file__), "..", "bindings", "python"))
+
+from safe_thing import FilterOperator, SAFEthing, ThingAttr  # noqa: E402
+
+GARDENING_DEVICE_ID = "gardening-device-serial-number-01010101"
+
+
+def handle_req_state_change(state, result, error):
+    print("The action request sent to open/close the water valve was reported to be in state: '{}'".format(state))
+    if error is not None:
+        print("The water valve couldn't be operated: {}".format(error))
+    elif result is not None:
+        print("The water valve was left with a pressure of {} psi".format(result))
+    return True
+
+
+def subscriptions_notif(safe_thing, thing_id, topic, data, timestamp):
+    print("New event: Notification received from thing_id: '{}', topic: '{}', data: '{}', timestamp: {}".format(
+        thing_id, topic, data, timestamp))
+
+    if topic == "moisture-level":
+        req_id = safe_thing.action_request(thing_id, "OpenValve", [60], handle_req_state_change)
+        print("Action request to OPEN the water valve sent, id: '{}'".format(req_id))
+    elif topic == "VeryWetAlarm":
+        req_id = safe_thing.action_request(thing_id, "CloseValve", [], handle_req_state_change)
+        print("Action request to CLOSE the water valve sent, id: '{}'".format(req_id))
+    else:
+        print("Unexpected notification received, topic: {}".format(topic))
+
+
+def main():
+    thing_id = "gardening-controller-device-serial-number-01010101"
+
+    attributes = [
+        ThingAttr("name", "SAFEthing Gardening Controller"),
+        ThingAttr("firmware", "v0.1.0"),
+        ThingAttr("status", "on", True),
+    ]
+
+    with SAFEthing(thing_id, "", subscriptions_notif) as safe_thing:
+        safe_thing.register(attributes)
+        print("Gardening controller device registered on the network")
+        safe_thing.publish()
+
+        safe_thing.subscribe_to_attr(GARDENING_DEVICE_ID, "moisture-level", FilterOperator.LESS_THAN, "5.0")
+        safe_thing.subscribe_to_topic(GARDENING_DEVICE_ID, "VeryWetAlarm")
+
+        # Let's just wait for any events
+        time.sleep(2000)
+
+
+if __name__ == "__main__":
+    main()
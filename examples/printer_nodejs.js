@@ -17,17 +17,51 @@
 
 const safeThingLib = require('../bindings/nodejs');
 
-let id = "thingA_id";
+let id = "printer-serial-number-01010101";
 
 let safeThing = safeThingLib.newSAFEthing(id);
-console.log("Thing A: ", safeThing);
+console.log("Printer instantiated: ", safeThing.thingId);
 
-let attrs = [{attr: "5", value: "67"}, {attr: "6", value: "68"}];
-let register_out = safeThing.register_thing(attrs);
-console.log("Register: ", register_out);
+safeThing.on('actionRequest', (requestId, thingId, action, args, reply) => {
+  console.log("Action request received from", thingId, ":", action, args);
+  const [data, copies] = args;
+  if (!data) {
+    return reply(new Error("Nothing to print"));
+  }
+  safeThing.setAttrValue("InkLevel", "Medium");
+  // let the requester know how many pages were printed, the request
+  // is set as "Done" by the framework once we return
+  reply(null, copies);
+});
 
-let publish_out = safeThing.publish_thing(id);
-console.log("Publish: ", publish_out);
+let attrs = [
+  {attr: "Model", value: "ArtPrinter"},
+  {attr: "Firmware", value: "v0.1.0"},
+  {attr: "InkLevel", value: "Full", isDynamic: true}
+];
+let topics = [
+  {name: "printRequested", access: safeThingLib.AccessType.All},
+  {name: "outOfInk", access: safeThingLib.AccessType.All}
+];
+let actions = [
+  {
+    name: "print",
+    access: safeThingLib.AccessType.All,
+    params: [
+      "data",
+      {name: "copies", type: {kind: safeThingLib.ValueKind.Number, min: 1, max: 10}, default: 1}
+    ],
+    output: {kind: safeThingLib.ValueKind.Number}
+  }
+];
+safeThing.register(attrs, topics, actions);
+safeThing.publish();
+console.log("Printer status: ", safeThing.status());
 
-// this is not needed
-safeThing.destroy();
+// Send an action request to itself and wait for it to be done
+safeThing.actionRequest(id, "print", ["Hello SAFEthing!"], (state) => console.log("Print state:", state))
+  .then(({requestId, result}) => {
+    console.log("Print request done:", requestId, ", pages printed:", result);
+    safeThing.destroy();
+  })
+  .catch((err) => console.error("Print request failed:", err));
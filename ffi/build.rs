@@ -0,0 +1,28 @@
+// Copyright 2017-2019 Gabriel Viganotti <@bochaco>.
+//
+// This file is part of the SAFEthing Framework.
+//
+// The SAFEthing Framework is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// The SAFEthing Framework is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+
+// Generate the C header file for the SAFEthing C interface
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file("cbindgen.toml").unwrap();
+
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Unable to generate C header file")
+        .write_to_file("include/safe_thing.h");
+}
@@ -0,0 +1,513 @@
+/* C interface of the SAFEthing Framework, see safe_thing_ffi/src/lib.rs for details. */
+
+#ifndef SAFE_THING_H
+#define SAFE_THING_H
+
+typedef struct SAFEthing SAFEthing;
+
+/* Generated with cbindgen:0.8.7 */
+
+/* Warning, this file is autogenerated by cbindgen. Don't modify this manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define SAFE_THING_ACCESS_ALL 3
+
+#define SAFE_THING_ACCESS_GROUP 2
+
+#define SAFE_THING_ACCESS_OWNER 1
+
+#define SAFE_THING_ACCESS_THING 0
+
+#define SAFE_THING_ERR_ACCESS_DENIED -10
+
+#define SAFE_THING_ERR_ATTR_NOT_DYNAMIC -7
+
+#define SAFE_THING_ERR_ATTR_NOT_FOUND -6
+
+#define SAFE_THING_ERR_CONNECTION -2
+
+#define SAFE_THING_ERR_INVALID_ARGUMENT -1
+
+#define SAFE_THING_ERR_NETWORK -3
+
+#define SAFE_THING_ERR_NO_SUCH_DATA -9
+
+#define SAFE_THING_ERR_THING_NOT_PUBLISHED -4
+
+#define SAFE_THING_ERR_TIMEOUT -8
+
+#define SAFE_THING_ERR_TOPIC_NOT_FOUND -5
+
+#define SAFE_THING_ERR_UNEXPECTED -1000
+
+#define SAFE_THING_FILTER_ANY 0
+
+#define SAFE_THING_FILTER_EQUAL 1
+
+#define SAFE_THING_FILTER_GREATER_THAN 4
+
+#define SAFE_THING_FILTER_LESS_THAN 3
+
+#define SAFE_THING_FILTER_NOT_EQUAL 2
+
+#define SAFE_THING_OK 0
+
+#define SAFE_THING_STATUS_CONNECTED 2
+
+#define SAFE_THING_STATUS_DISABLED 4
+
+#define SAFE_THING_STATUS_NON_CONNECTED 1
+
+#define SAFE_THING_STATUS_PUBLISHED 3
+
+#define SAFE_THING_STATUS_UNKNOWN 0
+
+#define SAFE_THING_VALUE_BOOL 1
+
+#define SAFE_THING_VALUE_ENUM 3
+
+#define SAFE_THING_VALUE_NUMBER 0
+
+#define SAFE_THING_VALUE_STRING 2
+
+/**
+ * Result returned by all the functions, `0` means success
+ */
+typedef int32_t FfiResult;
+
+/**
+ * Opaque handle to a SAFEthing instance
+ */
+typedef SAFEthing *SAFEthingHandle;
+
+/**
+ * Callback invoked upon a change in the state of an action request sent, along with the
+ * JSON encoded output of the action and the error reported, either of them can be NULL.
+ * It shall return `true` to keep receiving notifications for the action request.
+ */
+typedef bool (*ActionReqStateCb)(void *user_data, const char *state, const char *result, const char *error);
+
+/**
+ * Type of a value, `kind` is one of the `SAFE_THING_VALUE_*` values.
+ * The range is only used for numbers, and the options only for enums.
+ */
+typedef struct {
+  int32_t kind;
+  bool has_min;
+  double min;
+  bool has_max;
+  double max;
+  const char *const *options;
+  uintptr_t options_len;
+} FfiValueType;
+
+/**
+ * Parameter of an action, `default_value` is NULL if the parameter has no default value
+ */
+typedef struct {
+  const char *name;
+  FfiValueType value_type;
+  const char *default_value;
+} FfiActionParam;
+
+/**
+ * Action exposed by a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values.
+ * The `output` is NULL if the action doesn't return any value.
+ * The `groups` attached restrict the Group access to their members.
+ */
+typedef struct {
+  const char *name;
+  int32_t access;
+  const FfiActionParam *params;
+  uintptr_t params_len;
+  const FfiValueType *output;
+  const char *const *groups;
+  uintptr_t groups_len;
+} FfiActionDef;
+
+/**
+ * Attribute of a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values
+ */
+typedef struct {
+  const char *attr;
+  const char *value;
+  bool is_dynamic;
+  int32_t access;
+} FfiThingAttr;
+
+/**
+ * Group of a SAFEthing, its members are IDs of SAFEthings or public signing keys
+ */
+typedef struct {
+  const char *name;
+  const char *const *members;
+  uintptr_t members_len;
+} FfiGroup;
+
+/**
+ * Topic exposed by a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values.
+ * The `groups` attached restrict the Group access to their members.
+ */
+typedef struct {
+  const char *name;
+  int32_t access;
+  const char *const *groups;
+  uintptr_t groups_len;
+} FfiTopic;
+
+/**
+ * Callback invoked upon a notification for any of the subscriptions made by the SAFEthing.
+ * The `handle` is the one returned by `safe_thing_new`.
+ */
+typedef void (*SubsNotifCb)(void *user_data, SAFEthingHandle handle, const char *thing_id, const char *topic, const char *data, uint64_t timestamp);
+
+/**
+ * Callback invoked when an action request is received by the SAFEthing.
+ * The `handle` is the one returned by `safe_thing_new`, and the outcome of the action
+ * can be set with `safe_thing_action_request_reply`, from any thread, during the invocation
+ * of the callback.
+ * The `thing_id` of the requester was verified against the signature of the request.
+ */
+typedef void (*ActionReqCb)(void *user_data, SAFEthingHandle handle, uint64_t request_id, const char *thing_id, const char *action, const char *const *args, uintptr_t args_len);
+
+/**
+ * Callback invoked by `safe_thing_poll_events` upon a change in the state of an action request
+ * sent with `safe_thing_action_request_polled`, along with the JSON encoded output of the
+ * action and the error reported, either of them can be NULL.
+ */
+typedef void (*PolledActionReqStateCb)(void *user_data, uint64_t request_id, const char *state, const char *result, const char *error);
+
+/**
+ * Send an action request to a SAFEthing, its id is returned in `o_request_id`.
+ * The `state_cb` is invoked with `user_data` upon changes in the state of the request.
+ */
+FfiResult safe_thing_action_request(SAFEthingHandle handle,
+                                    const char *thing_id,
+                                    const char *action,
+                                    const char *const *args,
+                                    uintptr_t args_len,
+                                    void *user_data,
+                                    ActionReqStateCb state_cb,
+                                    uint64_t *o_request_id);
+
+/**
+ * Send an action request from a SAFEthing created with `safe_thing_new_polled`, its id is
+ * returned in `o_request_id`. The changes in its state are queued until they are polled
+ * with `safe_thing_poll_events`.
+ */
+FfiResult safe_thing_action_request_polled(SAFEthingHandle handle,
+                                           const char *thing_id,
+                                           const char *action,
+                                           const char *const *args,
+                                           uintptr_t args_len,
+                                           uint64_t *o_request_id);
+
+/**
+ * Set the outcome of the action request being handled, either its output (which can be NULL)
+ * or an error if it's not NULL. It can be called from any thread, but only while the action
+ * request callback is being invoked. For the SAFEthings created with `safe_thing_new_polled`
+ * it's called once, at any time after the action request was polled, and the framework
+ * doesn't process other action requests until then.
+ */
+FfiResult safe_thing_action_request_reply(SAFEthingHandle handle,
+                                          uint64_t request_id,
+                                          const char *result,
+                                          const char *error);
+
+/**
+ * Add a member to a group, either the ID of a SAFEthing or a public signing key
+ */
+FfiResult safe_thing_add_group_member(SAFEthingHandle handle,
+                                      const char *group,
+                                      const char *member);
+
+/**
+ * Authorise a public signing key to access the topics and actions of the SAFEthing,
+ * `access` is either `SAFE_THING_ACCESS_OWNER` or `SAFE_THING_ACCESS_GROUP`
+ */
+FfiResult safe_thing_authorise(SAFEthingHandle handle, const char *sign_key, int32_t access);
+
+/**
+ * Claim the ownership of a SAFEthing with the one-time code printed on it,
+ * it blocks until the claim is accepted or rejected
+ */
+FfiResult safe_thing_claim(SAFEthingHandle handle, const char *thing_id, const char *code);
+
+/**
+ * Create a named group, its members are granted Group access to the topics
+ * and actions it's attached to
+ */
+FfiResult safe_thing_create_group(SAFEthingHandle handle, const char *group);
+
+/**
+ * Free the SAFEthing instance, stopping its background workers
+ */
+void safe_thing_delete(SAFEthingHandle handle);
+
+/**
+ * Delete a group of the SAFEthing
+ */
+FfiResult safe_thing_delete_group(SAFEthingHandle handle, const char *group);
+
+/**
+ * Disable the SAFEthing, it rejects new action requests and doesn't notify events
+ */
+FfiResult safe_thing_disable(SAFEthingHandle handle);
+
+/**
+ * Enable a disabled SAFEthing, restoring the status it had before it was disabled
+ */
+FfiResult safe_thing_enable(SAFEthingHandle handle);
+
+/**
+ * Request the encryption info of a private SAFEthing, it's only sent if this SAFEthing is
+ * authorised by it. It blocks until the request is processed, the encryption info is then
+ * set to access it and passed to the callback so it can be kept.
+ */
+FfiResult safe_thing_fetch_thing_enc_info(SAFEthingHandle handle,
+                                          const char *thing_id,
+                                          void *user_data,
+                                          void (*o_cb)(void *user_data, const char *enc_info));
+
+/**
+ * Generate new encryption info to register a private SAFEthing with
+ */
+FfiResult safe_thing_gen_enc_info(SAFEthingHandle handle,
+                                  void *user_data,
+                                  void (*o_cb)(void *user_data, const char *enc_info));
+
+/**
+ * Get the current state of an action request sent to a SAFEthing
+ */
+FfiResult safe_thing_get_action_request_state(SAFEthingHandle handle,
+                                              const char *thing_id,
+                                              uint64_t request_id,
+                                              void *user_data,
+                                              void (*o_cb)(void *user_data, const char *state));
+
+/**
+ * Get the list of actions supported by a SAFEthing
+ */
+FfiResult safe_thing_get_thing_actions(SAFEthingHandle handle,
+                                       const char *thing_id,
+                                       void *user_data,
+                                       void (*o_cb)(void *user_data, const FfiActionDef *actions, uintptr_t actions_len));
+
+/**
+ * Get the list of attributes of a SAFEthing
+ */
+FfiResult safe_thing_get_thing_attrs(SAFEthingHandle handle,
+                                     const char *thing_id,
+                                     void *user_data,
+                                     void (*o_cb)(void *user_data, const FfiThingAttr *attrs, uintptr_t attrs_len));
+
+/**
+ * Get the encryption info known for a private SAFEthing, the callback
+ * receives a null pointer if it's not known
+ */
+FfiResult safe_thing_get_thing_enc_info(SAFEthingHandle handle,
+                                        const char *thing_id,
+                                        void *user_data,
+                                        void (*o_cb)(void *user_data, const char *enc_info));
+
+/**
+ * Get the groups of a SAFEthing along with their members
+ */
+FfiResult safe_thing_get_thing_groups(SAFEthingHandle handle,
+                                      const char *thing_id,
+                                      void *user_data,
+                                      void (*o_cb)(void *user_data, const FfiGroup *groups, uintptr_t groups_len));
+
+/**
+ * Get the public signing key of the owner of a SAFEthing, the callback
+ * receives a null pointer if it wasn't claimed
+ */
+FfiResult safe_thing_get_thing_owner(SAFEthingHandle handle,
+                                     const char *thing_id,
+                                     void *user_data,
+                                     void (*o_cb)(void *user_data, const char *owner_key));
+
+/**
+ * Get the status of any SAFEthing, it's `SAFE_THING_STATUS_NON_CONNECTED` if it was never registered
+ */
+FfiResult safe_thing_get_thing_status(SAFEthingHandle handle,
+                                      const char *thing_id,
+                                      int32_t *o_status);
+
+/**
+ * Get the list of topics supported by a SAFEthing
+ */
+FfiResult safe_thing_get_thing_topics(SAFEthingHandle handle,
+                                      const char *thing_id,
+                                      void *user_data,
+                                      void (*o_cb)(void *user_data, const FfiTopic *topics, uintptr_t topics_len));
+
+/**
+ * Get the description of the last error returned by a function in the current thread,
+ * or NULL if there was none. The string is valid until the next function call which fails.
+ */
+const char *safe_thing_last_error_message(void);
+
+/**
+ * Create a new SAFEthing instance, the handle is returned in `o_handle`.
+ * The `user_data` pointer is passed as is to the callbacks.
+ */
+FfiResult safe_thing_new(const char *thing_id,
+                         const char *auth_uri,
+                         void *user_data,
+                         SubsNotifCb notifs_cb,
+                         ActionReqCb action_req_cb,
+                         SAFEthingHandle *o_handle);
+
+/**
+ * Create a new SAFEthing instance whose notifications and action requests are queued
+ * until they are polled with `safe_thing_poll_events`, the handle is returned in `o_handle`
+ */
+FfiResult safe_thing_new_polled(const char *thing_id,
+                                const char *auth_uri,
+                                SAFEthingHandle *o_handle);
+
+/**
+ * Notify of an event associated to a topic
+ */
+FfiResult safe_thing_notify(SAFEthingHandle handle, const char *topic, const char *data);
+
+/**
+ * Invoke the callbacks with the events queued for a SAFEthing created with
+ * `safe_thing_new_polled`, it doesn't wait for new ones. The callbacks are invoked
+ * from the calling thread before the function returns.
+ * The outcome of the action requests is set afterwards with `safe_thing_action_request_reply`.
+ */
+FfiResult safe_thing_poll_events(SAFEthingHandle handle,
+                                 void *user_data,
+                                 SubsNotifCb notifs_cb,
+                                 ActionReqCb action_req_cb,
+                                 PolledActionReqStateCb state_cb);
+
+/**
+ * Publish the SAFEthing making it available and operative in the network
+ */
+FfiResult safe_thing_publish(SAFEthingHandle handle);
+
+/**
+ * Register the SAFEthing on the network with its attributes, topics and actions
+ */
+FfiResult safe_thing_register(SAFEthingHandle handle,
+                              const FfiThingAttr *attrs,
+                              uintptr_t attrs_len,
+                              const FfiTopic *topics,
+                              uintptr_t topics_len,
+                              const FfiActionDef *actions,
+                              uintptr_t actions_len);
+
+/**
+ * Register the SAFEthing on the network as a private and encrypted entity,
+ * `enc_info` is generated with `safe_thing_gen_enc_info`
+ */
+FfiResult safe_thing_register_private(SAFEthingHandle handle,
+                                      const FfiThingAttr *attrs,
+                                      uintptr_t attrs_len,
+                                      const FfiTopic *topics,
+                                      uintptr_t topics_len,
+                                      const FfiActionDef *actions,
+                                      uintptr_t actions_len,
+                                      const char *enc_info);
+
+/**
+ * Remove a member from a group
+ */
+FfiResult safe_thing_remove_group_member(SAFEthingHandle handle,
+                                         const char *group,
+                                         const char *member);
+
+/**
+ * Revoke the access previously authorised to a public signing key
+ */
+FfiResult safe_thing_revoke(SAFEthingHandle handle, const char *sign_key);
+
+/**
+ * Set a new value for an attribute of the SAFEthing, or add it if it didn't exist
+ */
+FfiResult safe_thing_set_attr_value(SAFEthingHandle handle, const char *attr, const char *value);
+
+/**
+ * Set the one-time code printed on the device, its owner provides it to claim the SAFEthing
+ */
+FfiResult safe_thing_set_claim_code(SAFEthingHandle handle, const char *code);
+
+/**
+ * Set the encryption info needed to access a private SAFEthing
+ */
+FfiResult safe_thing_set_thing_enc_info(SAFEthingHandle handle,
+                                        const char *thing_id,
+                                        const char *enc_info);
+
+/**
+ * Stop the background workers of the SAFEthing, optionally setting its status to Disabled.
+ * The SAFEthing can be registered again afterwards to resume its operation.
+ */
+FfiResult safe_thing_shutdown(SAFEthingHandle handle, bool disable);
+
+/**
+ * Get the public signing key the SAFEthing is identified with, hex encoded
+ */
+FfiResult safe_thing_sign_key(SAFEthingHandle handle,
+                              void *user_data,
+                              void (*o_cb)(void *user_data, const char *sign_key));
+
+/**
+ * Get the status of the SAFEthing, one of the `SAFE_THING_STATUS_*` values
+ */
+FfiResult safe_thing_status(SAFEthingHandle handle, int32_t *o_status);
+
+/**
+ * Subscribe to a dynamic attribute published by a SAFEthing, `filter_op` is one of
+ * the `SAFE_THING_FILTER_*` values
+ */
+FfiResult safe_thing_subscribe_to_attr(SAFEthingHandle handle,
+                                       const char *thing_id,
+                                       const char *attr,
+                                       int32_t filter_op,
+                                       const char *filter_value);
+
+/**
+ * Subscribe to a topic published by a SAFEthing, `filter_op` is one of
+ * the `SAFE_THING_FILTER_*` values
+ */
+FfiResult safe_thing_subscribe_to_topic(SAFEthingHandle handle,
+                                        const char *thing_id,
+                                        const char *topic,
+                                        int32_t filter_op,
+                                        const char *filter_value);
+
+/**
+ * Unregister the SAFEthing, clearing its attributes, topics and actions
+ */
+FfiResult safe_thing_unregister(SAFEthingHandle handle);
+
+/**
+ * Update the state of an action request received by the SAFEthing
+ */
+FfiResult safe_thing_update_action_request_state(SAFEthingHandle handle,
+                                                 uint64_t request_id,
+                                                 const char *new_state);
+
+/**
+ * Block until an action request sent to a SAFEthing reaches a final state, polling its
+ * state every `poll_interval_ms`, and pass it to `o_cb` along with its JSON encoded output
+ * and the error reported, either of them can be NULL.
+ * It fails with `SAFE_THING_ERR_TIMEOUT` if it's not final after `timeout_ms`.
+ */
+FfiResult safe_thing_wait_action_request(SAFEthingHandle handle,
+                                         const char *thing_id,
+                                         uint64_t request_id,
+                                         uint64_t poll_interval_ms,
+                                         uint64_t timeout_ms,
+                                         void *user_data,
+                                         void (*o_cb)(void *user_data, const char *state, const char *result, const char *error));
+
+#endif /* SAFE_THING_H */
@@ -15,75 +15,1667 @@
 // You should have received a copy of the GNU General Public License
 // along with the SAFEthing Framework. If not, see <https://www.gnu.org/licenses/>.
 
-extern crate safe_thing;
+//! C interface of the SAFEthing framework.
+//!
+//! All functions return `0` upon success, or one of the negative `SAFE_THING_ERR_*` codes
+//! otherwise, in which case `safe_thing_last_error_message` provides a description of it.
+//! Lists and strings are passed to the caller through an output callback which is
+//! invoked before the function returns, thus they are only valid during such invocation.
+//! Timestamps and action request ids are passed as `uint64_t`.
+//!
+//! The arguments of an action request received are passed as strings, already validated and
+//! coerced according to the types of the action's parameters, e.g. numbers as `"60"` and
+//! booleans as `"true"`. The output of an action is passed to the requester JSON encoded.
+//!
+//! All pointers provided by the caller must be either null or valid, and the strings
+//! must be NUL terminated. Handles must have been created with `safe_thing_new` or
+//! `safe_thing_new_polled`.
+//!
+//! The callbacks passed to `safe_thing_new` and `safe_thing_action_request` are invoked from
+//! the framework's threads. Applications which can only run code on their own thread, e.g.
+//! a JavaScript engine, create the SAFEthing with `safe_thing_new_polled` instead, and get
+//! the events from their own loop with `safe_thing_poll_events`.
 
-use safe_thing::{SAFEthing, ThingAttr};
-use std::slice;
-use std::ffi::CStr;
-use std::os::raw::c_char;
+#![allow(clippy::missing_safety_doc)]
 
+use lazy_static::lazy_static;
+use safe_thing::{
+    AccessType, ActionDef, ActionParam, ActionReqReply, ActionResult, ActionValue, Error,
+    ErrorCode, FilterOperator, SAFEthing, Status, ThingAttr, ThingEvent, Topic, ValueType,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{ptr, slice};
+
+/// Opaque handle to a SAFEthing instance
 pub type SAFEthingHandle = *mut SAFEthing;
 
-pub type ErrorCode = i32;
+/// Result returned by all the functions, `0` means success
+pub type FfiResult = i32;
+
+// Error codes, they are stable and new ones are only appended
+pub const SAFE_THING_OK: i32 = 0;
+pub const SAFE_THING_ERR_INVALID_ARGUMENT: i32 = -1;
+pub const SAFE_THING_ERR_CONNECTION: i32 = -2;
+pub const SAFE_THING_ERR_NETWORK: i32 = -3;
+pub const SAFE_THING_ERR_THING_NOT_PUBLISHED: i32 = -4;
+pub const SAFE_THING_ERR_TOPIC_NOT_FOUND: i32 = -5;
+pub const SAFE_THING_ERR_ATTR_NOT_FOUND: i32 = -6;
+pub const SAFE_THING_ERR_ATTR_NOT_DYNAMIC: i32 = -7;
+pub const SAFE_THING_ERR_TIMEOUT: i32 = -8;
+pub const SAFE_THING_ERR_NO_SUCH_DATA: i32 = -9;
+pub const SAFE_THING_ERR_ACCESS_DENIED: i32 = -10;
+pub const SAFE_THING_ERR_UNEXPECTED: i32 = -1000;
+
+// Access types for topics and actions
+pub const SAFE_THING_ACCESS_THING: i32 = 0;
+pub const SAFE_THING_ACCESS_OWNER: i32 = 1;
+pub const SAFE_THING_ACCESS_GROUP: i32 = 2;
+pub const SAFE_THING_ACCESS_ALL: i32 = 3;
+
+// Status of a SAFEthing
+pub const SAFE_THING_STATUS_UNKNOWN: i32 = 0;
+pub const SAFE_THING_STATUS_NON_CONNECTED: i32 = 1;
+pub const SAFE_THING_STATUS_CONNECTED: i32 = 2;
+pub const SAFE_THING_STATUS_PUBLISHED: i32 = 3;
+pub const SAFE_THING_STATUS_DISABLED: i32 = 4;
+
+// Filter operators for subscriptions
+pub const SAFE_THING_FILTER_ANY: i32 = 0;
+pub const SAFE_THING_FILTER_EQUAL: i32 = 1;
+pub const SAFE_THING_FILTER_NOT_EQUAL: i32 = 2;
+pub const SAFE_THING_FILTER_LESS_THAN: i32 = 3;
+pub const SAFE_THING_FILTER_GREATER_THAN: i32 = 4;
 
-#[derive(Clone, Debug)]
+// Types of the values of action parameters and outputs
+pub const SAFE_THING_VALUE_NUMBER: i32 = 0;
+pub const SAFE_THING_VALUE_BOOL: i32 = 1;
+pub const SAFE_THING_VALUE_STRING: i32 = 2;
+pub const SAFE_THING_VALUE_ENUM: i32 = 3;
+
+/// Attribute of a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values
+#[repr(C)]
 pub struct FfiThingAttr {
+    pub attr: *const c_char,
+    pub value: *const c_char,
+    pub is_dynamic: bool,
+    pub access: i32,
+}
+
+/// Topic exposed by a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values.
+/// The `groups` attached restrict the Group access to their members.
+#[repr(C)]
+pub struct FfiTopic {
+    pub name: *const c_char,
+    pub access: i32,
+    pub groups: *const *const c_char,
+    pub groups_len: usize,
+}
+
+/// Type of a value, `kind` is one of the `SAFE_THING_VALUE_*` values.
+/// The range is only used for numbers, and the options only for enums.
+#[repr(C)]
+pub struct FfiValueType {
+    pub kind: i32,
+    pub has_min: bool,
+    pub min: f64,
+    pub has_max: bool,
+    pub max: f64,
+    pub options: *const *const c_char,
+    pub options_len: usize,
+}
+
+/// Parameter of an action, `default_value` is NULL if the parameter has no default value
+#[repr(C)]
+pub struct FfiActionParam {
+    pub name: *const c_char,
+    pub value_type: FfiValueType,
+    pub default_value: *const c_char,
+}
+
+/// Action exposed by a SAFEthing, `access` is one of the `SAFE_THING_ACCESS_*` values.
+/// The `output` is NULL if the action doesn't return any value.
+/// The `groups` attached restrict the Group access to their members.
+#[repr(C)]
+pub struct FfiActionDef {
+    pub name: *const c_char,
+    pub access: i32,
+    pub params: *const FfiActionParam,
+    pub params_len: usize,
+    pub output: *const FfiValueType,
+    pub groups: *const *const c_char,
+    pub groups_len: usize,
+}
+
+/// Group of a SAFEthing, its members are IDs of SAFEthings or public signing keys
+#[repr(C)]
+pub struct FfiGroup {
+    pub name: *const c_char,
+    pub members: *const *const c_char,
+    pub members_len: usize,
+}
+
+/// Callback invoked upon a notification for any of the subscriptions made by the SAFEthing.
+/// The `handle` is the one returned by `safe_thing_new`.
+pub type SubsNotifCb = extern "C" fn(
+    user_data: *mut c_void,
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    topic: *const c_char,
+    data: *const c_char,
+    timestamp: u64,
+);
+
+/// Callback invoked when an action request is received by the SAFEthing.
+/// The `handle` is the one returned by `safe_thing_new`, and the outcome of the action
+/// can be set with `safe_thing_action_request_reply`, from any thread, during the invocation
+/// of the callback.
+/// The `thing_id` of the requester was verified against the signature of the request.
+pub type ActionReqCb = extern "C" fn(
+    user_data: *mut c_void,
+    handle: SAFEthingHandle,
+    request_id: u64,
+    thing_id: *const c_char,
+    action: *const c_char,
+    args: *const *const c_char,
+    args_len: usize,
+);
+
+/// Callback invoked upon a change in the state of an action request sent, along with the
+/// JSON encoded output of the action and the error reported, either of them can be NULL.
+/// It shall return `true` to keep receiving notifications for the action request.
+pub type ActionReqStateCb = extern "C" fn(
+    user_data: *mut c_void,
+    state: *const c_char,
+    result: *const c_char,
+    error: *const c_char,
+) -> bool;
+
+/// Callback invoked by `safe_thing_poll_events` upon a change in the state of an action request
+/// sent with `safe_thing_action_request_polled`, along with the JSON encoded output of the
+/// action and the error reported, either of them can be NULL.
+pub type PolledActionReqStateCb = extern "C" fn(
+    user_data: *mut c_void,
+    request_id: u64,
+    state: *const c_char,
+    result: *const c_char,
+    error: *const c_char,
+);
+
+// The user data pointer is opaque for us, the caller is responsible for
+// making it safe to be used from the framework's threads
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Error reported to the caller, its message is kept as the last error of the thread
+#[derive(Debug, PartialEq)]
+struct FfiError {
+    code: FfiResult,
+    message: String,
+}
+
+impl FfiError {
+    fn invalid_arg(message: &str) -> FfiError {
+        FfiError {
+            code: SAFE_THING_ERR_INVALID_ARGUMENT,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for FfiError {
+    fn from(err: Error) -> FfiError {
+        let code = match err.code() {
+            ErrorCode::InvalidArgument => SAFE_THING_ERR_INVALID_ARGUMENT,
+            ErrorCode::ConnectionErr => SAFE_THING_ERR_CONNECTION,
+            ErrorCode::NetworkErr => SAFE_THING_ERR_NETWORK,
+            ErrorCode::ThingNotPublished => SAFE_THING_ERR_THING_NOT_PUBLISHED,
+            ErrorCode::TopicNotFound => SAFE_THING_ERR_TOPIC_NOT_FOUND,
+            ErrorCode::AttrNotFound => SAFE_THING_ERR_ATTR_NOT_FOUND,
+            ErrorCode::AttrNotDynamic => SAFE_THING_ERR_ATTR_NOT_DYNAMIC,
+            ErrorCode::Timeout => SAFE_THING_ERR_TIMEOUT,
+            ErrorCode::NoSuchData => SAFE_THING_ERR_NO_SUCH_DATA,
+            ErrorCode::AccessDenied => SAFE_THING_ERR_ACCESS_DENIED,
+        };
+        FfiError {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = Default::default();
+}
+
+lazy_static! {
+    // Outcome of the action requests being handled by the callbacks, by SAFEthing handle and
+    // request ID, so it can be set from another thread, e.g. if the callback is marshalled
+    // to the main thread of the application
+    static ref ACTION_REPLIES: Mutex<HashMap<(usize, u64), ActionResult>> =
+        Mutex::new(HashMap::new());
+
+    // Events of the SAFEthings created with `safe_thing_new_polled`, by SAFEthing handle
+    static ref POLLED_EVENTS: Mutex<HashMap<usize, Arc<PolledEvents>>> =
+        Mutex::new(HashMap::new());
+}
+
+// Change in the state of an action request sent, its ID is only set once it was sent,
+// which is always the case by the time it's polled
+struct ActionReqStateEvent {
+    request_id: Arc<AtomicU64>,
+    state: String,
+    result: Option<CString>,
+    error: Option<CString>,
+}
+
+// Events queued for a SAFEthing until they are polled, and the action requests polled
+// which weren't replied yet
+struct PolledEvents {
+    events: Mutex<Receiver<ThingEvent>>,
+    states_tx: Mutex<Sender<ActionReqStateEvent>>,
+    states: Mutex<Receiver<ActionReqStateEvent>>,
+    replies: Mutex<HashMap<u64, ActionReqReply>>,
+}
+
+/// Get the description of the last error returned by a function in the current thread,
+/// or NULL if there was none. The string is valid until the next function call which fails.
+#[no_mangle]
+pub extern "C" fn safe_thing_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| match *last_error.borrow() {
+        Some(ref message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Create a new SAFEthing instance, the handle is returned in `o_handle`.
+/// The `user_data` pointer is passed as is to the callbacks.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_new(
+    thing_id: *const c_char,
+    auth_uri: *const c_char,
+    user_data: *mut c_void,
+    notifs_cb: SubsNotifCb,
+    action_req_cb: ActionReqCb,
+    o_handle: *mut SAFEthingHandle,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let auth_uri = from_c_str(auth_uri)?;
+        check_out_ptr(o_handle)?;
+        let user_data = UserData(user_data);
+        // The handle passed to the callbacks is only known once the instance is boxed
+        let handle_cell: Arc<AtomicPtr<SAFEthing>> = Arc::default();
+        let notifs_handle = Arc::clone(&handle_cell);
+        let action_req_handle = Arc::clone(&handle_cell);
+
+        let notifs_closure =
+            move |_: &SAFEthing, thing_id: &str, topic: &str, data: &str, timestamp: u128| {
+                let thing_id = to_c_string(thing_id);
+                let topic = to_c_string(topic);
+                let data = to_c_string(data);
+                notifs_cb(
+                    user_data.0,
+                    notifs_handle.load(Ordering::SeqCst),
+                    thing_id.as_ptr(),
+                    topic.as_ptr(),
+                    data.as_ptr(),
+                    timestamp as u64,
+                );
+            };
+
+        let action_req_closure = move |_: &SAFEthing,
+                                       request_id: u128,
+                                       thing_id: &str,
+                                       action: &str,
+                                       args: &[ActionValue]| {
+            let thing_id = to_c_string(thing_id);
+            let action = to_c_string(action);
+            let args: Vec<CString> = args
+                .iter()
+                .map(|arg| to_c_string(&arg.to_string()))
+                .collect();
+            let args_ptrs: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+            let request_id = request_id as u64;
+            let handle = action_req_handle.load(Ordering::SeqCst);
+            let reply_key = (handle as usize, request_id);
+            let _ = ACTION_REPLIES.lock().unwrap().insert(reply_key, Ok(None));
+            action_req_cb(
+                user_data.0,
+                handle,
+                request_id,
+                thing_id.as_ptr(),
+                action.as_ptr(),
+                args_ptrs.as_ptr(),
+                args_ptrs.len(),
+            );
+            ACTION_REPLIES
+                .lock()
+                .unwrap()
+                .remove(&reply_key)
+                .unwrap_or(Ok(None))
+        };
+
+        let safe_thing = SAFEthing::new(&thing_id, &auth_uri, notifs_closure, action_req_closure)?;
+
+        let handle = Box::into_raw(Box::new(safe_thing));
+        handle_cell.store(handle, Ordering::SeqCst);
+        *o_handle = handle;
+        Ok(())
+    })
+}
+
+/// Create a new SAFEthing instance whose notifications and action requests are queued
+/// until they are polled with `safe_thing_poll_events`, the handle is returned in `o_handle`
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_new_polled(
+    thing_id: *const c_char,
+    auth_uri: *const c_char,
+    o_handle: *mut SAFEthingHandle,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let auth_uri = from_c_str(auth_uri)?;
+        check_out_ptr(o_handle)?;
+        let (safe_thing, events) = SAFEthing::with_events(&thing_id, &auth_uri)?;
+        let (states_tx, states) = mpsc::channel();
+        let polled_events = PolledEvents {
+            events: Mutex::new(events),
+            states_tx: Mutex::new(states_tx),
+            states: Mutex::new(states),
+            replies: Mutex::default(),
+        };
+
+        let handle = Box::into_raw(Box::new(safe_thing));
+        let _ = POLLED_EVENTS
+            .lock()
+            .unwrap()
+            .insert(handle as usize, Arc::new(polled_events));
+        *o_handle = handle;
+        Ok(())
+    })
+}
+
+/// Invoke the callbacks with the events queued for a SAFEthing created with
+/// `safe_thing_new_polled`, it doesn't wait for new ones. The callbacks are invoked
+/// from the calling thread before the function returns.
+/// The outcome of the action requests is set afterwards with `safe_thing_action_request_reply`.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_poll_events(
+    handle: SAFEthingHandle,
+    user_data: *mut c_void,
+    notifs_cb: SubsNotifCb,
+    action_req_cb: ActionReqCb,
+    state_cb: PolledActionReqStateCb,
+) -> FfiResult {
+    ffi_call(|| {
+        let polled_events = polled_events(handle)?;
+        // The lock is released before invoking each callback, as they can call any function
+        loop {
+            let event = polled_events.events.lock().unwrap().try_recv();
+            match event {
+                Ok(ThingEvent::Notification {
+                    thing_id,
+                    topic,
+                    data,
+                    timestamp,
+                }) => {
+                    let thing_id = to_c_string(&thing_id);
+                    let topic = to_c_string(&topic);
+                    let data = to_c_string(&data);
+                    notifs_cb(
+                        user_data,
+                        handle,
+                        thing_id.as_ptr(),
+                        topic.as_ptr(),
+                        data.as_ptr(),
+                        timestamp as u64,
+                    );
+                }
+                Ok(ThingEvent::ActionRequest {
+                    request_id,
+                    thing_id,
+                    action,
+                    args,
+                    reply,
+                }) => {
+                    let request_id = request_id as u64;
+                    let _ = polled_events
+                        .replies
+                        .lock()
+                        .unwrap()
+                        .insert(request_id, reply);
+                    let thing_id = to_c_string(&thing_id);
+                    let action = to_c_string(&action);
+                    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                    let (_args, args_ptrs) = to_c_strings(&args);
+                    action_req_cb(
+                        user_data,
+                        handle,
+                        request_id,
+                        thing_id.as_ptr(),
+                        action.as_ptr(),
+                        args_ptrs.as_ptr(),
+                        args_ptrs.len(),
+                    );
+                }
+                Err(_) => break,
+            }
+        }
+        loop {
+            let state_event = polled_events.states.lock().unwrap().try_recv();
+            match state_event {
+                Ok(state_event) => {
+                    let state = to_c_string(&state_event.state);
+                    state_cb(
+                        user_data,
+                        state_event.request_id.load(Ordering::SeqCst),
+                        state.as_ptr(),
+                        opt_c_str_ptr(&state_event.result),
+                        opt_c_str_ptr(&state_event.error),
+                    );
+                }
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Register the SAFEthing on the network with its attributes, topics and actions
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_register(
+    handle: SAFEthingHandle,
+    attrs: *const FfiThingAttr,
+    attrs_len: usize,
+    topics: *const FfiTopic,
+    topics_len: usize,
+    actions: *const FfiActionDef,
+    actions_len: usize,
+) -> FfiResult {
+    ffi_call(|| {
+        let safe_thing = thing_ref(handle)?;
+        let (thing_attrs, thing_topics, thing_actions) =
+            to_thing_defs(attrs, attrs_len, topics, topics_len, actions, actions_len)?;
+        safe_thing.register(&thing_attrs, &thing_topics, &thing_actions)?;
+        Ok(())
+    })
+}
+
+/// Register the SAFEthing on the network as a private and encrypted entity,
+/// `enc_info` is generated with `safe_thing_gen_enc_info`
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_register_private(
+    handle: SAFEthingHandle,
+    attrs: *const FfiThingAttr,
+    attrs_len: usize,
+    topics: *const FfiTopic,
+    topics_len: usize,
+    actions: *const FfiActionDef,
+    actions_len: usize,
+    enc_info: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let safe_thing = thing_ref(handle)?;
+        let (thing_attrs, thing_topics, thing_actions) =
+            to_thing_defs(attrs, attrs_len, topics, topics_len, actions, actions_len)?;
+        let enc_info = from_c_str(enc_info)?;
+        safe_thing.register_private(&thing_attrs, &thing_topics, &thing_actions, &enc_info)?;
+        Ok(())
+    })
+}
+
+/// Generate new encryption info to register a private SAFEthing with
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_gen_enc_info(
+    handle: SAFEthingHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, enc_info: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let enc_info = to_c_string(&thing_ref(handle)?.gen_enc_info()?);
+        o_cb(user_data, enc_info.as_ptr());
+        Ok(())
+    })
+}
+
+/// Set the encryption info needed to access a private SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_set_thing_enc_info(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    enc_info: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let enc_info = from_c_str(enc_info)?;
+        thing_ref(handle)?.set_thing_enc_info(&thing_id, &enc_info);
+        Ok(())
+    })
+}
+
+/// Get the encryption info known for a private SAFEthing, the callback
+/// receives a null pointer if it's not known
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_thing_enc_info(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, enc_info: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        match thing_ref(handle)?.get_thing_enc_info(&thing_id) {
+            Some(enc_info) => {
+                let enc_info = to_c_string(&enc_info);
+                o_cb(user_data, enc_info.as_ptr());
+            }
+            None => o_cb(user_data, ptr::null()),
+        }
+        Ok(())
+    })
+}
+
+/// Request the encryption info of a private SAFEthing, it's only sent if this SAFEthing is
+/// authorised by it. It blocks until the request is processed, the encryption info is then
+/// set to access it and passed to the callback so it can be kept.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_fetch_thing_enc_info(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, enc_info: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let enc_info = to_c_string(&thing_ref(handle)?.fetch_thing_enc_info(&thing_id)?);
+        o_cb(user_data, enc_info.as_ptr());
+        Ok(())
+    })
+}
+
+/// Publish the SAFEthing making it available and operative in the network
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_publish(handle: SAFEthingHandle) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?.publish()?;
+        Ok(())
+    })
+}
+
+/// Disable the SAFEthing, it rejects new action requests and doesn't notify events
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_disable(handle: SAFEthingHandle) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?.disable()?;
+        Ok(())
+    })
+}
+
+/// Enable a disabled SAFEthing, restoring the status it had before it was disabled
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_enable(handle: SAFEthingHandle) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?.enable()?;
+        Ok(())
+    })
+}
+
+/// Unregister the SAFEthing, clearing its attributes, topics and actions
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_unregister(handle: SAFEthingHandle) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?.unregister()?;
+        Ok(())
+    })
+}
+
+/// Get the public signing key the SAFEthing is identified with, hex encoded
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_sign_key(
+    handle: SAFEthingHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, sign_key: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let sign_key = to_c_string(thing_ref(handle)?.sign_key());
+        o_cb(user_data, sign_key.as_ptr());
+        Ok(())
+    })
+}
+
+/// Authorise a public signing key to access the topics and actions of the SAFEthing,
+/// `access` is either `SAFE_THING_ACCESS_OWNER` or `SAFE_THING_ACCESS_GROUP`
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_authorise(
+    handle: SAFEthingHandle,
+    sign_key: *const c_char,
+    access: i32,
+) -> FfiResult {
+    ffi_call(|| {
+        let sign_key = from_c_str(sign_key)?;
+        let access = to_access_type(access)?;
+        thing_ref(handle)?.authorise(&sign_key, access)?;
+        Ok(())
+    })
+}
+
+/// Revoke the access previously authorised to a public signing key
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_revoke(
+    handle: SAFEthingHandle,
+    sign_key: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let sign_key = from_c_str(sign_key)?;
+        thing_ref(handle)?.revoke(&sign_key)?;
+        Ok(())
+    })
+}
+
+/// Set the one-time code printed on the device, its owner provides it to claim the SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_set_claim_code(
+    handle: SAFEthingHandle,
+    code: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let code = from_c_str(code)?;
+        thing_ref(handle)?.set_claim_code(&code);
+        Ok(())
+    })
+}
+
+/// Claim the ownership of a SAFEthing with the one-time code printed on it,
+/// it blocks until the claim is accepted or rejected
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_claim(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    code: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let code = from_c_str(code)?;
+        thing_ref(handle)?.claim(&thing_id, &code)?;
+        Ok(())
+    })
+}
+
+/// Get the public signing key of the owner of a SAFEthing, the callback
+/// receives a null pointer if it wasn't claimed
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_thing_owner(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, owner_key: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        match thing_ref(handle)?.get_thing_owner(&thing_id)? {
+            Some(owner_key) => {
+                let owner_key = to_c_string(&owner_key);
+                o_cb(user_data, owner_key.as_ptr());
+            }
+            None => o_cb(user_data, ptr::null()),
+        }
+        Ok(())
+    })
+}
+
+/// Create a named group, its members are granted Group access to the topics
+/// and actions it's attached to
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_create_group(
+    handle: SAFEthingHandle,
+    group: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let group = from_c_str(group)?;
+        thing_ref(handle)?.create_group(&group)?;
+        Ok(())
+    })
+}
+
+/// Delete a group of the SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_delete_group(
+    handle: SAFEthingHandle,
+    group: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let group = from_c_str(group)?;
+        thing_ref(handle)?.delete_group(&group)?;
+        Ok(())
+    })
+}
+
+/// Add a member to a group, either the ID of a SAFEthing or a public signing key
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_add_group_member(
+    handle: SAFEthingHandle,
+    group: *const c_char,
+    member: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let group = from_c_str(group)?;
+        let member = from_c_str(member)?;
+        thing_ref(handle)?.add_group_member(&group, &member)?;
+        Ok(())
+    })
+}
+
+/// Remove a member from a group
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_remove_group_member(
+    handle: SAFEthingHandle,
+    group: *const c_char,
+    member: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let group = from_c_str(group)?;
+        let member = from_c_str(member)?;
+        thing_ref(handle)?.remove_group_member(&group, &member)?;
+        Ok(())
+    })
+}
+
+/// Get the groups of a SAFEthing along with their members
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_thing_groups(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, groups: *const FfiGroup, groups_len: usize),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let groups = thing_ref(handle)?.get_thing_groups(&thing_id)?;
+
+        // All the strings need to be alive until the callback returns
+        let names: Vec<CString> = groups.keys().map(|name| to_c_string(name)).collect();
+        let members: Vec<(Vec<CString>, Vec<*const c_char>)> = groups
+            .values()
+            .map(|members| to_c_strings(&members.iter().cloned().collect::<Vec<String>>()))
+            .collect();
+        let ffi_groups: Vec<FfiGroup> = names
+            .iter()
+            .zip(members.iter())
+            .map(|(name, (_, members_ptrs))| FfiGroup {
+                name: name.as_ptr(),
+                members: members_ptrs.as_ptr(),
+                members_len: members_ptrs.len(),
+            })
+            .collect();
+
+        o_cb(user_data, ffi_groups.as_ptr(), ffi_groups.len());
+        Ok(())
+    })
+}
+
+/// Get the status of the SAFEthing, one of the `SAFE_THING_STATUS_*` values
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_status(
+    handle: SAFEthingHandle,
+    o_status: *mut i32,
+) -> FfiResult {
+    ffi_call(|| {
+        check_out_ptr(o_status)?;
+        *o_status = from_status(thing_ref(handle)?.status()?);
+        Ok(())
+    })
+}
+
+/// Get the status of any SAFEthing, it's `SAFE_THING_STATUS_NON_CONNECTED` if it was never registered
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_thing_status(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    o_status: *mut i32,
+) -> FfiResult {
+    ffi_call(|| {
+        check_out_ptr(o_status)?;
+        let thing_id = from_c_str(thing_id)?;
+        *o_status = from_status(thing_ref(handle)?.get_thing_status(&thing_id)?);
+        Ok(())
+    })
+}
+
+/// Get the list of attributes of a SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_thing_attrs(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, attrs: *const FfiThingAttr, attrs_len: usize),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let attrs = thing_ref(handle)?.get_thing_attrs(&thing_id)?;
+
+        let strings: Vec<(CString, CString)> = attrs
+            .iter()
+            .map(|a| (to_c_string(&a.attr), to_c_string(&a.value)))
+            .collect();
+        let ffi_attrs: Vec<FfiThingAttr> = attrs
+            .iter()
+            .zip(strings.iter())
+            .map(|(a, (attr, value))| FfiThingAttr {
+                attr: attr.as_ptr(),
+                value: value.as_ptr(),
+                is_dynamic: a.is_dynamic,
+                access: from_access_type(&a.access),
+            })
+            .collect();
+
+        o_cb(user_data, ffi_attrs.as_ptr(), ffi_attrs.len());
+        Ok(())
+    })
+}
+
+/// Set a new value for an attribute of the SAFEthing, or add it if it didn't exist
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_set_attr_value(
+    handle: SAFEthingHandle,
     attr: *const c_char,
-    value: *const c_char
+    value: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let attr = from_c_str(attr)?;
+        let value = from_c_str(value)?;
+        thing_ref(handle)?.set_attr_value(&attr, &value)?;
+        Ok(())
+    })
 }
 
+/// Get the list of topics supported by a SAFEthing
 #[no_mangle]
-pub extern "C" fn safe_thing_new(thing_id: *const c_char) -> SAFEthingHandle {
-    unsafe {
-        let id = CStr::from_ptr(thing_id);
-        let id_str = id.to_str().unwrap();
-        let safe_thing: Box<SAFEthing> = Box::new(SAFEthing::new(id_str).unwrap());
-        let _handle = Box::into_raw(safe_thing);
-        _handle
-    }
+pub unsafe extern "C" fn safe_thing_get_thing_topics(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, topics: *const FfiTopic, topics_len: usize),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let topics = thing_ref(handle)?.get_thing_topics(&thing_id)?;
+
+        let names: Vec<CString> = topics.iter().map(|t| to_c_string(&t.name)).collect();
+        let groups: Vec<(Vec<CString>, Vec<*const c_char>)> =
+            topics.iter().map(|t| to_c_strings(&t.groups)).collect();
+        let ffi_topics: Vec<FfiTopic> = topics
+            .iter()
+            .zip(names.iter().zip(groups.iter()))
+            .map(|(t, (name, (_, groups_ptrs)))| FfiTopic {
+                name: name.as_ptr(),
+                access: from_access_type(&t.access),
+                groups: groups_ptrs.as_ptr(),
+                groups_len: groups_ptrs.len(),
+            })
+            .collect();
+
+        o_cb(user_data, ffi_topics.as_ptr(), ffi_topics.len());
+        Ok(())
+    })
 }
 
+/// Get the list of actions supported by a SAFEthing
 #[no_mangle]
-pub extern "C" fn safe_thing_register_thing(handle: SAFEthingHandle,
-                                    attrs: *const FfiThingAttr, attrs_len: usize) -> ErrorCode {
-    unsafe {
-        let ffi_attrs = slice::from_raw_parts(attrs, attrs_len).to_vec();
-        let attr = CStr::from_ptr(ffi_attrs[0].attr);
-        let attr_str = attr.to_str().unwrap();
+pub unsafe extern "C" fn safe_thing_get_thing_actions(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, actions: *const FfiActionDef, actions_len: usize),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let actions = thing_ref(handle)?.get_thing_actions(&thing_id)?;
 
-        let value = CStr::from_ptr(ffi_attrs[0].value);
-        let value_str = value.to_str().unwrap();
+        // All the strings and structs need to be alive until the callback returns
+        let names: Vec<CString> = actions.iter().map(|a| to_c_string(&a.name)).collect();
+        let params: Vec<Vec<ParamStrings>> = actions
+            .iter()
+            .map(|a| a.params.iter().map(ParamStrings::new).collect())
+            .collect();
+        let ffi_params: Vec<Vec<FfiActionParam>> = actions
+            .iter()
+            .zip(params.iter())
+            .map(|(a, strings)| {
+                a.params
+                    .iter()
+                    .zip(strings.iter())
+                    .map(|(p, strings)| FfiActionParam {
+                        name: strings.name.as_ptr(),
+                        value_type: from_value_type(&p.value_type, &strings.options_ptrs),
+                        default_value: strings.default.as_ref().map_or(ptr::null(), |d| d.as_ptr()),
+                    })
+                    .collect()
+            })
+            .collect();
+        let outputs_options: Vec<(Vec<CString>, Vec<*const c_char>)> = actions
+            .iter()
+            .map(|a| value_type_options(a.output.as_ref()))
+            .collect();
+        let outputs: Vec<Option<FfiValueType>> = actions
+            .iter()
+            .zip(outputs_options.iter())
+            .map(|(a, (_, options_ptrs))| {
+                a.output
+                    .as_ref()
+                    .map(|output| from_value_type(output, options_ptrs))
+            })
+            .collect();
+        let groups: Vec<(Vec<CString>, Vec<*const c_char>)> =
+            actions.iter().map(|a| to_c_strings(&a.groups)).collect();
+        let ffi_actions: Vec<FfiActionDef> = actions
+            .iter()
+            .zip(names.iter().zip(ffi_params.iter().zip(outputs.iter())))
+            .zip(groups.iter())
+            .map(
+                |((a, (name, (params, output))), (_, groups_ptrs))| FfiActionDef {
+                    name: name.as_ptr(),
+                    access: from_access_type(&a.access),
+                    params: params.as_ptr(),
+                    params_len: params.len(),
+                    output: output
+                        .as_ref()
+                        .map_or(ptr::null(), |o| o as *const FfiValueType),
+                    groups: groups_ptrs.as_ptr(),
+                    groups_len: groups_ptrs.len(),
+                },
+            )
+            .collect();
 
-        let thing_attr = ThingAttr::new(attr_str, value_str);
-        (*handle).register_thing(vec![thing_attr], vec![], vec![]);
-        0
-    }
+        o_cb(user_data, ffi_actions.as_ptr(), ffi_actions.len());
+        Ok(())
+    })
 }
 
+/// Subscribe to a topic published by a SAFEthing, `filter_op` is one of
+/// the `SAFE_THING_FILTER_*` values
 #[no_mangle]
-pub extern "C" fn safe_thing_publish_thing(handle: SAFEthingHandle,
-                                            thing_id: *const c_char) -> ErrorCode {
-    unsafe {
-        let id = CStr::from_ptr(thing_id);
-        let id_str = id.to_str().unwrap();
-        (*handle).publish_thing(id_str);
-        0
-    }
+pub unsafe extern "C" fn safe_thing_subscribe_to_topic(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    topic: *const c_char,
+    filter_op: i32,
+    filter_value: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let topic = from_c_str(topic)?;
+        let filter_op = to_filter_op(filter_op)?;
+        let filter_value = from_c_str(filter_value)?;
+        thing_ref(handle)?.subscribe_to_topic(&thing_id, &topic, filter_op, &filter_value)?;
+        Ok(())
+    })
+}
+
+/// Subscribe to a dynamic attribute published by a SAFEthing, `filter_op` is one of
+/// the `SAFE_THING_FILTER_*` values
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_subscribe_to_attr(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    attr: *const c_char,
+    filter_op: i32,
+    filter_value: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let attr = from_c_str(attr)?;
+        let filter_op = to_filter_op(filter_op)?;
+        let filter_value = from_c_str(filter_value)?;
+        thing_ref(handle)?.subscribe_to_attr(&thing_id, &attr, filter_op, &filter_value)?;
+        Ok(())
+    })
+}
+
+/// Notify of an event associated to a topic
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_notify(
+    handle: SAFEthingHandle,
+    topic: *const c_char,
+    data: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let topic = from_c_str(topic)?;
+        let data = from_c_str(data)?;
+        thing_ref(handle)?.notify(&topic, &data)?;
+        Ok(())
+    })
+}
+
+/// Send an action request to a SAFEthing, its id is returned in `o_request_id`.
+/// The `state_cb` is invoked with `user_data` upon changes in the state of the request.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_action_request(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    action: *const c_char,
+    args: *const *const c_char,
+    args_len: usize,
+    user_data: *mut c_void,
+    state_cb: ActionReqStateCb,
+    o_request_id: *mut u64,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let action = from_c_str(action)?;
+        let args = from_c_str_array(args, args_len)?;
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        check_out_ptr(o_request_id)?;
+        let user_data = UserData(user_data);
+
+        let state_closure = move |state: &str, outcome: Result<Option<&ActionValue>, &str>| {
+            let state = to_c_string(state);
+            let (result, error) = outcome_c_strings(outcome);
+            state_cb(
+                user_data.0,
+                state.as_ptr(),
+                opt_c_str_ptr(&result),
+                opt_c_str_ptr(&error),
+            )
+        };
+
+        let request_id =
+            thing_ref(handle)?.action_request(&thing_id, &action, &args, state_closure)?;
+
+        *o_request_id = request_id as u64;
+        Ok(())
+    })
 }
 
+/// Send an action request from a SAFEthing created with `safe_thing_new_polled`, its id is
+/// returned in `o_request_id`. The changes in its state are queued until they are polled
+/// with `safe_thing_poll_events`.
 #[no_mangle]
-pub extern "C" fn safe_thing_delete(handle: SAFEthingHandle) {
-    unsafe {
-        if handle.is_null() {return}
+pub unsafe extern "C" fn safe_thing_action_request_polled(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    action: *const c_char,
+    args: *const *const c_char,
+    args_len: usize,
+    o_request_id: *mut u64,
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let action = from_c_str(action)?;
+        let args = from_c_str_array(args, args_len)?;
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        check_out_ptr(o_request_id)?;
+        let states_tx = Mutex::new(polled_events(handle)?.states_tx.lock().unwrap().clone());
+        let request_id_cell: Arc<AtomicU64> = Arc::default();
+        let request_id_event = Arc::clone(&request_id_cell);
+
+        let state_closure = move |state: &str, outcome: Result<Option<&ActionValue>, &str>| {
+            let (result, error) = outcome_c_strings(outcome);
+            let state_event = ActionReqStateEvent {
+                request_id: Arc::clone(&request_id_event),
+                state: state.to_string(),
+                result,
+                error,
+            };
+            // It stops monitoring the request once the SAFEthing was deleted
+            states_tx.lock().unwrap().send(state_event).is_ok()
+        };
+
+        let request_id =
+            thing_ref(handle)?.action_request(&thing_id, &action, &args, state_closure)? as u64;
+
+        request_id_cell.store(request_id, Ordering::SeqCst);
+        *o_request_id = request_id;
+        Ok(())
+    })
+}
+
+/// Get the current state of an action request sent to a SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_get_action_request_state(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    request_id: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, state: *const c_char),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let state =
+            thing_ref(handle)?.get_action_request_state(&thing_id, u128::from(request_id))?;
+
+        let state = to_c_string(&state);
+        o_cb(user_data, state.as_ptr());
+        Ok(())
+    })
+}
+
+/// Block until an action request sent to a SAFEthing reaches a final state, polling its
+/// state every `poll_interval_ms`, and pass it to `o_cb` along with its JSON encoded output
+/// and the error reported, either of them can be NULL.
+/// It fails with `SAFE_THING_ERR_TIMEOUT` if it's not final after `timeout_ms`.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_wait_action_request(
+    handle: SAFEthingHandle,
+    thing_id: *const c_char,
+    request_id: u64,
+    poll_interval_ms: u64,
+    timeout_ms: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        state: *const c_char,
+        result: *const c_char,
+        error: *const c_char,
+    ),
+) -> FfiResult {
+    ffi_call(|| {
+        let thing_id = from_c_str(thing_id)?;
+        let req_state = thing_ref(handle)?
+            .action_request_handle(&thing_id, u128::from(request_id))
+            .with_poll_interval(Duration::from_millis(poll_interval_ms))
+            .wait(Duration::from_millis(timeout_ms))?;
+
+        let state = to_c_string(&req_state.state);
+        let (result, error) = outcome_c_strings(req_state.outcome());
+        o_cb(
+            user_data,
+            state.as_ptr(),
+            opt_c_str_ptr(&result),
+            opt_c_str_ptr(&error),
+        );
+        Ok(())
+    })
+}
+
+/// Set the outcome of the action request being handled, either its output (which can be NULL)
+/// or an error if it's not NULL. It can be called from any thread, but only while the action
+/// request callback is being invoked. For the SAFEthings created with `safe_thing_new_polled`
+/// it's called once, at any time after the action request was polled, and the framework
+/// doesn't process other action requests until then.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_action_request_reply(
+    handle: SAFEthingHandle,
+    request_id: u64,
+    result: *const c_char,
+    error: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?;
+        let outcome = match from_opt_c_str(error)? {
+            Some(error) => Err(error),
+            None => Ok(from_opt_c_str(result)?),
+        };
+        let polled_reply = match POLLED_EVENTS.lock().unwrap().get(&(handle as usize)) {
+            Some(polled_events) => Some(polled_events.replies.lock().unwrap().remove(&request_id)),
+            None => None,
+        };
+        match polled_reply {
+            Some(Some(reply)) => {
+                reply.send(outcome);
+                return Ok(());
+            }
+            Some(None) => {
+                return Err(FfiError::invalid_arg(
+                    "Action request is not pending to be replied",
+                ))
+            }
+            None => (),
+        }
+        match ACTION_REPLIES
+            .lock()
+            .unwrap()
+            .get_mut(&(handle as usize, request_id))
+        {
+            Some(pending) => {
+                *pending = outcome;
+                Ok(())
+            }
+            None => Err(FfiError::invalid_arg(
+                "Action request is not being handled by the callback",
+            )),
+        }
+    })
+}
+
+/// Update the state of an action request received by the SAFEthing
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_update_action_request_state(
+    handle: SAFEthingHandle,
+    request_id: u64,
+    new_state: *const c_char,
+) -> FfiResult {
+    ffi_call(|| {
+        let new_state = from_c_str(new_state)?;
+        thing_ref(handle)?.update_action_request_state(u128::from(request_id), &new_state)?;
+        Ok(())
+    })
+}
+
+/// Stop the background workers of the SAFEthing, optionally setting its status to Disabled.
+/// The SAFEthing can be registered again afterwards to resume its operation.
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_shutdown(handle: SAFEthingHandle, disable: bool) -> FfiResult {
+    ffi_call(|| {
+        thing_ref(handle)?.shutdown(disable)?;
+        Ok(())
+    })
+}
+
+/// Free the SAFEthing instance, stopping its background workers
+#[no_mangle]
+pub unsafe extern "C" fn safe_thing_delete(handle: SAFEthingHandle) {
+    if handle.is_null() {
+        return;
+    }
+    let _ = ffi_call(|| {
+        // The action requests which weren't replied fail, thus the workers can be stopped
+        let _ = POLLED_EVENTS.lock().unwrap().remove(&(handle as usize));
         let _ = Box::from_raw(handle);
+        Ok(())
+    });
+}
+
+// Run the body of an FFI function making sure no panic unwinds into the caller,
+// and keeping the error message to be retrieved with `safe_thing_last_error_message`
+fn ffi_call<F>(f: F) -> FfiResult
+where
+    F: FnOnce() -> Result<(), FfiError>,
+{
+    let err = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return SAFE_THING_OK,
+        Ok(Err(err)) => err,
+        Err(cause) => {
+            let reason = if let Some(msg) = cause.downcast_ref::<&str>() {
+                msg.to_string()
+            } else if let Some(msg) = cause.downcast_ref::<String>() {
+                msg.clone()
+            } else {
+                "panic with unknown cause".to_string()
+            };
+            FfiError {
+                code: SAFE_THING_ERR_UNEXPECTED,
+                message: format!("[Error] Unexpected error - {}", reason),
+            }
+        }
+    };
+
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(to_c_string(&err.message)));
+    err.code
+}
+
+// Get the events queued for a SAFEthing created with `safe_thing_new_polled`
+unsafe fn polled_events(handle: SAFEthingHandle) -> Result<Arc<PolledEvents>, FfiError> {
+    thing_ref(handle)?;
+    match POLLED_EVENTS.lock().unwrap().get(&(handle as usize)) {
+        Some(polled_events) => Ok(Arc::clone(polled_events)),
+        None => Err(FfiError::invalid_arg(
+            "SAFEthing was not created with safe_thing_new_polled",
+        )),
+    }
+}
+
+// The instance is only ever accessed through a shared reference, as the same handle
+// can be used concurrently from the callbacks invoked by the background threads
+unsafe fn thing_ref<'a>(handle: SAFEthingHandle) -> Result<&'a SAFEthing, FfiError> {
+    if handle.is_null() {
+        Err(FfiError::invalid_arg("SAFEthing handle is NULL"))
+    } else {
+        Ok(&*handle)
     }
 }
 
+fn check_out_ptr<T>(ptr: *mut T) -> Result<(), FfiError> {
+    if ptr.is_null() {
+        Err(FfiError::invalid_arg("Output pointer is NULL"))
+    } else {
+        Ok(())
+    }
+}
+
+unsafe fn from_c_str(ptr: *const c_char) -> Result<String, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::invalid_arg("String pointer is NULL"));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(|s| s.to_string())
+        .map_err(|_| FfiError::invalid_arg("String is not valid UTF-8"))
+}
+
+// The output of an action is passed JSON encoded
+fn outcome_c_strings(
+    outcome: Result<Option<&ActionValue>, &str>,
+) -> (Option<CString>, Option<CString>) {
+    match outcome {
+        Ok(result) => (
+            result.map(|r| to_c_string(&serde_json::to_string(r).unwrap_or_default())),
+            None,
+        ),
+        Err(error) => (None, Some(to_c_string(error))),
+    }
+}
+
+fn opt_c_str_ptr(c_str: &Option<CString>) -> *const c_char {
+    c_str.as_ref().map_or(ptr::null(), |s| s.as_ptr())
+}
+
+// Convert the attributes, topics and actions a SAFEthing is registered with
+unsafe fn to_thing_defs(
+    attrs: *const FfiThingAttr,
+    attrs_len: usize,
+    topics: *const FfiTopic,
+    topics_len: usize,
+    actions: *const FfiActionDef,
+    actions_len: usize,
+) -> Result<(Vec<ThingAttr>, Vec<Topic>, Vec<ActionDef>), FfiError> {
+    let mut thing_attrs = vec![];
+    for attr in ffi_slice(attrs, attrs_len) {
+        thing_attrs.push(
+            ThingAttr::new(
+                &from_c_str(attr.attr)?,
+                &from_c_str(attr.value)?,
+                attr.is_dynamic,
+            )
+            .with_access(to_access_type(attr.access)?),
+        );
+    }
+
+    let mut thing_topics = vec![];
+    for topic in ffi_slice(topics, topics_len) {
+        let mut thing_topic = Topic::new(&from_c_str(topic.name)?, to_access_type(topic.access)?);
+        thing_topic.groups = from_c_str_array(topic.groups, topic.groups_len)?;
+        thing_topics.push(thing_topic);
+    }
+
+    let mut thing_actions = vec![];
+    for action in ffi_slice(actions, actions_len) {
+        let mut params = vec![];
+        for param in ffi_slice(action.params, action.params_len) {
+            let mut action_param = ActionParam::new(
+                &from_c_str(param.name)?,
+                to_value_type(&param.value_type)?,
+                None,
+            );
+            action_param.default = from_opt_c_str(param.default_value)?;
+            params.push(action_param);
+        }
+        let output = match action.output.as_ref() {
+            Some(output) => Some(to_value_type(output)?),
+            None => None,
+        };
+        let mut thing_action = ActionDef::with_types(
+            &from_c_str(action.name)?,
+            to_access_type(action.access)?,
+            &params,
+            output,
+        );
+        thing_action.groups = from_c_str_array(action.groups, action.groups_len)?;
+        thing_actions.push(thing_action);
+    }
+
+    Ok((thing_attrs, thing_topics, thing_actions))
+}
+
+unsafe fn from_opt_c_str(ptr: *const c_char) -> Result<Option<String>, FfiError> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        from_c_str(ptr).map(Some)
+    }
+}
+
+unsafe fn from_c_str_array(ptr: *const *const c_char, len: usize) -> Result<Vec<String>, FfiError> {
+    ffi_slice(ptr, len).iter().map(|s| from_c_str(*s)).collect()
+}
+
+// Strings with interior nul bytes cannot be passed to C, they are truncated
+fn to_c_string(s: &str) -> CString {
+    let bytes: Vec<u8> = s.bytes().take_while(|b| *b != 0).collect();
+    CString::new(bytes).unwrap_or_default()
+}
+
+unsafe fn ffi_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+fn to_access_type(access: i32) -> Result<AccessType, FfiError> {
+    match access {
+        SAFE_THING_ACCESS_THING => Ok(AccessType::Thing),
+        SAFE_THING_ACCESS_OWNER => Ok(AccessType::Owner),
+        SAFE_THING_ACCESS_GROUP => Ok(AccessType::Group),
+        SAFE_THING_ACCESS_ALL => Ok(AccessType::All),
+        _ => Err(FfiError::invalid_arg(&format!(
+            "Invalid access type: {}",
+            access
+        ))),
+    }
+}
+
+fn from_access_type(access: &AccessType) -> i32 {
+    match access {
+        AccessType::Thing => SAFE_THING_ACCESS_THING,
+        AccessType::Owner => SAFE_THING_ACCESS_OWNER,
+        AccessType::Group => SAFE_THING_ACCESS_GROUP,
+        AccessType::All => SAFE_THING_ACCESS_ALL,
+    }
+}
+
+fn from_status(status: Status) -> i32 {
+    match status {
+        Status::Unknown => SAFE_THING_STATUS_UNKNOWN,
+        Status::NonConnected => SAFE_THING_STATUS_NON_CONNECTED,
+        Status::Connected => SAFE_THING_STATUS_CONNECTED,
+        Status::Published => SAFE_THING_STATUS_PUBLISHED,
+        Status::Disabled => SAFE_THING_STATUS_DISABLED,
+    }
+}
+
+unsafe fn to_value_type(value_type: &FfiValueType) -> Result<ValueType, FfiError> {
+    let limit = |is_set: bool, limit: f64| if is_set { Some(limit) } else { None };
+    match value_type.kind {
+        SAFE_THING_VALUE_NUMBER => Ok(ValueType::Number {
+            min: limit(value_type.has_min, value_type.min),
+            max: limit(value_type.has_max, value_type.max),
+        }),
+        SAFE_THING_VALUE_BOOL => Ok(ValueType::Bool),
+        SAFE_THING_VALUE_STRING => Ok(ValueType::String),
+        SAFE_THING_VALUE_ENUM => Ok(ValueType::Enum(from_c_str_array(
+            value_type.options,
+            value_type.options_len,
+        )?)),
+        _ => Err(FfiError::invalid_arg(&format!(
+            "Invalid value type: {}",
+            value_type.kind
+        ))),
+    }
+}
+
+// The options pointers need to be alive while the returned struct is used
+fn from_value_type(value_type: &ValueType, options: &[*const c_char]) -> FfiValueType {
+    let (kind, min, max) = match value_type {
+        ValueType::Number { min, max } => (SAFE_THING_VALUE_NUMBER, *min, *max),
+        ValueType::Bool => (SAFE_THING_VALUE_BOOL, None, None),
+        ValueType::String => (SAFE_THING_VALUE_STRING, None, None),
+        ValueType::Enum(_) => (SAFE_THING_VALUE_ENUM, None, None),
+    };
+    FfiValueType {
+        kind,
+        has_min: min.is_some(),
+        min: min.unwrap_or_default(),
+        has_max: max.is_some(),
+        max: max.unwrap_or_default(),
+        options: options.as_ptr(),
+        options_len: options.len(),
+    }
+}
+
+// C strings of the options of an enum type, and the pointers to them
+fn value_type_options(value_type: Option<&ValueType>) -> (Vec<CString>, Vec<*const c_char>) {
+    match value_type {
+        Some(ValueType::Enum(options)) => to_c_strings(options),
+        _ => (vec![], vec![]),
+    }
+}
+
+// The pointers are only valid while the strings are alive
+fn to_c_strings(strings: &[String]) -> (Vec<CString>, Vec<*const c_char>) {
+    let c_strings: Vec<CString> = strings.iter().map(|s| to_c_string(s)).collect();
+    let ptrs = c_strings.iter().map(|s| s.as_ptr()).collect();
+    (c_strings, ptrs)
+}
+
+// C strings of an action parameter which need to be alive while it's passed to the caller
+struct ParamStrings {
+    name: CString,
+    default: Option<CString>,
+    #[allow(dead_code)]
+    options: Vec<CString>,
+    options_ptrs: Vec<*const c_char>,
+}
+
+impl ParamStrings {
+    fn new(param: &ActionParam) -> ParamStrings {
+        let (options, options_ptrs) = value_type_options(Some(&param.value_type));
+        ParamStrings {
+            name: to_c_string(&param.name),
+            default: param.default.as_ref().map(|d| to_c_string(d)),
+            options,
+            options_ptrs,
+        }
+    }
+}
+
+fn to_filter_op(filter_op: i32) -> Result<FilterOperator, FfiError> {
+    match filter_op {
+        SAFE_THING_FILTER_ANY => Ok(FilterOperator::Any),
+        SAFE_THING_FILTER_EQUAL => Ok(FilterOperator::Equal),
+        SAFE_THING_FILTER_NOT_EQUAL => Ok(FilterOperator::NotEqual),
+        SAFE_THING_FILTER_LESS_THAN => Ok(FilterOperator::LessThan),
+        SAFE_THING_FILTER_GREATER_THAN => Ok(FilterOperator::GreaterThan),
+        _ => Err(FfiError::invalid_arg(&format!(
+            "Invalid filter operator: {}",
+            filter_op
+        ))),
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn c_strings_conversion() {
+        let c_str = CString::new("some string").unwrap();
+        unsafe {
+            assert_eq!(from_c_str(c_str.as_ptr()), Ok("some string".to_string()));
+            assert_eq!(
+                from_c_str(ptr::null()).map_err(|err| err.code),
+                Err(SAFE_THING_ERR_INVALID_ARGUMENT)
+            );
+        }
+        assert_eq!(to_c_string("with\0nul").to_str().unwrap(), "with");
+    }
+
+    #[test]
+    fn c_strings_array_conversion() {
+        let strings = [CString::new("a").unwrap(), CString::new("b").unwrap()];
+        let ptrs: Vec<*const c_char> = strings.iter().map(|s| s.as_ptr()).collect();
+        unsafe {
+            let converted = from_c_str_array(ptrs.as_ptr(), ptrs.len()).unwrap();
+            assert_eq!(converted, vec!["a", "b"]);
+            assert_eq!(from_c_str_array(ptr::null(), 0), Ok(vec![]));
+        }
+    }
+
+    #[test]
+    fn invalid_enum_values() {
+        assert!(to_access_type(SAFE_THING_ACCESS_ALL).is_ok());
+        assert!(to_access_type(7).is_err());
+        assert!(to_filter_op(SAFE_THING_FILTER_GREATER_THAN).is_ok());
+        assert!(to_filter_op(-1).is_err());
+    }
+
+    #[test]
+    fn value_types_conversion() {
+        let options = [CString::new("on").unwrap(), CString::new("off").unwrap()];
+        let ptrs: Vec<*const c_char> = options.iter().map(|o| o.as_ptr()).collect();
+        let enum_type = ValueType::Enum(vec!["on".to_string(), "off".to_string()]);
+        let number_type = ValueType::Number {
+            min: Some(1.5),
+            max: None,
+        };
+        unsafe {
+            let ffi_type = from_value_type(&enum_type, &ptrs);
+            assert_eq!(ffi_type.kind, SAFE_THING_VALUE_ENUM);
+            assert_eq!(to_value_type(&ffi_type), Ok(enum_type));
+
+            let ffi_type = from_value_type(&number_type, &[]);
+            assert!(ffi_type.has_min && !ffi_type.has_max);
+            assert_eq!(to_value_type(&ffi_type), Ok(number_type));
+
+            let mut ffi_type = from_value_type(&ValueType::Bool, &[]);
+            ffi_type.kind = 9;
+            assert!(to_value_type(&ffi_type).is_err());
+        }
+    }
+
+    #[test]
+    fn error_codes_and_messages() {
+        let err = FfiError::from(Error::new(ErrorCode::Timeout, "too late"));
+        assert_eq!(err.code, SAFE_THING_ERR_TIMEOUT);
+
+        let err = FfiError::from(Error::new(ErrorCode::NoSuchData, "not found"));
+        assert_eq!(err.code, SAFE_THING_ERR_NO_SUCH_DATA);
+
+        let err = FfiError::from(Error::new(ErrorCode::AccessDenied, "not authorised"));
+        assert_eq!(err.code, SAFE_THING_ERR_ACCESS_DENIED);
+
+        let err = FfiError::from(Error::new(ErrorCode::NetworkErr, "some failure"));
+        assert_eq!(err.code, SAFE_THING_ERR_NETWORK);
+
+        assert_eq!(ffi_call(|| Err(err)), SAFE_THING_ERR_NETWORK);
+        let message = unsafe { CStr::from_ptr(safe_thing_last_error_message()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "[Error] Network error - some failure"
+        );
+
+        // a successful call doesn't reset the last error
+        assert_eq!(ffi_call(|| Ok(())), SAFE_THING_OK);
+        assert!(!safe_thing_last_error_message().is_null());
+    }
+
+    #[test]
+    fn null_arguments_are_rejected() {
+        let mut status = 0;
+        let res = unsafe { safe_thing_status(ptr::null_mut(), &mut status) };
+        assert_eq!(res, SAFE_THING_ERR_INVALID_ARGUMENT);
+        let message = unsafe { CStr::from_ptr(safe_thing_last_error_message()) };
+        assert_eq!(message.to_str().unwrap(), "SAFEthing handle is NULL");
+    }
+
     #[test]
-    fn it_works() {
-//        hello_rust(5);
+    fn panics_do_not_unwind() {
+        let res = ffi_call(|| panic!("something went wrong"));
+        assert_eq!(res, SAFE_THING_ERR_UNEXPECTED);
+        let message = unsafe { CStr::from_ptr(safe_thing_last_error_message()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "[Error] Unexpected error - something went wrong"
+        );
     }
 }